@@ -0,0 +1,30 @@
+use std::env;
+use std::path::PathBuf;
+
+/// When set, the generated header also replaces the checked-in `include/nail.h`.
+const UPDATE_HEADER_VAR: &str = "NAIL_FFI_UPDATE_HEADER";
+
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("failed to read cbindgen.toml");
+
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed={UPDATE_HEADER_VAR}");
+
+    let bindings = cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate C bindings");
+
+    // the build only writes to OUT_DIR, so that building
+    // the crate never modifies the source tree
+    bindings.write_to_file(out_dir.join("nail.h"));
+
+    if env::var_os(UPDATE_HEADER_VAR).is_some() {
+        bindings.write_to_file(crate_dir.join("include").join("nail.h"));
+    }
+}
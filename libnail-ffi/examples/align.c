@@ -0,0 +1,74 @@
+/*
+ * Align the first profile of a HMMER3 file to each sequence of a fasta file.
+ *
+ *   $ cargo build --release -p libnail-ffi
+ *   $ cc -o align libnail-ffi/examples/align.c -Ilibnail-ffi/include \
+ *       -Ltarget/release -lnail_ffi -lpthread -ldl -lm
+ *   $ LD_LIBRARY_PATH=target/release ./align fixtures/query.hmm fixtures/target.fa
+ */
+#include <stdio.h>
+
+#include "nail.h"
+
+int main(int argc, char **argv) {
+  if (argc != 3) {
+    fprintf(stderr, "usage: %s <query.hmm> <target.fa>\n", argv[0]);
+    return 1;
+  }
+
+  NailProfiles *profiles = nail_profiles_from_p7hmm(argv[1]);
+  if (profiles == NULL) {
+    fprintf(stderr, "error: %s\n", nail_last_error());
+    return 1;
+  }
+
+  NailSequences *targets = nail_sequences_from_fasta(argv[2]);
+  if (targets == NULL) {
+    fprintf(stderr, "error: %s\n", nail_last_error());
+    nail_profiles_free(profiles);
+    return 1;
+  }
+
+  const NailProfile *profile = nail_profiles_get(profiles, 0);
+
+  NailSearchParams params = nail_search_params_default();
+  params.target_database_size = nail_sequences_len(targets);
+
+  for (size_t i = 0; i < nail_sequences_len(targets); i++) {
+    const NailSequence *target = nail_sequences_get(targets, i);
+
+    /* without a seed, the full DP matrix is aligned */
+    NailAlignment *alignment = NULL;
+    NailStatus status = nail_align(profile, target, NULL, &params, &alignment);
+
+    if (status == NAIL_STATUS_ERROR) {
+      fprintf(stderr, "error: %s\n", nail_last_error());
+      continue;
+    } else if (status == NAIL_STATUS_FILTERED) {
+      continue;
+    }
+
+    NailScores scores = nail_alignment_scores(alignment);
+    NailBoundaries bounds = nail_alignment_boundaries(alignment);
+
+    size_t trace_len = 0;
+    const NailTraceStep *trace = nail_alignment_trace(alignment, &trace_len);
+
+    size_t matches = 0;
+    for (size_t j = 0; j < trace_len; j++) {
+      if (trace[j].state == NAIL_STATE_M) {
+        matches++;
+      }
+    }
+
+    printf("%s\t%s\t%zu\t%zu\t%zu\t%zu\t%.1f\t%.1e\t%zu\n", nail_sequence_name(target),
+           nail_profile_name(profile), bounds.target_start, bounds.target_end,
+           bounds.profile_start, bounds.profile_end, scores.score, scores.e_value, matches);
+
+    nail_alignment_free(alignment);
+  }
+
+  nail_sequences_free(targets);
+  nail_profiles_free(profiles);
+  return 0;
+}
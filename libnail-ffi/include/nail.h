@@ -0,0 +1,418 @@
+#ifndef NAIL_H
+#define NAIL_H
+
+/* This file is generated by cbindgen from libnail-ffi/src, do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The trace state of a match between a profile position and a target residue.
+ */
+#define NAIL_STATE_M 1
+
+/**
+ * The trace state of a profile position that is deleted from the target.
+ */
+#define NAIL_STATE_D 2
+
+/**
+ * The trace state of a target residue that is inserted after a profile position.
+ */
+#define NAIL_STATE_I 3
+
+typedef enum NailStatus {
+  /**
+   * The pair was aligned, and the alignment was written to the output pointer
+   */
+  NAIL_STATUS_OK = 0,
+  /**
+   * The pair was filtered by the pipeline, or its E-value was above the threshold
+   */
+  NAIL_STATUS_FILTERED = 1,
+  /**
+   * The alignment failed; see `nail_last_error()`
+   */
+  NAIL_STATUS_ERROR = -1,
+} NailStatus;
+
+/**
+ * An alignment of a profile to a target sequence.
+ */
+typedef struct NailAlignment NailAlignment;
+
+/**
+ * A search profile.
+ */
+typedef struct NailProfile NailProfile;
+
+/**
+ * The profiles read from a HMMER3 file.
+ */
+typedef struct NailProfiles NailProfiles;
+
+/**
+ * An amino acid sequence.
+ */
+typedef struct NailSequence NailSequence;
+
+/**
+ * The sequences read from a fasta file.
+ */
+typedef struct NailSequences NailSequences;
+
+/**
+ * The parameters of cloud search and alignment.
+ *
+ * Use `nail_search_params_default()` for the defaults of `nail search`.
+ */
+typedef struct NailSearchParams {
+  /**
+   * The local score pruning threshold of cloud search
+   */
+  float alpha;
+  /**
+   * The global score pruning threshold of cloud search
+   */
+  float beta;
+  /**
+   * The minimum number of anti-diagonals computed by cloud search
+   */
+  size_t gamma;
+  /**
+   * Skip cloud search and fill the full DP matrix
+   */
+  bool full_dp;
+  double cloud_p_value_threshold;
+  double forward_p_value_threshold;
+  double e_value_threshold;
+  /**
+   * The number of comparisons used for E-value calculation
+   */
+  size_t target_database_size;
+  /**
+   * Compute the composition bias score correction
+   */
+  bool null_two;
+} NailSearchParams;
+
+/**
+ * The rough position of an alignment, which cloud search starts from.
+ *
+ * Positions are 1-based and inclusive.
+ */
+typedef struct NailSeed {
+  size_t target_start;
+  size_t target_end;
+  size_t profile_start;
+  size_t profile_end;
+} NailSeed;
+
+/**
+ * The scores of an alignment.
+ */
+typedef struct NailScores {
+  /**
+   * The final bit score, including the composition bias correction
+   */
+  float score;
+  float forward_score;
+  /**
+   * The composition bias score correction; this is 0.0 if it wasn't computed
+   */
+  float bias;
+  double p_value;
+  double e_value;
+  /**
+   * The fraction of the full DP matrix that was computed
+   */
+  float cell_fraction;
+} NailScores;
+
+/**
+ * The coordinates of an alignment, which are 1-based and inclusive.
+ */
+typedef struct NailBoundaries {
+  /**
+   * The number of columns in the alignment
+   */
+  size_t length;
+  size_t profile_start;
+  size_t profile_end;
+  size_t target_start;
+  size_t target_end;
+} NailBoundaries;
+
+/**
+ * A step of an alignment's trace.
+ */
+typedef struct NailTraceStep {
+  /**
+   * One of `NAIL_STATE_M`, `NAIL_STATE_D`, or `NAIL_STATE_I`
+   */
+  uint8_t state;
+  size_t profile_idx;
+  size_t target_idx;
+  /**
+   * The posterior probability of the step
+   */
+  float posterior;
+} NailTraceStep;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * The defaults of `nail search`, with a target database size of 1.
+ */
+struct NailSearchParams nail_search_params_default(void);
+
+/**
+ * Align a profile to a target sequence with cloud search and sparse Forward/Backward.
+ *
+ * If `seed` is null, the full DP matrix is aligned. If `params` is null, the
+ * defaults of `nail_search_params_default()` are used. On `NAIL_STATUS_OK`, the
+ * alignment is written to `out` and must be freed with `nail_alignment_free()`.
+ *
+ * # Safety
+ *
+ * `profile` and `target` must be valid, `seed` and `params` must be null or
+ * valid, and `out` must be a valid pointer to write the alignment to.
+ */
+enum NailStatus nail_align(const struct NailProfile *profile,
+                           const struct NailSequence *target,
+                           const struct NailSeed *seed,
+                           const struct NailSearchParams *params,
+                           struct NailAlignment **out);
+
+/**
+ * Free an alignment returned by `nail_align()`.
+ *
+ * # Safety
+ *
+ * `alignment` must be null or have been returned by `nail_align()`.
+ */
+void nail_alignment_free(struct NailAlignment *alignment);
+
+/**
+ * The scores of an alignment.
+ *
+ * # Safety
+ *
+ * `alignment` must be a valid alignment.
+ */
+struct NailScores nail_alignment_scores(const struct NailAlignment *alignment);
+
+/**
+ * The coordinates of an alignment.
+ *
+ * # Safety
+ *
+ * `alignment` must be a valid alignment.
+ */
+struct NailBoundaries nail_alignment_boundaries(const struct NailAlignment *alignment);
+
+/**
+ * The core (M, D, I) steps of an alignment's trace.
+ *
+ * The number of steps is written to `len`. The steps are owned by the alignment.
+ *
+ * # Safety
+ *
+ * `alignment` must be a valid alignment, and `len` must be a valid pointer.
+ */
+const struct NailTraceStep *nail_alignment_trace(const struct NailAlignment *alignment,
+                                                 size_t *len);
+
+/**
+ * The profile line of the alignment display, which is owned by the alignment.
+ *
+ * # Safety
+ *
+ * `alignment` must be a valid alignment.
+ */
+const char *nail_alignment_profile_string(const struct NailAlignment *alignment);
+
+/**
+ * The match line of the alignment display, which is owned by the alignment.
+ *
+ * # Safety
+ *
+ * `alignment` must be a valid alignment.
+ */
+const char *nail_alignment_middle_string(const struct NailAlignment *alignment);
+
+/**
+ * The target line of the alignment display, which is owned by the alignment.
+ *
+ * # Safety
+ *
+ * `alignment` must be a valid alignment.
+ */
+const char *nail_alignment_target_string(const struct NailAlignment *alignment);
+
+/**
+ * The posterior probability line of the alignment display, which is owned by the alignment.
+ *
+ * # Safety
+ *
+ * `alignment` must be a valid alignment.
+ */
+const char *nail_alignment_posterior_string(const struct NailAlignment *alignment);
+
+/**
+ * The message of the last error that occurred on the calling thread,
+ * or null if there hasn't been one.
+ *
+ * The string is owned by libnail, and is valid until the next
+ * call on the same thread that fails.
+ */
+const char *nail_last_error(void);
+
+/**
+ * Read the profiles in a HMMER3 file.
+ *
+ * Returns null on failure. The profiles must be freed with `nail_profiles_free()`.
+ *
+ * # Safety
+ *
+ * `path` must be a nul-terminated string.
+ */
+struct NailProfiles *nail_profiles_from_p7hmm(const char *path);
+
+/**
+ * The number of profiles.
+ *
+ * # Safety
+ *
+ * `profiles` must have been returned by `nail_profiles_from_p7hmm()`.
+ */
+size_t nail_profiles_len(const struct NailProfiles *profiles);
+
+/**
+ * The profile at `idx`, or null if `idx` is out of bounds.
+ *
+ * The profile is owned by `profiles`.
+ *
+ * # Safety
+ *
+ * `profiles` must have been returned by `nail_profiles_from_p7hmm()`.
+ */
+const struct NailProfile *nail_profiles_get(const struct NailProfiles *profiles, size_t idx);
+
+/**
+ * Free profiles returned by `nail_profiles_from_p7hmm()`.
+ *
+ * # Safety
+ *
+ * `profiles` must be null or have been returned by `nail_profiles_from_p7hmm()`,
+ * and none of its profiles may be used afterwards.
+ */
+void nail_profiles_free(struct NailProfiles *profiles);
+
+/**
+ * The name of a profile, which is owned by the profile.
+ *
+ * # Safety
+ *
+ * `profile` must be a valid profile.
+ */
+const char *nail_profile_name(const struct NailProfile *profile);
+
+/**
+ * The number of model positions in a profile.
+ *
+ * # Safety
+ *
+ * `profile` must be a valid profile.
+ */
+size_t nail_profile_length(const struct NailProfile *profile);
+
+/**
+ * Read the amino acid sequences in a fasta file.
+ *
+ * Returns null on failure. The sequences must be freed with `nail_sequences_free()`.
+ *
+ * # Safety
+ *
+ * `path` must be a nul-terminated string.
+ */
+struct NailSequences *nail_sequences_from_fasta(const char *path);
+
+/**
+ * The number of sequences.
+ *
+ * # Safety
+ *
+ * `sequences` must have been returned by `nail_sequences_from_fasta()`.
+ */
+size_t nail_sequences_len(const struct NailSequences *sequences);
+
+/**
+ * The sequence at `idx`, or null if `idx` is out of bounds.
+ *
+ * The sequence is owned by `sequences`.
+ *
+ * # Safety
+ *
+ * `sequences` must have been returned by `nail_sequences_from_fasta()`.
+ */
+const struct NailSequence *nail_sequences_get(const struct NailSequences *sequences, size_t idx);
+
+/**
+ * Free sequences returned by `nail_sequences_from_fasta()`.
+ *
+ * # Safety
+ *
+ * `sequences` must be null or have been returned by `nail_sequences_from_fasta()`,
+ * and none of its sequences may be used afterwards.
+ */
+void nail_sequences_free(struct NailSequences *sequences);
+
+/**
+ * Create a sequence from a name and a string of amino acid residues.
+ *
+ * Returns null on failure. The sequence must be freed with `nail_sequence_free()`.
+ *
+ * # Safety
+ *
+ * `name` and `residues` must be nul-terminated strings.
+ */
+struct NailSequence *nail_sequence_new(const char *name, const char *residues);
+
+/**
+ * Free a sequence returned by `nail_sequence_new()`.
+ *
+ * # Safety
+ *
+ * `sequence` must be null or have been returned by `nail_sequence_new()`.
+ */
+void nail_sequence_free(struct NailSequence *sequence);
+
+/**
+ * The name of a sequence, which is owned by the sequence.
+ *
+ * # Safety
+ *
+ * `sequence` must be a valid sequence.
+ */
+const char *nail_sequence_name(const struct NailSequence *sequence);
+
+/**
+ * The number of residues in a sequence.
+ *
+ * # Safety
+ *
+ * `sequence` must be a valid sequence.
+ */
+size_t nail_sequence_length(const struct NailSequence *sequence);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NAIL_H */
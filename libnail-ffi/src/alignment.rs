@@ -0,0 +1,369 @@
+use std::ffi::{c_char, CString};
+use std::ptr;
+
+use libnail::{
+    align::{
+        structs::{Alignment, DisplayStrings, Seed, Trace},
+        CloudSearchParams,
+    },
+    search::{align_pair, SearchParams},
+};
+
+use crate::error::guard;
+use crate::structs::{NailProfile, NailSequence};
+
+// these are literals so that cbindgen can write them to the header
+/// The trace state of a match between a profile position and a target residue.
+pub const NAIL_STATE_M: u8 = 1;
+/// The trace state of a profile position that is deleted from the target.
+pub const NAIL_STATE_D: u8 = 2;
+/// The trace state of a target residue that is inserted after a profile position.
+pub const NAIL_STATE_I: u8 = 3;
+
+const _: () = assert!(
+    NAIL_STATE_M as usize == Trace::M_STATE
+        && NAIL_STATE_D as usize == Trace::D_STATE
+        && NAIL_STATE_I as usize == Trace::I_STATE
+);
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum NailStatus {
+    /// The pair was aligned, and the alignment was written to the output pointer
+    Ok = 0,
+    /// The pair was filtered by the pipeline, or its E-value was above the threshold
+    Filtered = 1,
+    /// The alignment failed; see `nail_last_error()`
+    Error = -1,
+}
+
+/// The rough position of an alignment, which cloud search starts from.
+///
+/// Positions are 1-based and inclusive.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct NailSeed {
+    pub target_start: usize,
+    pub target_end: usize,
+    pub profile_start: usize,
+    pub profile_end: usize,
+}
+
+/// The parameters of cloud search and alignment.
+///
+/// Use `nail_search_params_default()` for the defaults of `nail search`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct NailSearchParams {
+    /// The local score pruning threshold of cloud search
+    pub alpha: f32,
+    /// The global score pruning threshold of cloud search
+    pub beta: f32,
+    /// The minimum number of anti-diagonals computed by cloud search
+    pub gamma: usize,
+    /// Skip cloud search and fill the full DP matrix
+    pub full_dp: bool,
+    pub cloud_p_value_threshold: f64,
+    pub forward_p_value_threshold: f64,
+    pub e_value_threshold: f64,
+    /// The number of comparisons used for E-value calculation
+    pub target_database_size: usize,
+    /// Compute the composition bias score correction
+    pub null_two: bool,
+}
+
+impl From<&NailSearchParams> for SearchParams {
+    fn from(params: &NailSearchParams) -> Self {
+        SearchParams {
+            cloud_search: CloudSearchParams {
+                gamma: params.gamma,
+                alpha: params.alpha,
+                beta: params.beta,
+            },
+            full_dp: params.full_dp,
+            cloud_p_value_threshold: params.cloud_p_value_threshold,
+            forward_p_value_threshold: params.forward_p_value_threshold,
+            e_value_threshold: params.e_value_threshold,
+            target_database_size: Some(params.target_database_size),
+            null_two: params.null_two,
+            // the trace steps are exposed through nail_alignment_trace()
+            keep_trace: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// The scores of an alignment.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct NailScores {
+    /// The final bit score, including the composition bias correction
+    pub score: f32,
+    pub forward_score: f32,
+    /// The composition bias score correction; this is 0.0 if it wasn't computed
+    pub bias: f32,
+    pub p_value: f64,
+    pub e_value: f64,
+    /// The fraction of the full DP matrix that was computed
+    pub cell_fraction: f32,
+}
+
+/// The coordinates of an alignment, which are 1-based and inclusive.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NailBoundaries {
+    /// The number of columns in the alignment
+    pub length: usize,
+    pub profile_start: usize,
+    pub profile_end: usize,
+    pub target_start: usize,
+    pub target_end: usize,
+}
+
+/// A step of an alignment's trace.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct NailTraceStep {
+    /// One of `NAIL_STATE_M`, `NAIL_STATE_D`, or `NAIL_STATE_I`
+    pub state: u8,
+    pub profile_idx: usize,
+    pub target_idx: usize,
+    /// The posterior probability of the step
+    pub posterior: f32,
+}
+
+/// An alignment of a profile to a target sequence.
+pub struct NailAlignment {
+    alignment: Alignment,
+    trace: Vec<NailTraceStep>,
+    profile_string: CString,
+    middle_string: CString,
+    target_string: CString,
+    posterior_string: CString,
+}
+
+impl NailAlignment {
+    fn new(alignment: Alignment) -> anyhow::Result<Self> {
+        let trace = alignment
+            .trace
+            .iter()
+            .flatten()
+            .map(|step| NailTraceStep {
+                state: step.state as u8,
+                profile_idx: step.profile_idx,
+                target_idx: step.target_idx,
+                posterior: step.posterior,
+            })
+            .collect();
+
+        let display = |f: fn(&DisplayStrings) -> &String| {
+            let string = alignment
+                .display_strings
+                .as_ref()
+                .map(|d| f(d).clone())
+                .unwrap_or_default();
+            CString::new(string)
+        };
+
+        Ok(Self {
+            trace,
+            profile_string: display(|d| &d.profile_string)?,
+            middle_string: display(|d| &d.middle_string)?,
+            target_string: display(|d| &d.target_string)?,
+            posterior_string: display(|d| &d.posterior_string)?,
+            alignment,
+        })
+    }
+}
+
+/// The defaults of `nail search`, with a target database size of 1.
+#[no_mangle]
+pub extern "C" fn nail_search_params_default() -> NailSearchParams {
+    let params = SearchParams::default();
+    NailSearchParams {
+        alpha: params.cloud_search.alpha,
+        beta: params.cloud_search.beta,
+        gamma: params.cloud_search.gamma,
+        full_dp: params.full_dp,
+        cloud_p_value_threshold: params.cloud_p_value_threshold,
+        forward_p_value_threshold: params.forward_p_value_threshold,
+        e_value_threshold: params.e_value_threshold,
+        target_database_size: 1,
+        null_two: params.null_two,
+    }
+}
+
+/// Align a profile to a target sequence with cloud search and sparse Forward/Backward.
+///
+/// If `seed` is null, the full DP matrix is aligned. If `params` is null, the
+/// defaults of `nail_search_params_default()` are used. On `NAIL_STATUS_OK`, the
+/// alignment is written to `out` and must be freed with `nail_alignment_free()`.
+///
+/// # Safety
+///
+/// `profile` and `target` must be valid, `seed` and `params` must be null or
+/// valid, and `out` must be a valid pointer to write the alignment to.
+#[no_mangle]
+pub unsafe extern "C" fn nail_align(
+    profile: *const NailProfile,
+    target: *const NailSequence,
+    seed: *const NailSeed,
+    params: *const NailSearchParams,
+    out: *mut *mut NailAlignment,
+) -> NailStatus {
+    let profile = &(*profile).profile;
+    let target = &(*target).sequence;
+
+    let seed = seed.as_ref().map(|seed| Seed {
+        target_start: seed.target_start,
+        target_end: seed.target_end,
+        profile_start: seed.profile_start,
+        profile_end: seed.profile_end,
+        score: 0.0,
+    });
+
+    let params = match params.as_ref() {
+        Some(params) => SearchParams::from(params),
+        None => SearchParams::from(&nail_search_params_default()),
+    };
+
+    let alignment = guard(|| {
+        align_pair(profile, target, seed.as_ref(), &params)?
+            .map(NailAlignment::new)
+            .transpose()
+    });
+
+    match alignment {
+        Some(Some(alignment)) => {
+            *out = Box::into_raw(Box::new(alignment));
+            NailStatus::Ok
+        }
+        Some(None) => {
+            *out = ptr::null_mut();
+            NailStatus::Filtered
+        }
+        None => {
+            *out = ptr::null_mut();
+            NailStatus::Error
+        }
+    }
+}
+
+/// Free an alignment returned by `nail_align()`.
+///
+/// # Safety
+///
+/// `alignment` must be null or have been returned by `nail_align()`.
+#[no_mangle]
+pub unsafe extern "C" fn nail_alignment_free(alignment: *mut NailAlignment) {
+    if !alignment.is_null() {
+        drop(Box::from_raw(alignment));
+    }
+}
+
+/// The scores of an alignment.
+///
+/// # Safety
+///
+/// `alignment` must be a valid alignment.
+#[no_mangle]
+pub unsafe extern "C" fn nail_alignment_scores(alignment: *const NailAlignment) -> NailScores {
+    let alignment = &(*alignment).alignment;
+    NailScores {
+        score: alignment.scores.bit_score.value(),
+        forward_score: alignment.scores.forward_score.value(),
+        bias: alignment
+            .scores
+            .null_two_score
+            .map_or(0.0, |score| score.value()),
+        p_value: alignment.scores.p_value,
+        e_value: alignment.scores.e_value,
+        cell_fraction: alignment.cell_stats.as_ref().map_or(0.0, |c| c.fraction),
+    }
+}
+
+/// The coordinates of an alignment.
+///
+/// # Safety
+///
+/// `alignment` must be a valid alignment.
+#[no_mangle]
+pub unsafe extern "C" fn nail_alignment_boundaries(
+    alignment: *const NailAlignment,
+) -> NailBoundaries {
+    match (*alignment).alignment.boundaries {
+        Some(ref b) => NailBoundaries {
+            length: b.length,
+            profile_start: b.profile_start,
+            profile_end: b.profile_end,
+            target_start: b.target_start,
+            target_end: b.target_end,
+        },
+        None => NailBoundaries::default(),
+    }
+}
+
+/// The core (M, D, I) steps of an alignment's trace.
+///
+/// The number of steps is written to `len`. The steps are owned by the alignment.
+///
+/// # Safety
+///
+/// `alignment` must be a valid alignment, and `len` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nail_alignment_trace(
+    alignment: *const NailAlignment,
+    len: *mut usize,
+) -> *const NailTraceStep {
+    let trace = &(*alignment).trace;
+    *len = trace.len();
+    trace.as_ptr()
+}
+
+/// The profile line of the alignment display, which is owned by the alignment.
+///
+/// # Safety
+///
+/// `alignment` must be a valid alignment.
+#[no_mangle]
+pub unsafe extern "C" fn nail_alignment_profile_string(
+    alignment: *const NailAlignment,
+) -> *const c_char {
+    (*alignment).profile_string.as_ptr()
+}
+
+/// The match line of the alignment display, which is owned by the alignment.
+///
+/// # Safety
+///
+/// `alignment` must be a valid alignment.
+#[no_mangle]
+pub unsafe extern "C" fn nail_alignment_middle_string(
+    alignment: *const NailAlignment,
+) -> *const c_char {
+    (*alignment).middle_string.as_ptr()
+}
+
+/// The target line of the alignment display, which is owned by the alignment.
+///
+/// # Safety
+///
+/// `alignment` must be a valid alignment.
+#[no_mangle]
+pub unsafe extern "C" fn nail_alignment_target_string(
+    alignment: *const NailAlignment,
+) -> *const c_char {
+    (*alignment).target_string.as_ptr()
+}
+
+/// The posterior probability line of the alignment display, which is owned by the alignment.
+///
+/// # Safety
+///
+/// `alignment` must be a valid alignment.
+#[no_mangle]
+pub unsafe extern "C" fn nail_alignment_posterior_string(
+    alignment: *const NailAlignment,
+) -> *const c_char {
+    (*alignment).posterior_string.as_ptr()
+}
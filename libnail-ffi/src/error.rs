@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, UnwindSafe};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn set_last_error(err: anyhow::Error) {
+    // an error message can't contain a nul byte, so we drop any
+    let message = format!("{err:#}").replace('\0', "");
+    let message = CString::new(message).expect("error message contains a nul byte");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run a closure, catching any panic so that it doesn't unwind across the C ABI.
+///
+/// Returns `None` and records the error if the closure fails or panics.
+pub(crate) fn guard<T>(f: impl FnOnce() -> anyhow::Result<T> + UnwindSafe) -> Option<T> {
+    let result = match catch_unwind(f) {
+        Ok(result) => result,
+        Err(panic) => {
+            let message = match panic.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => match panic.downcast_ref::<String>() {
+                    Some(message) => message.clone(),
+                    None => "unknown panic".to_string(),
+                },
+            };
+            Err(anyhow::anyhow!("libnail panicked: {message}"))
+        }
+    };
+
+    match result {
+        Ok(value) => Some(value),
+        Err(err) => {
+            set_last_error(err);
+            None
+        }
+    }
+}
+
+/// Read a path or other string argument.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a nul-terminated string.
+pub(crate) unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> anyhow::Result<&'a str> {
+    if ptr.is_null() {
+        anyhow::bail!("{name} is null");
+    }
+
+    Ok(CStr::from_ptr(ptr).to_str()?)
+}
+
+/// The message of the last error that occurred on the calling thread,
+/// or null if there hasn't been one.
+///
+/// The string is owned by libnail, and is valid until the next
+/// call on the same thread that fails.
+#[no_mangle]
+pub extern "C" fn nail_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match *last.borrow() {
+        Some(ref message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
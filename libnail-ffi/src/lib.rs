@@ -0,0 +1,86 @@
+//! A C ABI for libnail.
+//!
+//! Profiles, sequences, and alignments are opaque handles that are created
+//! and freed by libnail. Functions that can fail return null or
+//! `NAIL_STATUS_ERROR`, and the error is available from `nail_last_error()`.
+//! The C header is generated by cbindgen and checked in at `include/nail.h`;
+//! building with `NAIL_FFI_UPDATE_HEADER` set regenerates it.
+
+mod alignment;
+mod error;
+mod structs;
+
+pub use alignment::*;
+pub use error::nail_last_error;
+pub use structs::*;
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+
+    use super::*;
+
+    #[test]
+    fn test_align() {
+        unsafe {
+            let path = CString::new("../fixtures/query.hmm").unwrap();
+            let profiles = nail_profiles_from_p7hmm(path.as_ptr());
+            assert!(!profiles.is_null());
+            assert!(nail_profiles_len(profiles) > 0);
+
+            let path = CString::new("../fixtures/target.fa").unwrap();
+            let targets = nail_sequences_from_fasta(path.as_ptr());
+            assert!(!targets.is_null());
+            assert_eq!(nail_sequences_len(targets), 100);
+
+            let profile = nail_profiles_get(profiles, 0);
+            let target = nail_sequences_get(targets, 0);
+            assert!(nail_sequences_get(targets, 100).is_null());
+
+            let mut params = nail_search_params_default();
+            params.full_dp = true;
+            params.target_database_size = nail_sequences_len(targets);
+
+            let mut alignment = ptr::null_mut();
+            let status = nail_align(profile, target, ptr::null(), &params, &mut alignment);
+            assert_eq!(status, NailStatus::Ok);
+
+            let scores = nail_alignment_scores(alignment);
+            assert!(scores.e_value <= params.e_value_threshold);
+
+            let bounds = nail_alignment_boundaries(alignment);
+            assert!(bounds.target_end <= nail_sequence_length(target));
+            assert!(bounds.profile_end <= nail_profile_length(profile));
+
+            let mut len = 0;
+            let trace = nail_alignment_trace(alignment, &mut len);
+            let trace = std::slice::from_raw_parts(trace, len);
+            assert_eq!(trace.len(), bounds.length);
+            assert_eq!(trace[0].state, NAIL_STATE_M);
+            assert_eq!(trace[0].target_idx, bounds.target_start);
+
+            let profile_string = CStr::from_ptr(nail_alignment_profile_string(alignment));
+            assert_eq!(profile_string.to_bytes().len(), bounds.length);
+
+            nail_alignment_free(alignment);
+            nail_sequences_free(targets);
+            nail_profiles_free(profiles);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let path = CString::new("does-not-exist.hmm").unwrap();
+            assert!(nail_profiles_from_p7hmm(path.as_ptr()).is_null());
+
+            let error = CStr::from_ptr(nail_last_error()).to_str().unwrap();
+            assert!(error.contains("does-not-exist.hmm"));
+
+            let name = CString::new("bad").unwrap();
+            let residues = CString::new("MKV*1").unwrap();
+            assert!(nail_sequence_new(name.as_ptr(), residues.as_ptr()).is_null());
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::ffi::{c_char, CString};
+use std::fs::File;
+use std::ptr;
+
+use anyhow::Context;
+use libnail::structs::{Hmm, Profile, Sequence};
+
+use crate::error::{guard, str_arg};
+
+/// A search profile.
+pub struct NailProfile {
+    pub(crate) profile: Profile,
+    name: CString,
+}
+
+impl NailProfile {
+    fn new(profile: Profile) -> anyhow::Result<Self> {
+        Ok(Self {
+            name: CString::new(profile.name.clone())?,
+            profile,
+        })
+    }
+}
+
+/// The profiles read from a HMMER3 file.
+pub struct NailProfiles {
+    profiles: Vec<NailProfile>,
+}
+
+/// An amino acid sequence.
+pub struct NailSequence {
+    pub(crate) sequence: Sequence,
+    name: CString,
+}
+
+impl NailSequence {
+    fn new(sequence: Sequence) -> anyhow::Result<Self> {
+        Ok(Self {
+            name: CString::new(sequence.name.clone())?,
+            sequence,
+        })
+    }
+}
+
+/// The sequences read from a fasta file.
+pub struct NailSequences {
+    sequences: Vec<NailSequence>,
+}
+
+/// Read the profiles in a HMMER3 file.
+///
+/// Returns null on failure. The profiles must be freed with `nail_profiles_free()`.
+///
+/// # Safety
+///
+/// `path` must be a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nail_profiles_from_p7hmm(path: *const c_char) -> *mut NailProfiles {
+    let profiles = guard(|| {
+        let path = str_arg(path, "path")?;
+        let file = File::open(path).context(format!("failed to open HMM file: {path}"))?;
+        let profiles = Hmm::from_p7hmm(file)?
+            .iter()
+            .map(|hmm| NailProfile::new(Profile::new(hmm)))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(NailProfiles { profiles })
+    });
+
+    match profiles {
+        Some(profiles) => Box::into_raw(Box::new(profiles)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The number of profiles.
+///
+/// # Safety
+///
+/// `profiles` must have been returned by `nail_profiles_from_p7hmm()`.
+#[no_mangle]
+pub unsafe extern "C" fn nail_profiles_len(profiles: *const NailProfiles) -> usize {
+    (*profiles).profiles.len()
+}
+
+/// The profile at `idx`, or null if `idx` is out of bounds.
+///
+/// The profile is owned by `profiles`.
+///
+/// # Safety
+///
+/// `profiles` must have been returned by `nail_profiles_from_p7hmm()`.
+#[no_mangle]
+pub unsafe extern "C" fn nail_profiles_get(
+    profiles: *const NailProfiles,
+    idx: usize,
+) -> *const NailProfile {
+    let profiles = &*profiles;
+    match profiles.profiles.get(idx) {
+        Some(profile) => profile,
+        None => ptr::null(),
+    }
+}
+
+/// Free profiles returned by `nail_profiles_from_p7hmm()`.
+///
+/// # Safety
+///
+/// `profiles` must be null or have been returned by `nail_profiles_from_p7hmm()`,
+/// and none of its profiles may be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn nail_profiles_free(profiles: *mut NailProfiles) {
+    if !profiles.is_null() {
+        drop(Box::from_raw(profiles));
+    }
+}
+
+/// The name of a profile, which is owned by the profile.
+///
+/// # Safety
+///
+/// `profile` must be a valid profile.
+#[no_mangle]
+pub unsafe extern "C" fn nail_profile_name(profile: *const NailProfile) -> *const c_char {
+    (*profile).name.as_ptr()
+}
+
+/// The number of model positions in a profile.
+///
+/// # Safety
+///
+/// `profile` must be a valid profile.
+#[no_mangle]
+pub unsafe extern "C" fn nail_profile_length(profile: *const NailProfile) -> usize {
+    (*profile).profile.length
+}
+
+/// Read the amino acid sequences in a fasta file.
+///
+/// Returns null on failure. The sequences must be freed with `nail_sequences_free()`.
+///
+/// # Safety
+///
+/// `path` must be a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nail_sequences_from_fasta(path: *const c_char) -> *mut NailSequences {
+    let sequences = guard(|| {
+        let path = str_arg(path, "path")?;
+        let sequences = Sequence::amino_from_fasta(path)
+            .context(format!("failed to read fasta: {path}"))?
+            .into_iter()
+            .map(NailSequence::new)
+            .collect::<anyhow::Result<_>>()?;
+        Ok(NailSequences { sequences })
+    });
+
+    match sequences {
+        Some(sequences) => Box::into_raw(Box::new(sequences)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The number of sequences.
+///
+/// # Safety
+///
+/// `sequences` must have been returned by `nail_sequences_from_fasta()`.
+#[no_mangle]
+pub unsafe extern "C" fn nail_sequences_len(sequences: *const NailSequences) -> usize {
+    (*sequences).sequences.len()
+}
+
+/// The sequence at `idx`, or null if `idx` is out of bounds.
+///
+/// The sequence is owned by `sequences`.
+///
+/// # Safety
+///
+/// `sequences` must have been returned by `nail_sequences_from_fasta()`.
+#[no_mangle]
+pub unsafe extern "C" fn nail_sequences_get(
+    sequences: *const NailSequences,
+    idx: usize,
+) -> *const NailSequence {
+    let sequences = &*sequences;
+    match sequences.sequences.get(idx) {
+        Some(sequence) => sequence,
+        None => ptr::null(),
+    }
+}
+
+/// Free sequences returned by `nail_sequences_from_fasta()`.
+///
+/// # Safety
+///
+/// `sequences` must be null or have been returned by `nail_sequences_from_fasta()`,
+/// and none of its sequences may be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn nail_sequences_free(sequences: *mut NailSequences) {
+    if !sequences.is_null() {
+        drop(Box::from_raw(sequences));
+    }
+}
+
+/// Create a sequence from a name and a string of amino acid residues.
+///
+/// Returns null on failure. The sequence must be freed with `nail_sequence_free()`.
+///
+/// # Safety
+///
+/// `name` and `residues` must be nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn nail_sequence_new(
+    name: *const c_char,
+    residues: *const c_char,
+) -> *mut NailSequence {
+    let sequence = guard(|| {
+        let name = str_arg(name, "name")?;
+        let residues = str_arg(residues, "residues")?;
+
+        let mut sequence = Sequence::from_utf8(residues.as_bytes())?;
+        sequence.name = name.to_string();
+        NailSequence::new(sequence)
+    });
+
+    match sequence {
+        Some(sequence) => Box::into_raw(Box::new(sequence)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Free a sequence returned by `nail_sequence_new()`.
+///
+/// # Safety
+///
+/// `sequence` must be null or have been returned by `nail_sequence_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn nail_sequence_free(sequence: *mut NailSequence) {
+    if !sequence.is_null() {
+        drop(Box::from_raw(sequence));
+    }
+}
+
+/// The name of a sequence, which is owned by the sequence.
+///
+/// # Safety
+///
+/// `sequence` must be a valid sequence.
+#[no_mangle]
+pub unsafe extern "C" fn nail_sequence_name(sequence: *const NailSequence) -> *const c_char {
+    (*sequence).name.as_ptr()
+}
+
+/// The number of residues in a sequence.
+///
+/// # Safety
+///
+/// `sequence` must be a valid sequence.
+#[no_mangle]
+pub unsafe extern "C" fn nail_sequence_length(sequence: *const NailSequence) -> usize {
+    (*sequence).sequence.length
+}
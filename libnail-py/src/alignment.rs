@@ -0,0 +1,157 @@
+use pyo3::prelude::*;
+
+use libnail::align::structs::{Alignment, Boundaries, DisplayStrings};
+
+/// An alignment of a profile to a target sequence.
+///
+/// Positions are 1-based and inclusive.
+#[pyclass(name = "Alignment", module = "libnail", frozen)]
+pub struct PyAlignment {
+    pub inner: Alignment,
+}
+
+impl PyAlignment {
+    fn boundary(&self, f: impl Fn(&Boundaries) -> usize) -> Option<usize> {
+        self.inner.boundaries.as_ref().map(f)
+    }
+
+    fn display_string(&self, f: impl Fn(&DisplayStrings) -> &String) -> Option<&str> {
+        self.inner.display_strings.as_ref().map(|d| f(d).as_str())
+    }
+}
+
+#[pymethods]
+impl PyAlignment {
+    #[getter]
+    fn profile_name(&self) -> Option<&str> {
+        self.inner.profile_name.as_deref()
+    }
+
+    #[getter]
+    fn target_name(&self) -> Option<&str> {
+        self.inner.target_name.as_deref()
+    }
+
+    #[getter]
+    fn target_description(&self) -> Option<&str> {
+        self.inner.target_description.as_deref()
+    }
+
+    #[getter]
+    fn profile_length(&self) -> Option<usize> {
+        self.inner.profile_length
+    }
+
+    #[getter]
+    fn target_length(&self) -> Option<usize> {
+        self.inner.target_length
+    }
+
+    /// The number of columns in the alignment
+    #[getter]
+    fn length(&self) -> Option<usize> {
+        self.boundary(|b| b.length)
+    }
+
+    #[getter]
+    fn profile_start(&self) -> Option<usize> {
+        self.boundary(|b| b.profile_start)
+    }
+
+    #[getter]
+    fn profile_end(&self) -> Option<usize> {
+        self.boundary(|b| b.profile_end)
+    }
+
+    #[getter]
+    fn target_start(&self) -> Option<usize> {
+        self.boundary(|b| b.target_start)
+    }
+
+    #[getter]
+    fn target_end(&self) -> Option<usize> {
+        self.boundary(|b| b.target_end)
+    }
+
+    /// The final bit score, including the composition bias correction
+    #[getter]
+    fn score(&self) -> f32 {
+        self.inner.scores.bit_score.value()
+    }
+
+    #[getter]
+    fn forward_score(&self) -> f32 {
+        self.inner.scores.forward_score.value()
+    }
+
+    #[getter]
+    fn forward_p_value(&self) -> f64 {
+        self.inner.scores.forward_p_value
+    }
+
+    /// The composition bias score correction, if it was computed
+    #[getter]
+    fn bias(&self) -> Option<f32> {
+        self.inner.scores.null_two_score.map(|s| s.value())
+    }
+
+    #[getter]
+    fn p_value(&self) -> f64 {
+        self.inner.scores.p_value
+    }
+
+    #[getter]
+    fn e_value(&self) -> f64 {
+        self.inner.scores.e_value
+    }
+
+    /// The fraction of the full DP matrix that was computed
+    #[getter]
+    fn cell_fraction(&self) -> Option<f32> {
+        self.inner.cell_stats.as_ref().map(|c| c.fraction)
+    }
+
+    #[getter]
+    fn profile_string(&self) -> Option<&str> {
+        self.display_string(|d| &d.profile_string)
+    }
+
+    #[getter]
+    fn target_string(&self) -> Option<&str> {
+        self.display_string(|d| &d.target_string)
+    }
+
+    #[getter]
+    fn middle_string(&self) -> Option<&str> {
+        self.display_string(|d| &d.middle_string)
+    }
+
+    #[getter]
+    fn posterior_string(&self) -> Option<&str> {
+        self.display_string(|d| &d.posterior_string)
+    }
+
+    /// The HMMER-style alignment display.
+    #[pyo3(signature = (width=None))]
+    fn ali_string(&self, width: Option<usize>) -> String {
+        match width {
+            Some(width) => self.inner.ali_string_with_width(width),
+            None => self.inner.ali_string(),
+        }
+    }
+
+    /// The alignment as a JSON object, in the format written by `nail search --json-out`.
+    fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(&self.inner)?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Alignment(profile_name={:?}, target_name={:?}, score={:.1}, e_value={:.1e})",
+            self.profile_name().unwrap_or_default(),
+            self.target_name().unwrap_or_default(),
+            self.score(),
+            self.e_value(),
+        )
+    }
+}
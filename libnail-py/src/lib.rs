@@ -0,0 +1,46 @@
+mod alignment;
+mod params;
+mod structs;
+
+use pyo3::prelude::*;
+
+use libnail::{align::structs::Seed, search::align_pair, search::SearchParams};
+
+use alignment::PyAlignment;
+use params::PySearchParams;
+use structs::{PyHmm, PyProfile, PySeed, PySequence};
+
+/// Align a profile to a target sequence with cloud search and sparse
+/// Forward/Backward, returning `None` if the pair is filtered.
+///
+/// If no seed is given, the full DP matrix is aligned.
+#[pyfunction]
+#[pyo3(signature = (profile, target, seed=None, params=None))]
+fn align(
+    py: Python<'_>,
+    profile: &PyProfile,
+    target: &PySequence,
+    seed: Option<PySeed>,
+    params: Option<PySearchParams>,
+) -> anyhow::Result<Option<PyAlignment>> {
+    let seed = seed.as_ref().map(Seed::from);
+    let params = SearchParams::from(&params.unwrap_or_default());
+
+    let alignment =
+        py.allow_threads(|| align_pair(&profile.inner, &target.inner, seed.as_ref(), &params))?;
+
+    Ok(alignment.map(|inner| PyAlignment { inner }))
+}
+
+#[pymodule]
+#[pyo3(name = "libnail")]
+fn libnail_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyHmm>()?;
+    m.add_class::<PyProfile>()?;
+    m.add_class::<PySequence>()?;
+    m.add_class::<PySeed>()?;
+    m.add_class::<PySearchParams>()?;
+    m.add_class::<PyAlignment>()?;
+    m.add_function(wrap_pyfunction!(align, m)?)?;
+    Ok(())
+}
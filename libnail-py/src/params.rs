@@ -0,0 +1,115 @@
+use pyo3::prelude::*;
+
+use libnail::{align::CloudSearchParams, search::SearchParams};
+
+/// The parameters of cloud search and alignment.
+///
+/// Any parameter that is not given takes the default of `nail search`.
+#[pyclass(name = "SearchParams", module = "libnail", get_all, set_all)]
+#[derive(Clone)]
+pub struct PySearchParams {
+    /// The local score pruning threshold of cloud search
+    pub alpha: f32,
+    /// The global score pruning threshold of cloud search
+    pub beta: f32,
+    /// The minimum number of anti-diagonals computed by cloud search
+    pub gamma: usize,
+    /// Scale the pruning parameters to each profile/target pair
+    pub adaptive_pruning: bool,
+    /// Skip cloud search and fill the full DP matrix
+    pub full_dp: bool,
+    pub cloud_p_value_threshold: f64,
+    pub forward_p_value_threshold: f64,
+    pub e_value_threshold: f64,
+    /// The number of comparisons used for E-value calculation
+    pub target_database_size: usize,
+    /// Compute the composition bias score correction
+    pub null_two: bool,
+}
+
+impl Default for PySearchParams {
+    fn default() -> Self {
+        let params = SearchParams::default();
+        Self {
+            alpha: params.cloud_search.alpha,
+            beta: params.cloud_search.beta,
+            gamma: params.cloud_search.gamma,
+            adaptive_pruning: params.adaptive_pruning,
+            full_dp: params.full_dp,
+            cloud_p_value_threshold: params.cloud_p_value_threshold,
+            forward_p_value_threshold: params.forward_p_value_threshold,
+            e_value_threshold: params.e_value_threshold,
+            target_database_size: 1,
+            null_two: params.null_two,
+        }
+    }
+}
+
+#[pymethods]
+impl PySearchParams {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        alpha=None,
+        beta=None,
+        gamma=None,
+        adaptive_pruning=None,
+        full_dp=None,
+        cloud_p_value_threshold=None,
+        forward_p_value_threshold=None,
+        e_value_threshold=None,
+        target_database_size=None,
+        null_two=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        alpha: Option<f32>,
+        beta: Option<f32>,
+        gamma: Option<usize>,
+        adaptive_pruning: Option<bool>,
+        full_dp: Option<bool>,
+        cloud_p_value_threshold: Option<f64>,
+        forward_p_value_threshold: Option<f64>,
+        e_value_threshold: Option<f64>,
+        target_database_size: Option<usize>,
+        null_two: Option<bool>,
+    ) -> Self {
+        let default = Self::default();
+        Self {
+            alpha: alpha.unwrap_or(default.alpha),
+            beta: beta.unwrap_or(default.beta),
+            gamma: gamma.unwrap_or(default.gamma),
+            adaptive_pruning: adaptive_pruning.unwrap_or(default.adaptive_pruning),
+            full_dp: full_dp.unwrap_or(default.full_dp),
+            cloud_p_value_threshold: cloud_p_value_threshold
+                .unwrap_or(default.cloud_p_value_threshold),
+            forward_p_value_threshold: forward_p_value_threshold
+                .unwrap_or(default.forward_p_value_threshold),
+            e_value_threshold: e_value_threshold.unwrap_or(default.e_value_threshold),
+            target_database_size: target_database_size.unwrap_or(default.target_database_size),
+            null_two: null_two.unwrap_or(default.null_two),
+        }
+    }
+}
+
+impl From<&PySearchParams> for SearchParams {
+    fn from(params: &PySearchParams) -> Self {
+        SearchParams {
+            cloud_search: CloudSearchParams {
+                gamma: params.gamma,
+                alpha: params.alpha,
+                beta: params.beta,
+            },
+            adaptive_pruning: params.adaptive_pruning,
+            full_dp: params.full_dp,
+            cloud_p_value_threshold: params.cloud_p_value_threshold,
+            forward_p_value_threshold: params.forward_p_value_threshold,
+            e_value_threshold: params.e_value_threshold,
+            target_database_size: Some(params.target_database_size),
+            null_two: params.null_two,
+            // the trace is part of Alignment.to_json()
+            keep_trace: true,
+            ..Default::default()
+        }
+    }
+}
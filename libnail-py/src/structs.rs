@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use pyo3::prelude::*;
+
+use libnail::{
+    align::structs::Seed,
+    structs::{Hmm, Profile, Sequence},
+};
+
+/// A profile HMM, as read from a HMMER3 (p7) file.
+#[pyclass(name = "Hmm", module = "libnail", frozen)]
+pub struct PyHmm {
+    pub inner: Hmm,
+}
+
+#[pymethods]
+impl PyHmm {
+    /// Read all of the HMMs in a HMMER3 file.
+    #[staticmethod]
+    fn from_p7hmm(path: PathBuf) -> anyhow::Result<Vec<Self>> {
+        let file = File::open(&path).context(format!(
+            "failed to open HMM file: {}",
+            path.to_string_lossy()
+        ))?;
+
+        Ok(Hmm::from_p7hmm(file)?
+            .into_iter()
+            .map(|inner| Self { inner })
+            .collect())
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.inner.header.name
+    }
+
+    #[getter]
+    fn accession(&self) -> &str {
+        &self.inner.header.accession_number
+    }
+
+    #[getter]
+    fn length(&self) -> usize {
+        self.inner.header.model_length
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Hmm(name={:?}, length={})",
+            self.inner.header.name, self.inner.header.model_length
+        )
+    }
+}
+
+/// A search profile, built from a profile HMM.
+#[pyclass(name = "Profile", module = "libnail", frozen)]
+#[derive(Clone)]
+pub struct PyProfile {
+    pub inner: Profile,
+}
+
+#[pymethods]
+impl PyProfile {
+    #[new]
+    fn new(hmm: &PyHmm) -> Self {
+        Self {
+            inner: Profile::new(&hmm.inner),
+        }
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    #[getter]
+    fn accession(&self) -> &str {
+        &self.inner.accession
+    }
+
+    #[getter]
+    fn length(&self) -> usize {
+        self.inner.length
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.length
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Profile(name={:?}, length={})",
+            self.inner.name, self.inner.length
+        )
+    }
+}
+
+/// An amino acid sequence.
+#[pyclass(name = "Sequence", module = "libnail", frozen)]
+#[derive(Clone)]
+pub struct PySequence {
+    pub inner: Sequence,
+}
+
+#[pymethods]
+impl PySequence {
+    #[new]
+    #[pyo3(signature = (name, residues, description=None))]
+    fn new(name: String, residues: &str, description: Option<String>) -> anyhow::Result<Self> {
+        let mut inner = Sequence::from_utf8(residues.as_bytes())?;
+        inner.name = name;
+        inner.details = description;
+        Ok(Self { inner })
+    }
+
+    /// Read all of the sequences in a fasta file.
+    #[staticmethod]
+    fn from_fasta(path: PathBuf) -> anyhow::Result<Vec<Self>> {
+        Ok(Sequence::amino_from_fasta(&path)
+            .context(format!("failed to read fasta: {}", path.to_string_lossy()))?
+            .into_iter()
+            .map(|inner| Self { inner })
+            .collect())
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    #[getter]
+    fn description(&self) -> Option<&str> {
+        self.inner.details.as_deref()
+    }
+
+    #[getter]
+    fn length(&self) -> usize {
+        self.inner.length
+    }
+
+    #[getter]
+    fn residues(&self) -> String {
+        // the utf8 bytes start with a padding byte
+        String::from_utf8_lossy(&self.inner.utf8_bytes[1..]).to_string()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.length
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Sequence(name={:?}, length={})",
+            self.inner.name, self.inner.length
+        )
+    }
+}
+
+/// The rough position of an alignment, which cloud search starts from.
+///
+/// Positions are 1-based and inclusive.
+#[pyclass(name = "Seed", module = "libnail", get_all, set_all)]
+#[derive(Clone)]
+pub struct PySeed {
+    pub target_start: usize,
+    pub target_end: usize,
+    pub profile_start: usize,
+    pub profile_end: usize,
+    pub score: f32,
+}
+
+#[pymethods]
+impl PySeed {
+    #[new]
+    #[pyo3(signature = (target_start, target_end, profile_start, profile_end, score=0.0))]
+    fn new(
+        target_start: usize,
+        target_end: usize,
+        profile_start: usize,
+        profile_end: usize,
+        score: f32,
+    ) -> Self {
+        Self {
+            target_start,
+            target_end,
+            profile_start,
+            profile_end,
+            score,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Seed(target_start={}, target_end={}, profile_start={}, profile_end={}, score={})",
+            self.target_start, self.target_end, self.profile_start, self.profile_end, self.score
+        )
+    }
+}
+
+impl From<&PySeed> for Seed {
+    fn from(seed: &PySeed) -> Self {
+        Seed {
+            target_start: seed.target_start,
+            target_end: seed.target_end,
+            profile_start: seed.profile_start,
+            profile_end: seed.profile_end,
+            score: seed.score,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+import json
+from pathlib import Path
+
+import pytest
+
+import libnail
+
+FIXTURES = Path(__file__).resolve().parents[2] / "fixtures"
+
+
+@pytest.fixture(scope="module")
+def profile():
+    hmms = libnail.Hmm.from_p7hmm(str(FIXTURES / "query.hmm"))
+    return libnail.Profile(hmms[0])
+
+
+@pytest.fixture(scope="module")
+def targets():
+    return libnail.Sequence.from_fasta(str(FIXTURES / "target.fa"))
+
+
+def test_read_hmms():
+    hmms = libnail.Hmm.from_p7hmm(str(FIXTURES / "query.hmm"))
+    assert len(hmms) > 0
+    assert all(hmm.length > 0 for hmm in hmms)
+
+    profile = libnail.Profile(hmms[0])
+    assert profile.name == hmms[0].name
+    assert len(profile) == hmms[0].length
+
+
+def test_read_sequences(targets):
+    assert len(targets) == 100
+    assert all(len(target.residues) == len(target) for target in targets)
+
+
+def test_sequence_from_residues():
+    sequence = libnail.Sequence("seq", "MKVLAT", description="a test sequence")
+    assert sequence.name == "seq"
+    assert sequence.description == "a test sequence"
+    assert sequence.residues == "MKVLAT"
+    assert len(sequence) == 6
+
+    with pytest.raises(Exception):
+        libnail.Sequence("bad", "MKV*1")
+
+
+def test_align_full_dp(profile, targets):
+    params = libnail.SearchParams(full_dp=True, target_database_size=len(targets))
+    alignment = libnail.align(profile, targets[0], params=params)
+
+    assert alignment is not None
+    assert alignment.profile_name == profile.name
+    assert alignment.target_name == targets[0].name
+    assert 1 <= alignment.target_start <= alignment.target_end <= len(targets[0])
+    assert 1 <= alignment.profile_start <= alignment.profile_end <= len(profile)
+    assert alignment.e_value <= params.e_value_threshold
+    assert alignment.cell_fraction == pytest.approx(1.0)
+
+    columns = alignment.length
+    assert len(alignment.profile_string) == columns
+    assert len(alignment.middle_string) == columns
+    assert len(alignment.target_string) == columns
+    assert len(alignment.posterior_string) == columns
+
+    assert alignment.target_name in alignment.ali_string()
+    assert json.loads(alignment.to_json())["target_name"] == alignment.target_name
+
+
+def test_align_cloud_search(profile, targets):
+    full_dp_params = libnail.SearchParams(full_dp=True, target_database_size=len(targets))
+    full = libnail.align(profile, targets[0], params=full_dp_params)
+
+    # seeding cloud search with the full DP alignment should recover it
+    seed = libnail.Seed(full.target_start, full.target_end, full.profile_start, full.profile_end)
+    params = libnail.SearchParams(target_database_size=len(targets))
+    cloud = libnail.align(profile, targets[0], seed=seed, params=params)
+
+    assert cloud is not None
+    assert cloud.cell_fraction < 1.0
+    assert cloud.score == pytest.approx(full.score, abs=0.5)
+
+
+def test_align_filtered(profile):
+    # a short low-complexity target won't pass the forward filter
+    target = libnail.Sequence("decoy", "A" * 50)
+    assert libnail.align(profile, target) is None
+
+
+def test_search_params_defaults():
+    params = libnail.SearchParams()
+    assert params.e_value_threshold == 10.0
+    assert params.target_database_size == 1
+    assert not params.full_dp
+
+    params.alpha = 8.0
+    assert params.alpha == 8.0
@@ -0,0 +1,148 @@
+use wasm_bindgen::prelude::*;
+
+use libnail::align::structs::{Boundaries, DisplayStrings};
+
+/// An alignment of a profile to a target sequence.
+///
+/// Positions are 1-based and inclusive.
+#[wasm_bindgen]
+pub struct Alignment {
+    pub(crate) inner: libnail::align::structs::Alignment,
+}
+
+impl Alignment {
+    fn boundary(&self, f: impl Fn(&Boundaries) -> usize) -> Option<usize> {
+        self.inner.boundaries.as_ref().map(f)
+    }
+
+    fn display_string(&self, f: impl Fn(&DisplayStrings) -> &String) -> Option<String> {
+        self.inner.display_strings.as_ref().map(|d| f(d).clone())
+    }
+}
+
+#[wasm_bindgen]
+impl Alignment {
+    #[wasm_bindgen(getter, js_name = profileName)]
+    pub fn profile_name(&self) -> Option<String> {
+        self.inner.profile_name.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = targetName)]
+    pub fn target_name(&self) -> Option<String> {
+        self.inner.target_name.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = targetDescription)]
+    pub fn target_description(&self) -> Option<String> {
+        self.inner.target_description.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = profileLength)]
+    pub fn profile_length(&self) -> Option<usize> {
+        self.inner.profile_length
+    }
+
+    #[wasm_bindgen(getter, js_name = targetLength)]
+    pub fn target_length(&self) -> Option<usize> {
+        self.inner.target_length
+    }
+
+    /// The number of columns in the alignment
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> Option<usize> {
+        self.boundary(|b| b.length)
+    }
+
+    #[wasm_bindgen(getter, js_name = profileStart)]
+    pub fn profile_start(&self) -> Option<usize> {
+        self.boundary(|b| b.profile_start)
+    }
+
+    #[wasm_bindgen(getter, js_name = profileEnd)]
+    pub fn profile_end(&self) -> Option<usize> {
+        self.boundary(|b| b.profile_end)
+    }
+
+    #[wasm_bindgen(getter, js_name = targetStart)]
+    pub fn target_start(&self) -> Option<usize> {
+        self.boundary(|b| b.target_start)
+    }
+
+    #[wasm_bindgen(getter, js_name = targetEnd)]
+    pub fn target_end(&self) -> Option<usize> {
+        self.boundary(|b| b.target_end)
+    }
+
+    /// The final bit score, including the composition bias correction
+    #[wasm_bindgen(getter)]
+    pub fn score(&self) -> f32 {
+        self.inner.scores.bit_score.value()
+    }
+
+    #[wasm_bindgen(getter, js_name = forwardScore)]
+    pub fn forward_score(&self) -> f32 {
+        self.inner.scores.forward_score.value()
+    }
+
+    #[wasm_bindgen(getter, js_name = forwardPValue)]
+    pub fn forward_p_value(&self) -> f64 {
+        self.inner.scores.forward_p_value
+    }
+
+    /// The composition bias score correction, if it was computed
+    #[wasm_bindgen(getter)]
+    pub fn bias(&self) -> Option<f32> {
+        self.inner.scores.null_two_score.map(|s| s.value())
+    }
+
+    #[wasm_bindgen(getter, js_name = pValue)]
+    pub fn p_value(&self) -> f64 {
+        self.inner.scores.p_value
+    }
+
+    #[wasm_bindgen(getter, js_name = eValue)]
+    pub fn e_value(&self) -> f64 {
+        self.inner.scores.e_value
+    }
+
+    /// The fraction of the full DP matrix that was computed
+    #[wasm_bindgen(getter, js_name = cellFraction)]
+    pub fn cell_fraction(&self) -> Option<f32> {
+        self.inner.cell_stats.as_ref().map(|c| c.fraction)
+    }
+
+    #[wasm_bindgen(getter, js_name = profileString)]
+    pub fn profile_string(&self) -> Option<String> {
+        self.display_string(|d| &d.profile_string)
+    }
+
+    #[wasm_bindgen(getter, js_name = targetString)]
+    pub fn target_string(&self) -> Option<String> {
+        self.display_string(|d| &d.target_string)
+    }
+
+    #[wasm_bindgen(getter, js_name = middleString)]
+    pub fn middle_string(&self) -> Option<String> {
+        self.display_string(|d| &d.middle_string)
+    }
+
+    #[wasm_bindgen(getter, js_name = posteriorString)]
+    pub fn posterior_string(&self) -> Option<String> {
+        self.display_string(|d| &d.posterior_string)
+    }
+
+    /// The HMMER-style alignment display.
+    #[wasm_bindgen(js_name = aliString)]
+    pub fn ali_string(&self, width: Option<usize>) -> String {
+        match width {
+            Some(width) => self.inner.ali_string_with_width(width),
+            None => self.inner.ali_string(),
+        }
+    }
+
+    /// The alignment as a JSON object, in the format written by `nail search --json-out`.
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&self.inner)?)
+    }
+}
@@ -0,0 +1,64 @@
+//! WebAssembly bindings for libnail.
+//!
+//! This builds libnail without its `fs` feature, so there are no file readers:
+//! profiles are read from the text of a HMMER3 file, and sequences are built
+//! from strings of residues.
+//!
+//! ```sh
+//! cargo build --release -p libnail-wasm --target wasm32-unknown-unknown
+//! wasm-bindgen --target web --out-dir pkg \
+//!     target/wasm32-unknown-unknown/release/libnail_wasm.wasm
+//! ```
+mod alignment;
+mod params;
+mod structs;
+
+use wasm_bindgen::prelude::*;
+
+use libnail::search::align_pair;
+
+pub use alignment::Alignment;
+pub use params::SearchParams;
+pub use structs::{Profile, Seed, Sequence};
+
+pub(crate) fn js_error(err: anyhow::Error) -> JsError {
+    JsError::new(&format!("{err:#}"))
+}
+
+/// Align a profile to a target sequence over the full DP matrix,
+/// returning `undefined` if the pair is filtered.
+#[wasm_bindgen]
+pub fn align(
+    profile: &Profile,
+    target: &Sequence,
+    params: &SearchParams,
+) -> Result<Option<Alignment>, JsError> {
+    align_inner(profile, target, None, params)
+}
+
+/// Align a profile to a target sequence with cloud search and sparse
+/// Forward/Backward, returning `undefined` if the pair is filtered.
+#[wasm_bindgen(js_name = alignSeeded)]
+pub fn align_seeded(
+    profile: &Profile,
+    target: &Sequence,
+    seed: &Seed,
+    params: &SearchParams,
+) -> Result<Option<Alignment>, JsError> {
+    align_inner(profile, target, Some(seed), params)
+}
+
+fn align_inner(
+    profile: &Profile,
+    target: &Sequence,
+    seed: Option<&Seed>,
+    params: &SearchParams,
+) -> Result<Option<Alignment>, JsError> {
+    let seed = seed.map(libnail::align::structs::Seed::from);
+    let params = libnail::search::SearchParams::from(params);
+
+    let alignment =
+        align_pair(&profile.inner, &target.inner, seed.as_ref(), &params).map_err(js_error)?;
+
+    Ok(alignment.map(|inner| Alignment { inner }))
+}
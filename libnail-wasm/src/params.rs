@@ -0,0 +1,84 @@
+use wasm_bindgen::prelude::*;
+
+use libnail::align::CloudSearchParams;
+
+/// The parameters of cloud search and alignment.
+///
+/// A new `SearchParams` has the defaults of `nail search`,
+/// with a target database size of 1.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct SearchParams {
+    /// The local score pruning threshold of cloud search
+    pub alpha: f32,
+    /// The global score pruning threshold of cloud search
+    pub beta: f32,
+    /// The minimum number of anti-diagonals computed by cloud search
+    pub gamma: usize,
+    /// Scale the pruning parameters to each profile/target pair
+    #[wasm_bindgen(js_name = adaptivePruning)]
+    pub adaptive_pruning: bool,
+    /// Skip cloud search and fill the full DP matrix
+    #[wasm_bindgen(js_name = fullDp)]
+    pub full_dp: bool,
+    #[wasm_bindgen(js_name = cloudPValueThreshold)]
+    pub cloud_p_value_threshold: f64,
+    #[wasm_bindgen(js_name = forwardPValueThreshold)]
+    pub forward_p_value_threshold: f64,
+    #[wasm_bindgen(js_name = eValueThreshold)]
+    pub e_value_threshold: f64,
+    /// The number of comparisons used for E-value calculation
+    #[wasm_bindgen(js_name = targetDatabaseSize)]
+    pub target_database_size: usize,
+    /// Compute the composition bias score correction
+    #[wasm_bindgen(js_name = nullTwo)]
+    pub null_two: bool,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        let params = libnail::search::SearchParams::default();
+        Self {
+            alpha: params.cloud_search.alpha,
+            beta: params.cloud_search.beta,
+            gamma: params.cloud_search.gamma,
+            adaptive_pruning: params.adaptive_pruning,
+            full_dp: params.full_dp,
+            cloud_p_value_threshold: params.cloud_p_value_threshold,
+            forward_p_value_threshold: params.forward_p_value_threshold,
+            e_value_threshold: params.e_value_threshold,
+            target_database_size: 1,
+            null_two: params.null_two,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl SearchParams {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SearchParams {
+        Self::default()
+    }
+}
+
+impl From<&SearchParams> for libnail::search::SearchParams {
+    fn from(params: &SearchParams) -> Self {
+        libnail::search::SearchParams {
+            cloud_search: CloudSearchParams {
+                gamma: params.gamma,
+                alpha: params.alpha,
+                beta: params.beta,
+            },
+            adaptive_pruning: params.adaptive_pruning,
+            full_dp: params.full_dp,
+            cloud_p_value_threshold: params.cloud_p_value_threshold,
+            forward_p_value_threshold: params.forward_p_value_threshold,
+            e_value_threshold: params.e_value_threshold,
+            target_database_size: Some(params.target_database_size),
+            null_two: params.null_two,
+            // the trace is part of Alignment.toJson()
+            keep_trace: true,
+            ..Default::default()
+        }
+    }
+}
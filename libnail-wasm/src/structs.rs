@@ -0,0 +1,135 @@
+use wasm_bindgen::prelude::*;
+
+use libnail::structs::Hmm;
+
+use crate::js_error;
+
+/// A search profile, built from a profile HMM.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Profile {
+    pub(crate) inner: libnail::structs::Profile,
+}
+
+#[wasm_bindgen]
+impl Profile {
+    /// Build a profile from each of the HMMs in the text of a HMMER3 file.
+    #[wasm_bindgen(js_name = fromP7hmm)]
+    pub fn from_p7hmm(text: &str) -> Result<Vec<Profile>, JsError> {
+        Ok(Hmm::from_p7hmm(text.as_bytes())
+            .map_err(js_error)?
+            .iter()
+            .map(|hmm| Self {
+                inner: libnail::structs::Profile::new(hmm),
+            })
+            .collect())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.inner.name.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn accession(&self) -> String {
+        self.inner.accession.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.inner.length
+    }
+}
+
+/// An amino acid sequence.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Sequence {
+    pub(crate) inner: libnail::structs::Sequence,
+}
+
+#[wasm_bindgen]
+impl Sequence {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        name: String,
+        residues: &str,
+        description: Option<String>,
+    ) -> Result<Sequence, JsError> {
+        let mut inner =
+            libnail::structs::Sequence::from_utf8(residues.as_bytes()).map_err(js_error)?;
+        inner.name = name;
+        inner.details = description;
+        Ok(Self { inner })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.inner.name.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn description(&self) -> Option<String> {
+        self.inner.details.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.inner.length
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn residues(&self) -> String {
+        // the utf8 bytes start with a padding byte
+        String::from_utf8_lossy(&self.inner.utf8_bytes[1..]).to_string()
+    }
+}
+
+/// The rough position of an alignment, which cloud search starts from.
+///
+/// Positions are 1-based and inclusive.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct Seed {
+    #[wasm_bindgen(js_name = targetStart)]
+    pub target_start: usize,
+    #[wasm_bindgen(js_name = targetEnd)]
+    pub target_end: usize,
+    #[wasm_bindgen(js_name = profileStart)]
+    pub profile_start: usize,
+    #[wasm_bindgen(js_name = profileEnd)]
+    pub profile_end: usize,
+    pub score: f32,
+}
+
+#[wasm_bindgen]
+impl Seed {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        target_start: usize,
+        target_end: usize,
+        profile_start: usize,
+        profile_end: usize,
+        score: Option<f32>,
+    ) -> Seed {
+        Self {
+            target_start,
+            target_end,
+            profile_start,
+            profile_end,
+            score: score.unwrap_or(0.0),
+        }
+    }
+}
+
+impl From<&Seed> for libnail::align::structs::Seed {
+    fn from(seed: &Seed) -> Self {
+        libnail::align::structs::Seed {
+            target_start: seed.target_start,
+            target_end: seed.target_end,
+            profile_start: seed.profile_start,
+            profile_end: seed.profile_end,
+            score: seed.score,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Run with `wasm-pack test --node libnail-wasm`, or with
+//! `cargo test` from `libnail-wasm/` if `wasm-bindgen-test-runner` is installed.
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::*;
+
+use libnail_wasm::{align, align_seeded, Profile, SearchParams, Seed, Sequence};
+
+const QUERY_HMM: &str = include_str!("../../fixtures/query.hmm");
+const TARGET_FA: &str = include_str!("../../fixtures/target.fa");
+
+fn profile() -> Profile {
+    Profile::from_p7hmm(QUERY_HMM).unwrap().remove(0)
+}
+
+/// The first record of the target fasta.
+fn target() -> Sequence {
+    let mut lines = TARGET_FA.lines();
+    let name = lines.next().unwrap()[1..]
+        .split_whitespace()
+        .next()
+        .unwrap()
+        .to_string();
+    let residues: String = lines.take_while(|line| !line.starts_with('>')).collect();
+    Sequence::new(name, &residues, None).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn test_profiles_from_text() {
+    let profiles = Profile::from_p7hmm(QUERY_HMM).unwrap();
+    assert!(!profiles.is_empty());
+    assert!(profiles.iter().all(|profile| profile.length() > 0));
+
+    assert!(Profile::from_p7hmm("not a HMMER3 file").is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_sequence_from_residues() {
+    let sequence = Sequence::new("seq".to_string(), "MKVLAT", None).unwrap();
+    assert_eq!(sequence.name(), "seq");
+    assert_eq!(sequence.residues(), "MKVLAT");
+    assert_eq!(sequence.length(), 6);
+
+    assert!(Sequence::new("bad".to_string(), "MKV*1", None).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_align_full_dp() {
+    let profile = profile();
+    let target = target();
+
+    let mut params = SearchParams::new();
+    params.full_dp = true;
+
+    let alignment = align(&profile, &target, &params).unwrap().unwrap();
+
+    assert_eq!(alignment.profile_name().unwrap(), profile.name());
+    assert_eq!(alignment.target_name().unwrap(), target.name());
+    assert!(alignment.e_value() <= params.e_value_threshold);
+    assert_eq!(alignment.cell_fraction(), Some(1.0));
+
+    let columns = alignment.length().unwrap();
+    assert_eq!(alignment.profile_string().unwrap().len(), columns);
+    assert_eq!(alignment.middle_string().unwrap().len(), columns);
+    assert_eq!(alignment.target_string().unwrap().len(), columns);
+    assert!(alignment.ali_string(None).contains(&target.name()));
+}
+
+#[wasm_bindgen_test]
+fn test_align_seeded() {
+    let profile = profile();
+    let target = target();
+
+    let mut params = SearchParams::new();
+    params.full_dp = true;
+    let full = align(&profile, &target, &params).unwrap().unwrap();
+
+    // seeding cloud search with the full DP alignment should recover it
+    let seed = Seed::new(
+        full.target_start().unwrap(),
+        full.target_end().unwrap(),
+        full.profile_start().unwrap(),
+        full.profile_end().unwrap(),
+        None,
+    );
+    let cloud = align_seeded(&profile, &target, &seed, &SearchParams::new())
+        .unwrap()
+        .unwrap();
+
+    assert!(cloud.cell_fraction().unwrap() < 1.0);
+    assert!((cloud.score() - full.score()).abs() < 0.5);
+}
+
+#[wasm_bindgen_test]
+fn test_align_filtered() {
+    // a short low-complexity target won't pass the forward filter
+    let target = Sequence::new("decoy".to_string(), &"A".repeat(50), None).unwrap();
+    assert!(align(&profile(), &target, &SearchParams::new())
+        .unwrap()
+        .is_none());
+}
@@ -23,6 +23,54 @@ impl Default for CloudSearchParams {
     }
 }
 
+impl CloudSearchParams {
+    /// The mean relative entropy (bits per position) at which α & β are left unscaled.
+    ///
+    /// This is HMMER's default target for protein models (hmmbuild's `--ere`), so
+    /// a profile built by hmmbuild with default settings sits at this value.
+    pub const REFERENCE_RELATIVE_ENTROPY: f32 = 0.59;
+    /// The alignment length at which α & β are left unscaled.
+    ///
+    /// This is a round number on the scale of a typical protein domain, not a
+    /// fitted value. Together with [`Self::REFERENCE_RELATIVE_ENTROPY`], it sets
+    /// the point where scaling leaves the defaults (α = 12, β = 20) unchanged.
+    pub const REFERENCE_ALIGNMENT_LENGTH: f32 = 200.0;
+    /// The smallest factor that α & β will be scaled by.
+    pub const MIN_SCALE: f32 = 0.5;
+    /// The largest factor that α & β will be scaled by.
+    pub const MAX_SCALE: f32 = 2.0;
+
+    /// Produce a copy of the parameters with α & β scaled to the
+    /// expected score of an alignment between a profile and a target.
+    ///
+    /// The fluctuations in the score of an alignment grow (roughly) with the square
+    /// root of its length times the information content of each position, so we
+    /// scale α & β by the same to keep pruning about as aggressive as it is for
+    /// the reference profile.
+    pub fn scaled(
+        &self,
+        profile_length: usize,
+        target_length: usize,
+        relative_entropy: f32,
+    ) -> Self {
+        // we can't align more positions than the shorter of the two sequences
+        let alignment_length = profile_length.min(target_length) as f32;
+
+        let expected_score = alignment_length * relative_entropy.max(0.0);
+        let reference_score = Self::REFERENCE_ALIGNMENT_LENGTH * Self::REFERENCE_RELATIVE_ENTROPY;
+
+        let scale = (expected_score / reference_score)
+            .sqrt()
+            .clamp(Self::MIN_SCALE, Self::MAX_SCALE);
+
+        Self {
+            gamma: self.gamma,
+            alpha: self.alpha * scale,
+            beta: self.beta * scale,
+        }
+    }
+}
+
 pub enum PruneStatus {
     FullyPruned,
     PartiallyPruned,
@@ -673,3 +721,65 @@ pub fn cloud_search_forward(
         num_cells_computed,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaled_params() {
+        let params = CloudSearchParams::default();
+
+        // the reference profile keeps the default params
+        let scaled = params.scaled(
+            CloudSearchParams::REFERENCE_ALIGNMENT_LENGTH as usize,
+            1000,
+            CloudSearchParams::REFERENCE_RELATIVE_ENTROPY,
+        );
+        assert!((scaled.alpha - params.alpha).abs() < 1e-4);
+        assert!((scaled.beta - params.beta).abs() < 1e-4);
+        assert_eq!(scaled.gamma, params.gamma);
+
+        // short, low-information profiles prune more aggressively
+        let scaled = params.scaled(20, 1000, 0.3);
+        assert!((scaled.alpha - params.alpha * CloudSearchParams::MIN_SCALE).abs() < 1e-4);
+        assert!((scaled.beta - params.beta * CloudSearchParams::MIN_SCALE).abs() < 1e-4);
+
+        // long profiles prune less aggressively
+        let scaled = params.scaled(400, 1000, CloudSearchParams::REFERENCE_RELATIVE_ENTROPY);
+        assert!(scaled.alpha > params.alpha);
+        assert!(scaled.beta > params.beta);
+
+        // but the target length caps the expected alignment length
+        let capped = params.scaled(400, 200, CloudSearchParams::REFERENCE_RELATIVE_ENTROPY);
+        assert!((capped.beta - params.beta).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_scaled_params_monotonic() {
+        // the defaults that nail's -A and -B use
+        let params = CloudSearchParams::default();
+        assert_eq!((params.alpha, params.beta), (12.0, 20.0));
+
+        let reference = params.scaled(200, 200, 0.59);
+        assert_eq!((reference.alpha, reference.beta), (12.0, 20.0));
+
+        let mut previous = params.scaled(1, 10_000, 0.59);
+        for length in (10..2000).step_by(10) {
+            let scaled = params.scaled(length, 10_000, 0.59);
+            assert!(scaled.alpha >= previous.alpha && scaled.beta >= previous.beta);
+            previous = scaled;
+        }
+
+        let mut previous = params.scaled(200, 10_000, 0.0);
+        for step in 1..=40 {
+            let scaled = params.scaled(200, 10_000, step as f32 * 0.05);
+            assert!(scaled.alpha >= previous.alpha && scaled.beta >= previous.beta);
+            previous = scaled;
+        }
+
+        // strictly increasing between the clamps
+        assert!(params.scaled(150, 10_000, 0.59).alpha < params.scaled(250, 10_000, 0.59).alpha);
+        assert!(params.scaled(200, 10_000, 0.4).beta < params.scaled(200, 10_000, 0.8).beta);
+    }
+}
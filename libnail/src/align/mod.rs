@@ -12,6 +12,12 @@ pub use forward::forward;
 mod backward;
 pub use backward::backward;
 
+mod viterbi;
+pub use viterbi::{viterbi, viterbi_score};
+
+mod msv;
+pub use msv::msv_score;
+
 mod posterior;
 pub use posterior::posterior;
 
@@ -20,11 +26,12 @@ pub use optimal_accuracy::optimal_accuracy;
 
 mod scoring;
 pub use scoring::{
-    cloud_score, e_value, null_one_score, null_two_score, p_value, Bits, Nats, Score,
+    bias_filter_score, cloud_score, e_value, null_one_score, null_two_score, p_value, Bits, Nats,
+    Score,
 };
 
 mod traceback;
-pub use traceback::traceback;
+pub use traceback::{traceback, viterbi_traceback};
 
 mod needleman_wunsch;
 pub use needleman_wunsch::{needleman_wunsch, SimpleTraceStep};
@@ -0,0 +1,62 @@
+use crate::structs::{Profile, Sequence};
+
+use super::Nats;
+
+/// Compute the MSV (multiple segment Viterbi) score of a target against a profile.
+///
+/// This follows HMMER's generic MSV: the core model is reduced to ungapped
+/// local diagonals, entered uniformly from the B state and exited freely to
+/// the E state. The E, N, J, and C transitions come from the profile, so the
+/// profile's hit mode and target length configuration are respected.
+pub fn msv_score(profile: &Profile, target: &Sequence) -> Nats {
+    let num_cols = profile.length + 1;
+
+    let mut prev_match = vec![-f32::INFINITY; num_cols];
+    let mut cur_match = vec![-f32::INFINITY; num_cols];
+
+    // uniform local entry: 2 / (M * (M + 1))
+    let begin_to_match = (2.0 / (profile.length as f32 * (profile.length as f32 + 1.0))).ln();
+
+    let n_loop =
+        profile.special_transition_score(Profile::SPECIAL_N_IDX, Profile::SPECIAL_LOOP_IDX);
+    let n_move =
+        profile.special_transition_score(Profile::SPECIAL_N_IDX, Profile::SPECIAL_MOVE_IDX);
+    let e_loop =
+        profile.special_transition_score(Profile::SPECIAL_E_IDX, Profile::SPECIAL_LOOP_IDX);
+    let e_move =
+        profile.special_transition_score(Profile::SPECIAL_E_IDX, Profile::SPECIAL_MOVE_IDX);
+    let j_loop =
+        profile.special_transition_score(Profile::SPECIAL_J_IDX, Profile::SPECIAL_LOOP_IDX);
+    let j_move =
+        profile.special_transition_score(Profile::SPECIAL_J_IDX, Profile::SPECIAL_MOVE_IDX);
+    let c_loop =
+        profile.special_transition_score(Profile::SPECIAL_C_IDX, Profile::SPECIAL_LOOP_IDX);
+    let c_move =
+        profile.special_transition_score(Profile::SPECIAL_C_IDX, Profile::SPECIAL_MOVE_IDX);
+
+    let mut n_score = 0.0f32;
+    let mut b_score = n_move;
+    let mut j_score = -f32::INFINITY;
+    let mut c_score = -f32::INFINITY;
+
+    for target_idx in 1..=target.length {
+        let residue = target.digital_bytes[target_idx] as usize;
+        let mut e_score = -f32::INFINITY;
+
+        for profile_idx in 1..=profile.length {
+            cur_match[profile_idx] = prev_match[profile_idx - 1].max(b_score + begin_to_match)
+                + profile.match_score(residue, profile_idx);
+
+            e_score = e_score.max(cur_match[profile_idx]);
+        }
+
+        j_score = (j_score + j_loop).max(e_score + e_loop);
+        c_score = (c_score + c_loop).max(e_score + e_move);
+        n_score += n_loop;
+        b_score = (n_score + n_move).max(j_score + j_move);
+
+        std::mem::swap(&mut prev_match, &mut cur_match);
+    }
+
+    Nats(c_score + c_move)
+}
@@ -1,12 +1,15 @@
 use crate::align::structs::{DpMatrix, RowBounds};
+use crate::alphabet::AMINO_BACKGROUND_FREQUENCIES;
 use crate::log_sum;
 use crate::structs::{Profile, Sequence};
 use crate::util::log_add;
 
+use serde::Serialize;
+
 use super::CloudSearchResults;
 
 /// A wrapper around f32 to describe nats
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone, Copy, Serialize)]
 pub struct Nats(pub f32);
 impl Nats {
     pub fn value(&self) -> f32 {
@@ -57,7 +60,7 @@ impl std::ops::Sub<Bits> for Nats {
 }
 
 /// A wrapper around f32 to describe bits
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone, Copy, Serialize)]
 pub struct Bits(pub f32);
 impl Bits {
     pub fn value(&self) -> f32 {
@@ -188,6 +191,74 @@ pub fn null_one_score(target_length: usize) -> Nats {
     Nats(target_length as f32 * p1.ln() + (1.0 - p1).ln())
 }
 
+/// Compute HMMER's bias filter score adjustment for a target region: the log
+/// odds of the region under a two-state "filter null" model versus the null
+/// one model. One state emits the background composition and the other emits
+/// the profile's composition, so regions with a profile-like composition
+/// (e.g. low complexity repeats of common profile residues) score highly.
+///
+/// `composition` is indexed by digital amino residue.
+///
+/// HMMER subtracts this from the MSV score before computing a p-value with
+/// the MSV Gumbel parameters. nail has no MSV score, so the bias filter stage
+/// subtracts it from the MMseqs2 bit score of the seed instead, and computes
+/// the p-value with the profile's Forward lambda and tau. The seed score is
+/// not calibrated against either distribution, so the resulting p-value is
+/// an uncalibrated approximation and the threshold should be set loosely.
+pub fn bias_filter_score(
+    composition: &[f32],
+    profile_length: usize,
+    target: &Sequence,
+    target_start: usize,
+    target_end: usize,
+) -> Nats {
+    // these are the parameters of HMMER's p7_bg_SetFilter():
+    // the expected lengths of background and biased segments
+    let background_length = 400.0f64;
+    let biased_length = profile_length as f64 / 8.0;
+
+    let t_bb = background_length / (background_length + 1.0);
+    let t_bm = 1.0 / (background_length + 1.0);
+    let t_mb = 1.0 / (biased_length + 1.0);
+    let t_mm = biased_length / (biased_length + 1.0);
+
+    // the odds of the biased state emitting each residue
+    let odds = |residue: u8| -> f64 {
+        let residue = residue as usize;
+        if residue < Profile::MAX_ALPHABET_SIZE {
+            composition[residue] as f64 / AMINO_BACKGROUND_FREQUENCIES[residue] as f64
+        } else {
+            1.0
+        }
+    };
+
+    // scaled forward over the two state HMM
+    let mut background = 0.999f64;
+    let mut biased = 0.001f64;
+    let mut log_scale = 0.0f64;
+
+    for (idx, residue) in target.digital_bytes[target_start..=target_end]
+        .iter()
+        .enumerate()
+    {
+        if idx == 0 {
+            biased *= odds(*residue);
+        } else {
+            let next_background = background * t_bb + biased * t_mb;
+            let next_biased = (background * t_bm + biased * t_mm) * odds(*residue);
+            background = next_background;
+            biased = next_biased;
+        }
+
+        let scale = background + biased;
+        background /= scale;
+        biased /= scale;
+        log_scale += scale.ln();
+    }
+
+    Nats(log_scale as f32)
+}
+
 /// Compute the null two score adjustment: the composition bias.
 pub fn null_two_score(
     posterior_matrix: &impl DpMatrix,
@@ -6,9 +6,11 @@ use crate::output::output_tabular::{Field, TableFormat};
 use crate::structs::{Profile, Sequence};
 use std::cmp::{max, min};
 
-use super::Trace;
+use serde::Serialize;
 
-#[derive(Default)]
+use super::{Trace, TraceStep};
+
+#[derive(Default, Serialize)]
 pub struct Boundaries {
     /// The length of the alignment
     pub length: usize,
@@ -22,6 +24,7 @@ pub struct Boundaries {
     pub target_end: usize,
 }
 
+#[derive(Serialize)]
 pub struct Scores {
     /// The Forward score (no bias adjustment)
     pub forward_score: Bits,
@@ -37,6 +40,7 @@ pub struct Scores {
     pub e_value: f64,
 }
 
+#[derive(Serialize)]
 pub struct CellStats {
     /// The number of dynamic programming cells filled in during alignment.
     pub count: usize,
@@ -44,6 +48,7 @@ pub struct CellStats {
     pub fraction: f32,
 }
 
+#[derive(Serialize)]
 pub struct DisplayStrings {
     /// The display for the profile portion of the alignment
     pub profile_string: String,
@@ -53,13 +58,26 @@ pub struct DisplayStrings {
     pub middle_string: String,
     /// The display for position-specific posterior probability bins
     pub posterior_string: String,
+    /// The display for the profile's reference (RF) annotation, if it has one
+    pub reference_string: Option<String>,
+    /// The display for the profile's consensus structure (CS) annotation, if it has one
+    pub structure_string: Option<String>,
 }
 
+#[derive(Serialize)]
 pub struct Alignment {
     /// The name of the profile/model
     pub profile_name: Option<String>,
     /// The name of the target sequence
     pub target_name: Option<String>,
+    /// The accession number of the profile/model
+    pub profile_accession: Option<String>,
+    /// The description of the target sequence
+    pub target_description: Option<String>,
+    /// The length of the profile/model
+    pub profile_length: Option<usize>,
+    /// The length of the target sequence
+    pub target_length: Option<usize>,
     /// The boundaries of the alignment
     pub boundaries: Option<Boundaries>,
     /// The bitscores and significance metrics of the alignment
@@ -68,6 +86,9 @@ pub struct Alignment {
     pub cell_stats: Option<CellStats>,
     /// The strings used for alignment display
     pub display_strings: Option<DisplayStrings>,
+    /// The core (M, I, D) steps of the alignment's trace;
+    /// these are only kept if the builder is asked to keep them
+    pub trace: Option<Vec<TraceStep>>,
 }
 
 impl AsRef<Alignment> for &Alignment {
@@ -120,6 +141,7 @@ pub struct AlignmentBuilder<'a> {
     forward_score: Option<Bits>,
     null_two: Option<Bits>,
     cell_count: Option<usize>,
+    keep_trace: bool,
 }
 
 impl<'a> AlignmentBuilder<'a> {
@@ -128,6 +150,12 @@ impl<'a> AlignmentBuilder<'a> {
         self
     }
 
+    /// Keep the core steps of the trace in the alignment, e.g. for JSON or SAM output.
+    pub fn with_trace_steps(mut self, keep: bool) -> Self {
+        self.keep_trace = keep;
+        self
+    }
+
     pub fn with_profile(mut self, profile: &'a Profile) -> Self {
         self.profile = Some(profile);
         self
@@ -231,6 +259,8 @@ impl<'a> AlignmentBuilder<'a> {
                 let mut target_bytes = vec![];
                 let mut middle_bytes = vec![];
                 let mut posteriors = vec![];
+                let mut reference_bytes = vec![];
+                let mut structure_bytes = vec![];
 
                 trace
                     .iter()
@@ -244,6 +274,18 @@ impl<'a> AlignmentBuilder<'a> {
                         let profile_byte = profile.consensus_sequence_bytes_utf8[step.profile_idx];
                         let target_byte = target.utf8_bytes[step.target_idx];
 
+                        // insertions are not in the model, so they have no annotation
+                        let annotation_byte = |annotation: &Option<Vec<u8>>| match step.state {
+                            Trace::I_STATE => UTF8_DOT,
+                            _ => annotation
+                                .as_ref()
+                                .map_or(UTF8_SPACE, |a| a[step.profile_idx]),
+                        };
+                        reference_bytes
+                            .push(annotation_byte(&profile.reference_annotation_bytes_utf8));
+                        structure_bytes
+                            .push(annotation_byte(&profile.consensus_structure_bytes_utf8));
+
                         match step.state {
                             Trace::I_STATE => {
                                 profile_bytes.push(Alignment::PROFILE_GAP_BYTE);
@@ -285,11 +327,23 @@ impl<'a> AlignmentBuilder<'a> {
                 let middle_string = String::from_utf8(middle_bytes)?;
                 let posterior_string = String::from_utf8(posteriors)?;
 
+                let reference_string = match profile.reference_annotation_bytes_utf8 {
+                    Some(_) => Some(String::from_utf8(reference_bytes)?),
+                    None => None,
+                };
+
+                let structure_string = match profile.consensus_structure_bytes_utf8 {
+                    Some(_) => Some(String::from_utf8(structure_bytes)?),
+                    None => None,
+                };
+
                 Some(DisplayStrings {
                     profile_string,
                     target_string,
                     middle_string,
                     posterior_string,
+                    reference_string,
+                    structure_string,
                 })
             }
             _ => None,
@@ -298,10 +352,21 @@ impl<'a> AlignmentBuilder<'a> {
         Ok(Alignment {
             profile_name: self.profile.map(|profile| profile.name.clone()),
             target_name: self.target.map(|target| target.name.clone()),
+            profile_accession: self
+                .profile
+                .map(|profile| profile.accession.clone())
+                .filter(|accession| !accession.is_empty()),
+            target_description: self.target.and_then(|target| target.details.clone()),
+            profile_length: self.profile.map(|profile| profile.length),
+            target_length: self.target.map(|target| target.length),
             boundaries,
             scores,
             cell_stats,
             display_strings,
+            trace: match self.keep_trace {
+                true => self.trace.map(|trace| trace.core_iter().collect()),
+                false => None,
+            },
         })
     }
 }
@@ -309,6 +374,7 @@ impl<'a> AlignmentBuilder<'a> {
 impl Alignment {
     pub const PROFILE_GAP_BYTE: u8 = UTF8_DOT;
     pub const TARGET_GAP_BYTE: u8 = UTF8_DASH;
+    pub const DEFAULT_ALI_WIDTH: usize = 80;
     pub const TAB_HEADER: &'static str = "#target\tquery\ttarget start\ttarget end\tprofile start\tprofile end\tscore\tcomposition bias\tE-value\tcell fraction";
 
     pub fn tab_string_formatted(&self, format: &TableFormat) -> String {
@@ -329,6 +395,16 @@ impl Alignment {
         tab_string
     }
 
+    /// Produce a strict tab-separated row of the format's fields, without width padding.
+    pub fn tsv_string(&self, format: &TableFormat) -> String {
+        format
+            .fields
+            .iter()
+            .map(|field| field.extract_from(self))
+            .collect::<Vec<_>>()
+            .join("\t")
+    }
+
     pub fn vert_string(&self) -> String {
         let mut vert_string = String::new();
 
@@ -360,26 +436,36 @@ impl Alignment {
     }
 
     pub fn ali_string(&self) -> String {
+        self.ali_string_with_width(Self::DEFAULT_ALI_WIDTH)
+    }
+
+    /// Produce the alignment display, wrapped into HMMER-style blocks of
+    /// at most `width` alignment columns. Each block has the profile's
+    /// CS and RF annotation lines (if it has them), the profile line,
+    /// the match line, the target line, and the posterior probability line.
+    pub fn ali_string_with_width(&self, width: usize) -> String {
         match (&self.display_strings, &self.boundaries) {
             (Some(display), Some(boundaries)) => {
+                let width = width.max(1);
                 let mut ali_string = String::new();
-                let mut start_offset: usize = 0;
-                let mut end_offset: usize = 80;
 
                 let name_width = max(
                     Field::Query.extract_from(self).len(),
                     Field::Target.extract_from(self).len(),
                 );
 
+                // the annotation lines are indented past
+                // the name and start coordinate columns
+                let label_width = name_width + 6;
+
                 ali_string.push_str(&self.vert_string());
                 ali_string.push_str("\n==\n\n");
 
                 let mut profile_offset = 0;
                 let mut target_offset = 0;
 
-                while start_offset <= boundaries.length {
-                    start_offset = min(start_offset, boundaries.length);
-                    end_offset = min(end_offset, boundaries.length);
+                for start_offset in (0..boundaries.length).step_by(width) {
+                    let end_offset = min(start_offset + width, boundaries.length);
 
                     let profile_slice = &display.profile_string[start_offset..end_offset];
                     let target_slice = &display.target_string[start_offset..end_offset];
@@ -394,6 +480,26 @@ impl Alignment {
                         .filter(|&c| c != Self::TARGET_GAP_BYTE)
                         .count();
 
+                    // consensus structure
+                    if let Some(ref structure_string) = display.structure_string {
+                        ali_string.push_str(&format!(
+                            "{:W$} {} CS\n",
+                            "",
+                            &structure_string[start_offset..end_offset],
+                            W = label_width
+                        ));
+                    }
+
+                    // reference annotation
+                    if let Some(ref reference_string) = display.reference_string {
+                        ali_string.push_str(&format!(
+                            "{:W$} {} RF\n",
+                            "",
+                            &reference_string[start_offset..end_offset],
+                            W = label_width
+                        ));
+                    }
+
                     // profile sequence
                     ali_string.push_str(&format!(
                         "{:>W$} {:5} {} {:<5}\n",
@@ -406,11 +512,10 @@ impl Alignment {
 
                     // middle line
                     ali_string.push_str(&format!(
-                        "{:W$} {:5} {}\n",
-                        "",
+                        "{:W$} {}\n",
                         "",
                         &display.middle_string[start_offset..end_offset],
-                        W = name_width
+                        W = label_width
                     ));
 
                     // target sequence
@@ -428,15 +533,11 @@ impl Alignment {
 
                     // position-specific posterior probabilities
                     ali_string.push_str(&format!(
-                        "{:W$} {:5} {}\n\n",
-                        "",
+                        "{:W$} {} PP\n\n",
                         "",
                         &display.posterior_string[start_offset..end_offset],
-                        W = name_width
+                        W = label_width
                     ));
-
-                    start_offset += 80;
-                    end_offset += 80;
                 }
                 ali_string.push_str("//");
 
@@ -446,3 +547,147 @@ impl Alignment {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A short alignment with a profile insert and a target gap,
+    /// along with the RF and CS annotation lines.
+    fn fixture_alignment() -> Alignment {
+        Alignment {
+            profile_name: Some("q1".to_string()),
+            target_name: Some("t1".to_string()),
+            profile_accession: None,
+            target_description: None,
+            profile_length: Some(20),
+            target_length: Some(30),
+            boundaries: Some(Boundaries {
+                length: 10,
+                profile_start: 3,
+                profile_end: 11,
+                target_start: 10,
+                target_end: 18,
+            }),
+            scores: Scores {
+                forward_score: Bits(25.0),
+                forward_p_value: 1e-6,
+                bit_score: Bits(24.5),
+                null_two_score: Some(Bits(0.5)),
+                p_value: 1e-6,
+                e_value: 1e-3,
+            },
+            cell_stats: Some(CellStats {
+                count: 60,
+                fraction: 0.1,
+            }),
+            display_strings: Some(DisplayStrings {
+                profile_string: "acde.fghik".to_string(),
+                target_string: "AC-EMFGHIK".to_string(),
+                middle_string: "AC E+FGHIK".to_string(),
+                posterior_string: "99.9*99999".to_string(),
+                reference_string: Some("xxxx.xxxxx".to_string()),
+                structure_string: Some("<<<-.->>>>".to_string()),
+            }),
+            trace: None,
+        }
+    }
+
+    #[test]
+    fn test_ali_string_wrapping() {
+        let alignment = fixture_alignment();
+        let ali_string = alignment.ali_string_with_width(4);
+
+        let blocks: Vec<Vec<&str>> = ali_string
+            .split("\n==\n\n")
+            .nth(1)
+            .unwrap()
+            .trim_end_matches("//")
+            .split("\n\n")
+            .filter(|block| !block.is_empty())
+            .map(|block| block.lines().map(|line| line.trim_end()).collect())
+            .collect();
+
+        // 10 columns at a width of 4 wrap into blocks of 4, 4, and 2
+        assert_eq!(
+            blocks,
+            vec![
+                vec![
+                    "         <<<- CS",
+                    "         xxxx RF",
+                    "q1     3 acde 6",
+                    "         AC E",
+                    "t1    10 AC-E 12",
+                    "         99.9 PP",
+                ],
+                vec![
+                    "         .->> CS",
+                    "         .xxx RF",
+                    "q1     7 .fgh 9",
+                    "         +FGH",
+                    "t1    13 MFGH 16",
+                    "         *999 PP",
+                ],
+                vec![
+                    "         >> CS",
+                    "         xx RF",
+                    "q1    10 ik 11",
+                    "         IK",
+                    "t1    17 IK 18",
+                    "         99 PP",
+                ],
+            ]
+        );
+
+        // a width that covers the whole alignment gives a single block
+        let unwrapped = alignment.ali_string_with_width(Alignment::DEFAULT_ALI_WIDTH);
+        assert_eq!(unwrapped.matches(" PP\n").count(), 1);
+        assert!(unwrapped.contains("q1     3 acde.fghik 11"));
+        assert!(unwrapped.contains("t1    10 AC-EMFGHIK 18"));
+    }
+
+    #[test]
+    fn test_alignment_json() -> anyhow::Result<()> {
+        let mut alignment = fixture_alignment();
+
+        let json = serde_json::to_value(&alignment)?;
+        assert_eq!(json["profile_name"], "q1");
+        assert_eq!(json["target_name"], "t1");
+        assert!(json["profile_accession"].is_null());
+        assert_eq!(json["boundaries"]["profile_start"], 3);
+        assert_eq!(json["boundaries"]["target_end"], 18);
+        assert_eq!(json["scores"]["bit_score"], 24.5);
+        assert_eq!(json["scores"]["null_two_score"], 0.5);
+        assert_eq!(json["scores"]["e_value"], 1e-3);
+        assert_eq!(json["cell_stats"]["count"], 60);
+        assert_eq!(json["display_strings"]["target_string"], "AC-EMFGHIK");
+        assert_eq!(json["display_strings"]["reference_string"], "xxxx.xxxxx");
+        assert!(json["trace"].is_null());
+
+        alignment.trace = Some(vec![
+            TraceStep {
+                state: Trace::M_STATE,
+                profile_idx: 3,
+                target_idx: 10,
+                posterior: 0.5,
+            },
+            TraceStep {
+                state: Trace::I_STATE,
+                profile_idx: 3,
+                target_idx: 11,
+                posterior: 0.25,
+            },
+        ]);
+
+        let json = serde_json::to_value(&alignment)?;
+        assert_eq!(
+            json["trace"],
+            serde_json::json!([
+                { "state": "M", "profile_idx": 3, "target_idx": 10, "posterior": 0.5 },
+                { "state": "I", "profile_idx": 3, "target_idx": 11, "posterior": 0.25 },
+            ])
+        );
+
+        Ok(())
+    }
+}
@@ -19,4 +19,4 @@ mod seed;
 pub use seed::Seed;
 
 mod trace;
-pub use trace::Trace;
+pub use trace::{Trace, TraceStep};
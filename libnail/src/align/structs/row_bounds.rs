@@ -1,4 +1,5 @@
 use crate::align::structs::anti_diagonal_bounds::AntiDiagonalBounds;
+use crate::align::structs::Seed;
 use anyhow::Result;
 use std::fmt::{Debug, Formatter};
 use std::io::Write;
@@ -100,6 +101,90 @@ impl RowBounds {
             self.left_row_bounds[row_idx] = profile_start;
             self.right_row_bounds[row_idx] = profile_end;
         }
+
+        self.num_cells = (target_end - target_start + 1) * (profile_end - profile_start + 1);
+    }
+
+    /// Fill a band of half-width `width` around the diagonal that runs through the seed.
+    ///
+    /// Within the seed, the diagonal is interpolated between its corners;
+    /// outside of it, the diagonal is extended with a slope of one.
+    pub fn fill_band(
+        &mut self,
+        seed: &Seed,
+        target_length: usize,
+        profile_length: usize,
+        width: usize,
+    ) {
+        self.reuse(target_length);
+        self.target_length = target_length;
+        self.profile_length = profile_length;
+
+        let width = width as i64;
+        let profile_length = profile_length as i64;
+        let (target_start, target_end) = (seed.target_start as i64, seed.target_end as i64);
+        let (profile_start, profile_end) = (seed.profile_start as i64, seed.profile_end as i64);
+
+        let diagonal = |target_idx: i64| -> i64 {
+            if target_idx < target_start {
+                profile_start - (target_start - target_idx)
+            } else if target_idx > target_end {
+                profile_end + (target_idx - target_end)
+            } else if target_end == target_start {
+                profile_start
+            } else {
+                profile_start
+                    + (target_idx - target_start) * (profile_end - profile_start)
+                        / (target_end - target_start)
+            }
+        };
+
+        // the diagonal never decreases, so the rows
+        // that intersect the profile are contiguous
+        let mut first_row = None;
+        let mut last_row = 0;
+        for target_idx in 1..=target_length {
+            let center = diagonal(target_idx as i64);
+            let left = (center - width).max(1);
+            let right = (center + width).min(profile_length);
+            if left > right {
+                if first_row.is_some() {
+                    break;
+                }
+                continue;
+            }
+
+            let mut left = left as usize;
+            let right = right as usize;
+            match first_row {
+                // a steep diagonal can jump past the previous
+                // row, so we stretch the row back to meet it
+                Some(_) => left = left.min(self.right_row_bounds[target_idx - 1]),
+                None => first_row = Some(target_idx),
+            }
+
+            self.left_row_bounds[target_idx] = left;
+            self.right_row_bounds[target_idx] = right;
+            last_row = target_idx;
+        }
+
+        match first_row {
+            Some(first_row) => {
+                self.target_start = first_row;
+                self.target_end = last_row;
+            }
+            // this only happens for a degenerate seed, so
+            // we fall back to the row through its start
+            None => {
+                let row = seed.target_start.clamp(1, target_length);
+                self.target_start = row;
+                self.target_end = row;
+                self.left_row_bounds[row] = 1;
+                self.right_row_bounds[row] = profile_length as usize;
+            }
+        }
+
+        self.num_cells = self.count_cells();
     }
 
     pub fn valid(&self) -> bool {
@@ -161,3 +246,83 @@ impl Debug for RowBounds {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed(
+        target_start: usize,
+        target_end: usize,
+        profile_start: usize,
+        profile_end: usize,
+    ) -> Seed {
+        Seed {
+            target_start,
+            target_end,
+            profile_start,
+            profile_end,
+            score: 0.0,
+        }
+    }
+
+    fn rows(bounds: &RowBounds) -> Vec<(usize, usize)> {
+        (bounds.target_start..=bounds.target_end)
+            .map(|row_idx| {
+                (
+                    bounds.left_row_bounds[row_idx],
+                    bounds.right_row_bounds[row_idx],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fill_band_steep_diagonal() {
+        let mut bounds = RowBounds::default();
+        bounds.fill_band(&seed(3, 5, 10, 60), 10, 100, 2);
+
+        assert_eq!((bounds.target_start, bounds.target_end), (1, 10));
+        // the rows through the seed are stretched back to meet the row above
+        assert_eq!(
+            rows(&bounds)[..6],
+            [(6, 10), (7, 11), (8, 12), (12, 37), (37, 62), (59, 63)]
+        );
+        assert!(bounds.valid());
+        assert_eq!(bounds.num_cells, bounds.count_cells());
+    }
+
+    #[test]
+    fn test_fill_band_matrix_edges() {
+        // a seed on the main diagonal is clamped to the corners
+        let mut bounds = RowBounds::default();
+        bounds.fill_band(&seed(1, 10, 1, 10), 10, 10, 3);
+
+        assert_eq!((bounds.target_start, bounds.target_end), (1, 10));
+        assert_eq!(rows(&bounds)[0], (1, 4));
+        assert_eq!(rows(&bounds)[9], (7, 10));
+        assert!(bounds.valid());
+
+        // a seed that ends in the last cell of the matrix starts the
+        // band at the first row whose diagonal reaches the profile
+        bounds.fill_band(&seed(15, 20, 5, 10), 20, 10, 3);
+
+        assert_eq!((bounds.target_start, bounds.target_end), (8, 20));
+        assert_eq!(rows(&bounds)[0], (1, 1));
+        assert_eq!(rows(&bounds)[12], (7, 10));
+        assert!(bounds.valid());
+        assert_eq!(bounds.num_cells, bounds.count_cells());
+    }
+
+    #[test]
+    fn test_fill_band_zero_width() {
+        let mut bounds = RowBounds::default();
+        bounds.fill_band(&seed(1, 5, 1, 5), 5, 5, 0);
+
+        // each row reaches back to the previous diagonal cell
+        assert_eq!((bounds.target_start, bounds.target_end), (1, 5));
+        assert_eq!(rows(&bounds), [(1, 1), (1, 2), (2, 3), (3, 4), (4, 5)]);
+        assert!(bounds.valid());
+        assert_eq!(bounds.num_cells, 9);
+    }
+}
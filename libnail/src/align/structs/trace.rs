@@ -1,8 +1,21 @@
 use crate::alphabet::AMINO_INVERSE_MAP;
 use crate::structs::{Profile, Sequence};
+use serde::{Serialize, Serializer};
 use std::io::Write;
 
+/// Serialize a trace state as its name, e.g. "M".
+fn serialize_state<S: Serializer>(state: &usize, serializer: S) -> Result<S::Ok, S::Error> {
+    match Trace::TRACE_IDX_TO_NAME.get(*state) {
+        Some(name) => serializer.serialize_str(name),
+        None => Err(serde::ser::Error::custom(format!(
+            "invalid trace state: {state}"
+        ))),
+    }
+}
+
+#[derive(Clone, Serialize)]
 pub struct TraceStep {
+    #[serde(serialize_with = "serialize_state")]
     pub state: usize,
     pub profile_idx: usize,
     pub target_idx: usize,
@@ -75,8 +88,7 @@ impl Trace {
         let last = self
             .states
             .iter()
-            .rev()
-            .position(|&s| s == Self::M_STATE || s == Self::I_STATE || s == Self::D_STATE);
+            .rposition(|&s| s == Self::M_STATE || s == Self::I_STATE || s == Self::D_STATE);
 
         match (first, last) {
             (Some(first), Some(last)) => (first..=last)
@@ -294,3 +306,50 @@ impl Trace {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_iter() {
+        let mut trace = Trace::new(6, 4);
+        [
+            (Trace::S_STATE, 0, 0),
+            (Trace::N_STATE, 0, 0),
+            (Trace::N_STATE, 1, 0),
+            (Trace::B_STATE, 0, 0),
+            (Trace::M_STATE, 2, 1),
+            (Trace::I_STATE, 3, 0),
+            (Trace::M_STATE, 4, 2),
+            (Trace::D_STATE, 0, 3),
+            (Trace::M_STATE, 5, 4),
+            (Trace::E_STATE, 0, 0),
+            (Trace::C_STATE, 0, 0),
+            (Trace::C_STATE, 6, 0),
+            (Trace::T_STATE, 0, 0),
+        ]
+        .into_iter()
+        .for_each(|(state, target_idx, profile_idx)| {
+            trace.append_with_posterior_probability(state, target_idx, profile_idx, 1.0)
+        });
+
+        let core: Vec<(usize, usize, usize)> = trace
+            .core_iter()
+            .map(|step| (step.state, step.target_idx, step.profile_idx))
+            .collect();
+
+        assert_eq!(
+            core,
+            vec![
+                (Trace::M_STATE, 2, 1),
+                (Trace::I_STATE, 3, 0),
+                (Trace::M_STATE, 4, 2),
+                (Trace::D_STATE, 0, 3),
+                (Trace::M_STATE, 5, 4),
+            ]
+        );
+
+        assert_eq!(Trace::new(6, 4).core_iter().count(), 0);
+    }
+}
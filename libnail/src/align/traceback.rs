@@ -1,4 +1,4 @@
-use crate::align::structs::{DpMatrix, Trace};
+use crate::align::structs::{DpMatrix, RowBounds, Trace};
 use crate::structs::Profile;
 
 pub fn traceback(
@@ -209,6 +209,211 @@ pub fn traceback(
     trace.reverse();
 }
 
+/// Trace back the maximum scoring path through a matrix filled by `viterbi()`.
+///
+/// The posterior probability of each step is read from the posterior matrix,
+/// so the trace can be displayed in the same way as an optimal accuracy trace.
+pub fn viterbi_traceback(
+    profile: &Profile,
+    posterior_matrix: &impl DpMatrix,
+    viterbi_matrix: &impl DpMatrix,
+    trace: &mut Trace,
+    bounds: &RowBounds,
+) {
+    let mut target_idx = bounds.target_end;
+    let mut profile_idx = 0;
+
+    // we trace back starting from the last C state
+    let mut previous_state: usize = Trace::C_STATE;
+    let mut current_state: usize;
+
+    trace.append_with_posterior_probability(Trace::T_STATE, target_idx, profile_idx, 0.0);
+    trace.append_with_posterior_probability(Trace::C_STATE, target_idx, profile_idx, 0.0);
+
+    while previous_state != Trace::S_STATE {
+        current_state = match previous_state {
+            Trace::C_STATE => {
+                let c_to_c_path = viterbi_matrix
+                    .get_special(target_idx - 1, Profile::SPECIAL_C_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_C_IDX,
+                        Profile::SPECIAL_LOOP_IDX,
+                    );
+
+                let c_to_e_path = viterbi_matrix.get_special(target_idx, Profile::SPECIAL_E_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_E_IDX,
+                        Profile::SPECIAL_MOVE_IDX,
+                    );
+
+                if c_to_c_path > c_to_e_path {
+                    Trace::C_STATE
+                } else {
+                    Trace::E_STATE
+                }
+            }
+            Trace::E_STATE => {
+                let mut max_score = -f32::INFINITY;
+                let mut state_of_max_score = Trace::M_STATE;
+                let mut profile_idx_of_max_score = bounds.left_row_bounds[target_idx];
+
+                for profile_idx in
+                    bounds.left_row_bounds[target_idx]..=bounds.right_row_bounds[target_idx]
+                {
+                    if viterbi_matrix.get_match(target_idx, profile_idx) > max_score {
+                        max_score = viterbi_matrix.get_match(target_idx, profile_idx);
+                        state_of_max_score = Trace::M_STATE;
+                        profile_idx_of_max_score = profile_idx;
+                    }
+                    if viterbi_matrix.get_delete(target_idx, profile_idx) > max_score {
+                        max_score = viterbi_matrix.get_delete(target_idx, profile_idx);
+                        state_of_max_score = Trace::D_STATE;
+                        profile_idx_of_max_score = profile_idx;
+                    }
+                }
+                profile_idx = profile_idx_of_max_score;
+                state_of_max_score
+            }
+            Trace::M_STATE => {
+                let possible_states: [usize; 4] = [
+                    Trace::M_STATE,
+                    Trace::I_STATE,
+                    Trace::D_STATE,
+                    Trace::B_STATE,
+                ];
+
+                let possible_paths: [f32; 4] = [
+                    viterbi_matrix.get_match(target_idx - 1, profile_idx - 1)
+                        + profile.transition_score(Profile::MATCH_TO_MATCH_IDX, profile_idx - 1),
+                    viterbi_matrix.get_insert(target_idx - 1, profile_idx - 1)
+                        + profile.transition_score(Profile::INSERT_TO_MATCH_IDX, profile_idx - 1),
+                    viterbi_matrix.get_delete(target_idx - 1, profile_idx - 1)
+                        + profile.transition_score(Profile::DELETE_TO_MATCH_IDX, profile_idx - 1),
+                    viterbi_matrix.get_special(target_idx - 1, Profile::SPECIAL_B_IDX)
+                        + profile.transition_score(Profile::BEGIN_TO_MATCH_IDX, profile_idx - 1),
+                ];
+
+                let argmax = (1..4).fold(0, |argmax, i| {
+                    if possible_paths[i] > possible_paths[argmax] {
+                        i
+                    } else {
+                        argmax
+                    }
+                });
+
+                profile_idx -= 1;
+                target_idx -= 1;
+
+                possible_states[argmax]
+            }
+            Trace::I_STATE => {
+                let match_to_insert_path = viterbi_matrix.get_match(target_idx - 1, profile_idx)
+                    + profile.transition_score(Profile::MATCH_TO_INSERT_IDX, profile_idx);
+
+                let insert_to_insert_path = viterbi_matrix.get_insert(target_idx - 1, profile_idx)
+                    + profile.transition_score(Profile::INSERT_TO_INSERT_IDX, profile_idx);
+
+                target_idx -= 1;
+
+                if match_to_insert_path >= insert_to_insert_path {
+                    Trace::M_STATE
+                } else {
+                    Trace::I_STATE
+                }
+            }
+            Trace::D_STATE => {
+                let match_to_delete_path = viterbi_matrix.get_match(target_idx, profile_idx - 1)
+                    + profile.transition_score(Profile::MATCH_TO_DELETE_IDX, profile_idx - 1);
+
+                let delete_to_delete_path = viterbi_matrix.get_delete(target_idx, profile_idx - 1)
+                    + profile.transition_score(Profile::DELETE_TO_DELETE_IDX, profile_idx - 1);
+
+                profile_idx -= 1;
+
+                if match_to_delete_path >= delete_to_delete_path {
+                    Trace::M_STATE
+                } else {
+                    Trace::D_STATE
+                }
+            }
+            Trace::B_STATE => {
+                let n_to_b_path = viterbi_matrix.get_special(target_idx, Profile::SPECIAL_N_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_N_IDX,
+                        Profile::SPECIAL_MOVE_IDX,
+                    );
+
+                let j_to_b_path = viterbi_matrix.get_special(target_idx, Profile::SPECIAL_J_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_J_IDX,
+                        Profile::SPECIAL_MOVE_IDX,
+                    );
+
+                if n_to_b_path >= j_to_b_path {
+                    Trace::N_STATE
+                } else {
+                    Trace::J_STATE
+                }
+            }
+            Trace::N_STATE => {
+                if target_idx == 0 {
+                    Trace::S_STATE
+                } else {
+                    Trace::N_STATE
+                }
+            }
+            Trace::J_STATE => {
+                let j_to_j_path = viterbi_matrix
+                    .get_special(target_idx - 1, Profile::SPECIAL_J_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_J_IDX,
+                        Profile::SPECIAL_LOOP_IDX,
+                    );
+
+                let e_to_j_path = viterbi_matrix.get_special(target_idx, Profile::SPECIAL_E_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_E_IDX,
+                        Profile::SPECIAL_LOOP_IDX,
+                    );
+
+                if j_to_j_path > e_to_j_path {
+                    Trace::J_STATE
+                } else {
+                    Trace::E_STATE
+                }
+            }
+            _ => {
+                panic!("bad state in traceback")
+            }
+        };
+
+        let posterior_probability = get_posterior_probability(
+            posterior_matrix,
+            current_state,
+            previous_state,
+            profile_idx,
+            target_idx,
+        );
+
+        trace.append_with_posterior_probability(
+            current_state,
+            target_idx,
+            profile_idx,
+            posterior_probability,
+        );
+
+        if (current_state == Trace::N_STATE
+            || current_state == Trace::J_STATE
+            || current_state == Trace::C_STATE)
+            && current_state == previous_state
+        {
+            target_idx -= 1;
+        }
+        previous_state = current_state;
+    }
+    trace.reverse();
+}
+
 pub fn get_posterior_probability(
     optimal_matrix: &impl DpMatrix,
     current_state: usize,
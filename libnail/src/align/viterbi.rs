@@ -0,0 +1,348 @@
+use crate::align::structs::{DpMatrix, RowBounds};
+use crate::max_f32;
+use crate::structs::{Profile, Sequence};
+
+use super::Nats;
+
+/// Compute the Viterbi (maximum scoring path) score of a target
+/// against a profile, using the full DP matrix in linear memory.
+///
+/// This is used for calibrating score distributions, where only the
+/// score is needed, so no traceback information is retained.
+pub fn viterbi_score(profile: &Profile, target: &Sequence) -> Nats {
+    let num_cols = profile.length + 1;
+
+    let mut prev_match = vec![-f32::INFINITY; num_cols];
+    let mut prev_insert = vec![-f32::INFINITY; num_cols];
+    let mut prev_delete = vec![-f32::INFINITY; num_cols];
+    let mut cur_match = vec![-f32::INFINITY; num_cols];
+    let mut cur_insert = vec![-f32::INFINITY; num_cols];
+    let mut cur_delete = vec![-f32::INFINITY; num_cols];
+
+    let n_loop =
+        profile.special_transition_score(Profile::SPECIAL_N_IDX, Profile::SPECIAL_LOOP_IDX);
+    let n_move =
+        profile.special_transition_score(Profile::SPECIAL_N_IDX, Profile::SPECIAL_MOVE_IDX);
+    let e_loop =
+        profile.special_transition_score(Profile::SPECIAL_E_IDX, Profile::SPECIAL_LOOP_IDX);
+    let e_move =
+        profile.special_transition_score(Profile::SPECIAL_E_IDX, Profile::SPECIAL_MOVE_IDX);
+    let j_loop =
+        profile.special_transition_score(Profile::SPECIAL_J_IDX, Profile::SPECIAL_LOOP_IDX);
+    let j_move =
+        profile.special_transition_score(Profile::SPECIAL_J_IDX, Profile::SPECIAL_MOVE_IDX);
+    let c_loop =
+        profile.special_transition_score(Profile::SPECIAL_C_IDX, Profile::SPECIAL_LOOP_IDX);
+    let c_move =
+        profile.special_transition_score(Profile::SPECIAL_C_IDX, Profile::SPECIAL_MOVE_IDX);
+
+    let mut n_score = 0.0f32;
+    let mut b_score = n_move;
+    let mut j_score = -f32::INFINITY;
+    let mut c_score = -f32::INFINITY;
+
+    for target_idx in 1..=target.length {
+        let residue = target.digital_bytes[target_idx] as usize;
+        let mut e_score = -f32::INFINITY;
+
+        for profile_idx in 1..=profile.length {
+            cur_match[profile_idx] = (prev_match[profile_idx - 1]
+                + profile.transition_score(Profile::MATCH_TO_MATCH_IDX, profile_idx - 1))
+            .max(
+                prev_insert[profile_idx - 1]
+                    + profile.transition_score(Profile::INSERT_TO_MATCH_IDX, profile_idx - 1),
+            )
+            .max(
+                prev_delete[profile_idx - 1]
+                    + profile.transition_score(Profile::DELETE_TO_MATCH_IDX, profile_idx - 1),
+            )
+            .max(b_score + profile.transition_score(Profile::BEGIN_TO_MATCH_IDX, profile_idx - 1))
+                + profile.match_score(residue, profile_idx);
+
+            cur_insert[profile_idx] = if profile_idx < profile.length {
+                (prev_match[profile_idx]
+                    + profile.transition_score(Profile::MATCH_TO_INSERT_IDX, profile_idx))
+                .max(
+                    prev_insert[profile_idx]
+                        + profile.transition_score(Profile::INSERT_TO_INSERT_IDX, profile_idx),
+                ) + profile.insert_score(residue, profile_idx)
+            } else {
+                -f32::INFINITY
+            };
+
+            cur_delete[profile_idx] = (cur_match[profile_idx - 1]
+                + profile.transition_score(Profile::MATCH_TO_DELETE_IDX, profile_idx - 1))
+            .max(
+                cur_delete[profile_idx - 1]
+                    + profile.transition_score(Profile::DELETE_TO_DELETE_IDX, profile_idx - 1),
+            );
+
+            e_score = e_score
+                .max(cur_match[profile_idx])
+                .max(cur_delete[profile_idx]);
+        }
+
+        j_score = (j_score + j_loop).max(e_score + e_loop);
+        c_score = (c_score + c_loop).max(e_score + e_move);
+        n_score += n_loop;
+        b_score = (n_score + n_move).max(j_score + j_move);
+
+        std::mem::swap(&mut prev_match, &mut cur_match);
+        std::mem::swap(&mut prev_insert, &mut cur_insert);
+        std::mem::swap(&mut prev_delete, &mut cur_delete);
+    }
+
+    Nats(c_score + c_move)
+}
+
+/// Fill a sparse Viterbi matrix over the cells in the row bounds, returning
+/// the score of the maximum scoring path through the bounds.
+///
+/// This follows the same recurrence as `forward()`, with a max in place of
+/// each log sum, so that the matrix can be traced back with `viterbi_traceback()`.
+pub fn viterbi(
+    profile: &Profile,
+    target: &Sequence,
+    dp_matrix: &mut impl DpMatrix,
+    bounds: &RowBounds,
+) -> Nats {
+    let end_score: f32 = 0.0;
+
+    dp_matrix.set_special(bounds.target_start - 1, Profile::SPECIAL_N_IDX, 0.0);
+    dp_matrix.set_special(
+        bounds.target_start - 1,
+        Profile::SPECIAL_B_IDX,
+        profile.special_transition_score(Profile::SPECIAL_N_IDX, Profile::SPECIAL_MOVE_IDX),
+    );
+    dp_matrix.set_special(
+        bounds.target_start - 1,
+        Profile::SPECIAL_E_IDX,
+        -f32::INFINITY,
+    );
+    dp_matrix.set_special(
+        bounds.target_start - 1,
+        Profile::SPECIAL_C_IDX,
+        -f32::INFINITY,
+    );
+    dp_matrix.set_special(
+        bounds.target_start - 1,
+        Profile::SPECIAL_J_IDX,
+        -f32::INFINITY,
+    );
+
+    for target_idx in bounds.target_start..=bounds.target_end {
+        let current_target_character = target.digital_bytes[target_idx];
+
+        dp_matrix.set_special(target_idx, Profile::SPECIAL_E_IDX, -f32::INFINITY);
+
+        for profile_idx in bounds.left_row_bounds[target_idx]..bounds.right_row_bounds[target_idx] {
+            // match state
+            dp_matrix.set_match(
+                target_idx,
+                profile_idx,
+                max_f32!(
+                    dp_matrix.get_match(target_idx - 1, profile_idx - 1)
+                        + profile.transition_score(Profile::MATCH_TO_MATCH_IDX, profile_idx - 1),
+                    dp_matrix.get_insert(target_idx - 1, profile_idx - 1)
+                        + profile.transition_score(Profile::INSERT_TO_MATCH_IDX, profile_idx - 1),
+                    dp_matrix.get_special(target_idx - 1, Profile::SPECIAL_B_IDX)
+                        + profile.transition_score(Profile::BEGIN_TO_MATCH_IDX, profile_idx - 1),
+                    dp_matrix.get_delete(target_idx - 1, profile_idx - 1)
+                        + profile.transition_score(Profile::DELETE_TO_MATCH_IDX, profile_idx - 1)
+                ) + profile.match_score(current_target_character as usize, profile_idx),
+            );
+
+            // insert state
+            dp_matrix.set_insert(
+                target_idx,
+                profile_idx,
+                max_f32!(
+                    dp_matrix.get_match(target_idx - 1, profile_idx)
+                        + profile.transition_score(Profile::MATCH_TO_INSERT_IDX, profile_idx),
+                    dp_matrix.get_insert(target_idx - 1, profile_idx)
+                        + profile.transition_score(Profile::INSERT_TO_INSERT_IDX, profile_idx)
+                ) + profile.insert_score(current_target_character as usize, profile_idx),
+            );
+
+            // delete state
+            dp_matrix.set_delete(
+                target_idx,
+                profile_idx,
+                max_f32!(
+                    dp_matrix.get_match(target_idx, profile_idx - 1)
+                        + profile.transition_score(Profile::MATCH_TO_DELETE_IDX, profile_idx - 1),
+                    dp_matrix.get_delete(target_idx, profile_idx - 1)
+                        + profile.transition_score(Profile::DELETE_TO_DELETE_IDX, profile_idx - 1)
+                ),
+            );
+
+            // E state
+            dp_matrix.set_special(
+                target_idx,
+                Profile::SPECIAL_E_IDX,
+                max_f32!(
+                    dp_matrix.get_match(target_idx, profile_idx) + end_score,
+                    dp_matrix.get_delete(target_idx, profile_idx) + end_score,
+                    dp_matrix.get_special(target_idx, Profile::SPECIAL_E_IDX)
+                ),
+            );
+        }
+
+        let last_profile_idx = bounds.right_row_bounds[target_idx];
+
+        // unrolled match state match[M]
+        dp_matrix.set_match(
+            target_idx,
+            last_profile_idx,
+            max_f32!(
+                dp_matrix.get_match(target_idx - 1, last_profile_idx - 1)
+                    + profile.transition_score(Profile::MATCH_TO_MATCH_IDX, last_profile_idx - 1),
+                dp_matrix.get_insert(target_idx - 1, last_profile_idx - 1)
+                    + profile.transition_score(Profile::INSERT_TO_MATCH_IDX, last_profile_idx - 1),
+                dp_matrix.get_special(target_idx - 1, Profile::SPECIAL_B_IDX)
+                    + profile.transition_score(Profile::BEGIN_TO_MATCH_IDX, last_profile_idx - 1),
+                dp_matrix.get_delete(target_idx - 1, last_profile_idx - 1)
+                    + profile.transition_score(Profile::DELETE_TO_MATCH_IDX, last_profile_idx - 1)
+            ) + profile.match_score(current_target_character as usize, last_profile_idx),
+        );
+
+        // unrolled insert state insert[M]
+        dp_matrix.set_insert(target_idx, last_profile_idx, -f32::INFINITY);
+
+        // unrolled delete state delete[M]
+        dp_matrix.set_delete(
+            target_idx,
+            last_profile_idx,
+            max_f32!(
+                dp_matrix.get_match(target_idx, last_profile_idx - 1)
+                    + profile.transition_score(Profile::MATCH_TO_DELETE_IDX, last_profile_idx - 1),
+                dp_matrix.get_delete(target_idx, last_profile_idx - 1)
+                    + profile.transition_score(Profile::DELETE_TO_DELETE_IDX, last_profile_idx - 1)
+            ),
+        );
+
+        // unrolled E state
+        dp_matrix.set_special(
+            target_idx,
+            Profile::SPECIAL_E_IDX,
+            max_f32!(
+                dp_matrix.get_match(target_idx, last_profile_idx),
+                dp_matrix.get_delete(target_idx, last_profile_idx),
+                dp_matrix.get_special(target_idx, Profile::SPECIAL_E_IDX)
+            ),
+        );
+
+        // unrolled J state
+        dp_matrix.set_special(
+            target_idx,
+            Profile::SPECIAL_J_IDX,
+            max_f32!(
+                dp_matrix.get_special(target_idx - 1, Profile::SPECIAL_J_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_J_IDX,
+                        Profile::SPECIAL_LOOP_IDX
+                    ),
+                dp_matrix.get_special(target_idx, Profile::SPECIAL_E_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_E_IDX,
+                        Profile::SPECIAL_LOOP_IDX
+                    )
+            ),
+        );
+
+        // unrolled C state
+        dp_matrix.set_special(
+            target_idx,
+            Profile::SPECIAL_C_IDX,
+            max_f32!(
+                dp_matrix.get_special(target_idx - 1, Profile::SPECIAL_C_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_C_IDX,
+                        Profile::SPECIAL_LOOP_IDX
+                    ),
+                dp_matrix.get_special(target_idx, Profile::SPECIAL_E_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_E_IDX,
+                        Profile::SPECIAL_MOVE_IDX
+                    )
+            ),
+        );
+
+        // unrolled N state
+        dp_matrix.set_special(
+            target_idx,
+            Profile::SPECIAL_N_IDX,
+            dp_matrix.get_special(target_idx - 1, Profile::SPECIAL_N_IDX)
+                + profile
+                    .special_transition_score(Profile::SPECIAL_N_IDX, Profile::SPECIAL_LOOP_IDX),
+        );
+
+        // unrolled B state
+        dp_matrix.set_special(
+            target_idx,
+            Profile::SPECIAL_B_IDX,
+            max_f32!(
+                dp_matrix.get_special(target_idx, Profile::SPECIAL_N_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_N_IDX,
+                        Profile::SPECIAL_MOVE_IDX
+                    ),
+                dp_matrix.get_special(target_idx, Profile::SPECIAL_J_IDX)
+                    + profile.special_transition_score(
+                        Profile::SPECIAL_J_IDX,
+                        Profile::SPECIAL_MOVE_IDX
+                    )
+            ),
+        );
+    }
+
+    // the unaligned target positions are emitted by the N and/or C
+    // states, just as they are in the sparse Forward score
+    let aligned_target_length = bounds.target_end - bounds.target_start + 1;
+    let unaligned_target_length = target.length - aligned_target_length;
+    let background_correction = unaligned_target_length as f32
+        * profile.special_transition_score(Profile::SPECIAL_N_IDX, Profile::SPECIAL_LOOP_IDX);
+
+    let final_c_state_score = dp_matrix.get_special(bounds.target_end, Profile::SPECIAL_C_IDX);
+    let c_to_n_score =
+        profile.special_transition_score(Profile::SPECIAL_C_IDX, Profile::SPECIAL_MOVE_IDX);
+
+    Nats(final_c_state_score + background_correction + c_to_n_score)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::align::structs::DpMatrixFlat;
+    use crate::io::{Fasta, SequenceDatabase};
+    use crate::structs::Hmm;
+
+    #[test]
+    fn test_viterbi_full_bounds() -> anyhow::Result<()> {
+        let hmms = Hmm::from_p7hmm(File::open("../fixtures/query.hmm")?)?;
+        let mut profile = Profile::new(&hmms[0]);
+        let targets = Fasta::from_path("../fixtures/target.fa")?;
+
+        for target in targets.iter().take(10) {
+            profile.configure_for_target_length(target.length);
+
+            let mut bounds = RowBounds::new(target.length);
+            bounds.fill_rectangle(1, 1, target.length, profile.length);
+            let mut dp_matrix = DpMatrixFlat::new(target.length, profile.length);
+
+            let score = viterbi(&profile, &target, &mut dp_matrix, &bounds);
+            let expected = viterbi_score(&profile, &target);
+            assert!(
+                (score.0 - expected.0).abs() < 1e-3,
+                "{}: {} != {}",
+                target.name,
+                score.0,
+                expected.0
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -6,6 +6,8 @@ pub const UTF8_PLUS: u8 = 43;
 pub const UTF8_DASH: u8 = 45;
 pub const UTF8_DOT: u8 = 46;
 pub const UTF8_PIPE: u8 = 124;
+pub const UTF8_X: u8 = 88;
+pub const DIGITAL_AMINO_X: u8 = 22;
 pub const AMINO_SENTINEL: u8 = 255;
 
 /// maps from \<usize\> -> \<UTF8 value for usize\>
@@ -0,0 +1,329 @@
+use anyhow::bail;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+
+use crate::align::structs::{DpMatrixSparse, RowBounds};
+use crate::align::{forward, msv_score, null_one_score, viterbi_score, Nats};
+use crate::structs::hmm::Stats;
+use crate::structs::profile::HitMode;
+use crate::structs::{Profile, Sequence};
+
+/// The parameters used to calibrate the score distributions of a profile.
+///
+/// The defaults match HMMER's `hmmbuild`/`hmmcalibrate` defaults.
+#[derive(Clone, Debug)]
+pub struct CalibrationParams {
+    /// The hit mode that the profile is configured in while sampling scores
+    pub hit_mode: HitMode,
+    /// The number of random sequences used to fit the MSV Gumbel
+    pub msv_samples: usize,
+    /// The length of the random sequences used to fit the MSV Gumbel
+    pub msv_length: usize,
+    /// The number of random sequences used to fit the Viterbi Gumbel
+    pub viterbi_samples: usize,
+    /// The length of the random sequences used to fit the Viterbi Gumbel
+    pub viterbi_length: usize,
+    /// The number of random sequences used to fit the Forward exponential tail
+    pub forward_samples: usize,
+    /// The length of the random sequences used to fit the Forward exponential tail
+    pub forward_length: usize,
+    /// The tail mass used to fit the Forward exponential tail
+    pub forward_tail_probability: f32,
+    /// Fit lambda to the sampled scores, rather than using
+    /// HMMER's relative entropy based estimate for all three
+    pub fit_lambda: bool,
+    /// The seed of the random sequence generator
+    pub seed: u64,
+}
+
+impl Default for CalibrationParams {
+    fn default() -> Self {
+        Self {
+            hit_mode: HitMode::MultiHit,
+            msv_samples: 200,
+            msv_length: 200,
+            viterbi_samples: 200,
+            viterbi_length: 200,
+            forward_samples: 200,
+            forward_length: 100,
+            forward_tail_probability: 0.04,
+            fit_lambda: false,
+            seed: 42,
+        }
+    }
+}
+
+/// HMMER's estimate of lambda, which is a function of the total
+/// relative entropy (in bits) of the profile's match emissions.
+pub fn lambda_from_relative_entropy(profile: &Profile) -> f32 {
+    std::f32::consts::LN_2 + 1.44 / (profile.length as f32 * profile.relative_entropy())
+}
+
+/// Convert a raw score to a null one corrected score in bits.
+fn corrected_bits(score: Nats, target_length: usize) -> f32 {
+    (score - null_one_score(target_length)).to_bits().value()
+}
+
+/// Score `n` random sequences of length `target_length` with MSV.
+pub fn msv_scores(
+    profile: &mut Profile,
+    n: usize,
+    target_length: usize,
+    rng: &mut Pcg64,
+) -> Vec<f32> {
+    profile.configure_for_target_length(target_length);
+    (0..n)
+        .map(|_| {
+            let seq = Sequence::random_amino(target_length, rng);
+            corrected_bits(msv_score(profile, &seq), target_length)
+        })
+        .collect()
+}
+
+/// Score `n` random sequences of length `target_length` with Viterbi.
+pub fn viterbi_scores(
+    profile: &mut Profile,
+    n: usize,
+    target_length: usize,
+    rng: &mut Pcg64,
+) -> Vec<f32> {
+    profile.configure_for_target_length(target_length);
+    (0..n)
+        .map(|_| {
+            let seq = Sequence::random_amino(target_length, rng);
+            corrected_bits(viterbi_score(profile, &seq), target_length)
+        })
+        .collect()
+}
+
+/// Score `n` random sequences of length `target_length` with Forward.
+pub fn forward_scores(
+    profile: &mut Profile,
+    n: usize,
+    target_length: usize,
+    rng: &mut Pcg64,
+) -> Vec<f32> {
+    profile.configure_for_target_length(target_length);
+
+    let mut row_bounds = RowBounds::new(target_length);
+    row_bounds.fill_rectangle(1, 1, target_length, profile.length);
+    let mut forward_matrix = DpMatrixSparse::new(target_length, profile.length, &row_bounds);
+
+    (0..n)
+        .map(|_| {
+            forward_matrix.reuse(target_length, profile.length, &row_bounds);
+            let seq = Sequence::random_amino(target_length, rng);
+            let score = forward(profile, &seq, &mut forward_matrix, &row_bounds);
+            corrected_bits(score, target_length)
+        })
+        .collect()
+}
+
+/// This is some black magic from a textbook:
+///   Statistical Models and Methods for
+///   Lifetime Data by Joseph F. Lawless
+///
+/// From HMMER:
+///   Equation 4.1.6 from [Lawless82], pg. 143, and
+///   its first derivative with respect to lambda,
+///   for finding the ML fit to Gumbel lambda parameter.
+///   This equation gives a result of zero for the maximum
+///   likelihood lambda.
+fn lawless416(samples: &[f32], lambda: f32) -> (f32, f32) {
+    // e_sum is the sum of e^(-lambda x_i)
+    let mut e_sum = 0.0f32;
+    // x_sum is the sum of x_i
+    let mut x_sum = 0.0f32;
+    // xe_sum is the sum of x_i * e^(-lambda x_i)
+    let mut xe_sum = 0.0f32;
+    // xe_sum is the sum of x_i^2 * e^(-lambda x_i)
+    let mut xxe_sum = 0.0f32;
+
+    samples.iter().for_each(|x| {
+        e_sum += (-lambda * x).exp();
+        x_sum += x;
+        xe_sum += x * (-lambda * x).exp();
+        xxe_sum += x.powi(2) * (-lambda * x).exp();
+    });
+
+    let fx = (1.0 / lambda) - (x_sum / samples.len() as f32) + (xe_sum / e_sum);
+    let dfx = (xe_sum / e_sum).powi(2) - (xxe_sum / e_sum) - (1.0 / (lambda.powi(2)));
+
+    (fx, dfx)
+}
+
+/// Find the maximum likelihood Gumbel location
+/// parameter mu for a known scale parameter lambda.
+///
+/// This is substituting into equation 4.1.5 from Lawless[82].
+pub fn fit_gumbel_location(scores: &[f32], lambda: f32) -> f32 {
+    let e_sum: f32 = scores.iter().map(|s| (-lambda * s).exp()).sum();
+    -(e_sum / scores.len() as f32).ln() / lambda
+}
+
+/// Find the maximum likelihood Gumbel parameters (mu, lambda) for a set of scores.
+pub fn fit_gumbel(scores: &[f32]) -> anyhow::Result<(f32, f32)> {
+    // make an initial guess at lambda
+    //
+    // from hmmer:
+    //   (Evans/Hastings/Peacock, Statistical Distributions, 2000, p.86)
+    let sum: f32 = scores.iter().sum();
+    let squared_sum: f32 = scores.iter().map(|s| s.powi(2)).sum();
+    let sample_variance: f32 =
+        (squared_sum - sum * sum / scores.len() as f32) / (scores.len() as f32 - 1.0);
+    let mut lambda: f32 = std::f32::consts::PI / (6.0 / sample_variance).sqrt();
+
+    // now we do this Newton/Raphson root finding
+    // thing until we have converged on lambda
+    let tolerance: f32 = 1e-5;
+    let mut newton_raphson_success = false;
+    for _ in 0..=100 {
+        let (fx, dfx) = lawless416(scores, lambda);
+        if fx.abs() < tolerance {
+            newton_raphson_success = true;
+            break;
+        }
+        lambda -= fx / dfx;
+
+        if lambda <= 0.0 {
+            lambda = 0.001;
+        }
+    }
+
+    if !newton_raphson_success {
+        bail!("newton/raphson failed");
+    }
+
+    Ok((fit_gumbel_location(scores, lambda), lambda))
+}
+
+/// Calculates the inverse CDF for a Gumbel distribution
+/// with parameters <mu> and <lambda>. That is, returns
+/// the quantile <x> at which the CDF is <p>.
+pub fn gumbel_inverse_cdf(p: f32, mu: f32, lambda: f32) -> f32 {
+    mu - ((-p.ln()).ln() / lambda)
+}
+
+/// Find the maximum likelihood exponential scale parameter lambda
+/// for the scores in the upper `tail_probability` mass of the sample.
+pub fn fit_exponential_tail(scores: &[f32], tail_probability: f32) -> anyhow::Result<f32> {
+    let mut sorted = scores.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+
+    let tail_size = ((scores.len() as f32 * tail_probability) as usize).min(scores.len());
+    if tail_size < 2 {
+        bail!("too few scores to fit an exponential tail");
+    }
+
+    // the origin of the tail is the highest score that
+    // isn't in it, or the lowest score in it otherwise
+    let origin = sorted
+        .get(tail_size)
+        .copied()
+        .unwrap_or(sorted[tail_size - 1]);
+    let mean_excess =
+        sorted[..tail_size].iter().map(|s| s - origin).sum::<f32>() / tail_size as f32;
+
+    if mean_excess <= 0.0 {
+        bail!("degenerate exponential tail");
+    }
+
+    Ok(1.0 / mean_excess)
+}
+
+/// Find the Forward exponential tail origin tau.
+///
+/// From hmmer:
+///   Explanation of the eqn below: first find the x at which the Gumbel tail
+///   mass is predicted to be equal to tailp. Then back up from that x
+///   by log(tailp)/lambda to set the origin of the exponential tail to 1.0
+///   instead of tailp.
+pub fn fit_tau(scores: &[f32], tail_probability: f32, lambda: f32) -> anyhow::Result<f32> {
+    // there is (maybe?) no good method for fitting an exponential,
+    // so instead we fit a Gumbel that we use to pick our tau
+    let (gumbel_mu, gumbel_lambda) = fit_gumbel(scores)?;
+    Ok(
+        gumbel_inverse_cdf(1.0 - tail_probability, gumbel_mu, gumbel_lambda)
+            + (tail_probability.ln() / lambda),
+    )
+}
+
+/// Calibrate the MSV, Viterbi, and Forward score distributions of a profile.
+///
+/// The profile itself is left unchanged; the returned
+/// stats correspond to the `STATS LOCAL` lines of an HMM file.
+pub fn calibrate(profile: &Profile, params: &CalibrationParams) -> anyhow::Result<Stats> {
+    let mut profile = profile.clone();
+    profile.configure_hit_mode(params.hit_mode);
+
+    let mut rng = Pcg64::seed_from_u64(params.seed);
+    let entropy_lambda = lambda_from_relative_entropy(&profile);
+
+    let scores = msv_scores(
+        &mut profile,
+        params.msv_samples,
+        params.msv_length,
+        &mut rng,
+    );
+    let (msv_gumble_mu, msv_gumble_lambda) = match params.fit_lambda {
+        true => fit_gumbel(&scores)?,
+        false => (fit_gumbel_location(&scores, entropy_lambda), entropy_lambda),
+    };
+
+    let scores = viterbi_scores(
+        &mut profile,
+        params.viterbi_samples,
+        params.viterbi_length,
+        &mut rng,
+    );
+    let (viterbi_gumble_mu, viterbi_gumble_lambda) = match params.fit_lambda {
+        true => fit_gumbel(&scores)?,
+        false => (fit_gumbel_location(&scores, entropy_lambda), entropy_lambda),
+    };
+
+    let scores = forward_scores(
+        &mut profile,
+        params.forward_samples,
+        params.forward_length,
+        &mut rng,
+    );
+    let forward_lambda = match params.fit_lambda {
+        true => fit_exponential_tail(&scores, params.forward_tail_probability)?,
+        false => entropy_lambda,
+    };
+    let forward_tau = fit_tau(&scores, params.forward_tail_probability, forward_lambda)?;
+
+    Ok(Stats {
+        msv_gumble_mu,
+        msv_gumble_lambda,
+        viterbi_gumble_mu,
+        viterbi_gumble_lambda,
+        forward_tau,
+        forward_lambda,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::Hmm;
+
+    #[test]
+    fn test_calibrate_matches_hmmer() -> anyhow::Result<()> {
+        let hmm_file = std::fs::File::open("../fixtures/query.hmm")?;
+        let hmm = Hmm::from_p7hmm(hmm_file)?.remove(0);
+        let profile = Profile::new(&hmm);
+
+        let stats = calibrate(&profile, &CalibrationParams::default())?;
+
+        // HMMER's values come from a different random
+        // sample, so we can only expect to be close
+        assert!((stats.msv_gumble_lambda - hmm.stats.msv_gumble_lambda).abs() < 1e-3);
+        assert!((stats.forward_lambda - hmm.stats.forward_lambda).abs() < 1e-3);
+        assert!((stats.msv_gumble_mu - hmm.stats.msv_gumble_mu).abs() < 0.5);
+        assert!((stats.viterbi_gumble_mu - hmm.stats.viterbi_gumble_mu).abs() < 0.5);
+        assert!((stats.forward_tau - hmm.stats.forward_tau).abs() < 0.5);
+
+        Ok(())
+    }
+}
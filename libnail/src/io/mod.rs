@@ -7,9 +7,9 @@ use std::{
     sync::Arc,
 };
 
+use crate::{alphabet::UTF8_TO_DIGITAL_AMINO, structs::Sequence};
 use anyhow::bail;
 use indexmap::IndexMap;
-use libnail::{alphabet::UTF8_TO_DIGITAL_AMINO, structs::Sequence};
 
 fn sequence_from_fasta_record_bytes(bytes: &[u8]) -> anyhow::Result<Sequence> {
     let header_newline_pos = match bytes.iter().position(|&b| b == b'\n') {
@@ -72,6 +72,10 @@ pub trait SequenceDatabase: dyn_clone::DynClone + Send + Sync {
     fn get(&mut self, name: &str) -> Option<Sequence>;
     fn len(&self) -> usize;
     fn iter(&self) -> SequenceDatabaseIter;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 pub struct SequenceDatabaseIter<'a> {
@@ -223,6 +227,10 @@ impl LexicalFastaIndex {
         self.offsets.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
     fn offset_by_name(&self, name: &str) -> Option<FastaOffset> {
         self.offsets.get(name).cloned()
     }
@@ -271,6 +279,10 @@ impl Fasta {
         self.index.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
     pub fn get(&mut self, name: &str) -> Option<Sequence> {
         let offset = self.index.offset_by_name(name)?;
 
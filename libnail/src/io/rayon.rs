@@ -1,4 +1,4 @@
-use libnail::structs::Sequence;
+use crate::structs::Sequence;
 use rayon::iter::{
     plumbing::{bridge, Consumer, Producer, ProducerCallback, UnindexedConsumer},
     IndexedParallelIterator, IntoParallelIterator, ParallelIterator,
@@ -1,5 +1,10 @@
 pub mod align;
 pub mod alphabet;
+pub mod calibration;
+#[cfg(feature = "fs")]
+pub mod io;
+pub mod mask;
 pub mod output;
+pub mod search;
 pub mod structs;
 pub mod util;
@@ -0,0 +1,208 @@
+#[cfg(feature = "fs")]
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::alphabet::{DIGITAL_AMINO_X, UTF8_X};
+#[cfg(feature = "fs")]
+use crate::io::{Fasta, SequenceDatabase};
+use crate::structs::Sequence;
+
+/// The parameters of the low-complexity masker.
+///
+/// These mirror the three parameters of SEG: the window length, the
+/// entropy (in bits) below which a window triggers a low-complexity
+/// region, and the entropy below which neighboring windows extend it.
+#[derive(Clone, Copy, Debug)]
+pub struct SegParams {
+    pub window: usize,
+    pub trigger_entropy: f32,
+    pub extension_entropy: f32,
+}
+
+impl Default for SegParams {
+    fn default() -> Self {
+        // the SEG defaults for protein sequences
+        Self {
+            window: 12,
+            trigger_entropy: 2.2,
+            extension_entropy: 2.5,
+        }
+    }
+}
+
+/// Compute the Shannon entropy (in bits) of every window of a sequence.
+///
+/// The entropy at index i is that of the window starting at sequence
+/// position i + 1, so there are `length - window + 1` entries.
+fn window_entropies(sequence: &Sequence, window: usize) -> Vec<f32> {
+    if window == 0 || sequence.length < window {
+        return vec![];
+    }
+
+    let residues = &sequence.digital_bytes[1..];
+
+    // every non-canonical residue is counted as a single symbol
+    let symbol = |b: u8| (b as usize).min(20);
+    let mut counts = [0usize; 21];
+    residues[..window]
+        .iter()
+        .for_each(|&b| counts[symbol(b)] += 1);
+
+    let entropy = |counts: &[usize; 21]| {
+        counts
+            .iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| c as f32 / window as f32)
+            .map(|p| -p * p.log2())
+            .sum::<f32>()
+    };
+
+    let mut entropies = Vec::with_capacity(sequence.length - window + 1);
+    entropies.push(entropy(&counts));
+
+    for idx in window..sequence.length {
+        counts[symbol(residues[idx - window])] -= 1;
+        counts[symbol(residues[idx])] += 1;
+        entropies.push(entropy(&counts));
+    }
+
+    entropies
+}
+
+/// Find the low-complexity regions of a sequence.
+///
+/// This is a simplified SEG: a region is a maximal run of windows with
+/// entropy below the extension threshold that contains at least one window
+/// below the trigger threshold. Unlike SEG, the region is not trimmed to
+/// its lowest-complexity subsequence, so masking is slightly more aggressive.
+///
+/// The regions are returned as 1-based, inclusive (start, end) positions.
+pub fn low_complexity_regions(sequence: &Sequence, params: &SegParams) -> Vec<(usize, usize)> {
+    let entropies = window_entropies(sequence, params.window);
+    let mut regions: Vec<(usize, usize)> = vec![];
+
+    let mut idx = 0;
+    while idx < entropies.len() {
+        if entropies[idx] >= params.extension_entropy {
+            idx += 1;
+            continue;
+        }
+
+        let run_start = idx;
+        let mut triggered = false;
+        while idx < entropies.len() && entropies[idx] < params.extension_entropy {
+            triggered |= entropies[idx] < params.trigger_entropy;
+            idx += 1;
+        }
+
+        if triggered {
+            // the windows run_start..idx cover positions
+            // (run_start + 1)..=(idx - 1 + window)
+            let start = run_start + 1;
+            let end = idx - 1 + params.window;
+
+            match regions.last_mut() {
+                Some((_, last_end)) if *last_end + 1 >= start => *last_end = end,
+                _ => regions.push((start, end)),
+            }
+        }
+    }
+
+    regions
+}
+
+/// Produce a copy of a sequence with its low-complexity regions replaced by X.
+pub fn mask_low_complexity(sequence: &Sequence, params: &SegParams) -> Sequence {
+    let mut masked = sequence.clone();
+
+    low_complexity_regions(sequence, params)
+        .into_iter()
+        .for_each(|(start, end)| {
+            masked.digital_bytes[start..=end].fill(DIGITAL_AMINO_X);
+            masked.utf8_bytes[start..=end].fill(UTF8_X);
+        });
+
+    masked
+}
+
+/// Write a copy of a FASTA database with the low-complexity regions of
+/// every sequence replaced by X, and return the indexed copy.
+#[cfg(feature = "fs")]
+pub fn mask_fasta(
+    fasta: &Fasta,
+    params: &SegParams,
+    path: impl AsRef<Path>,
+) -> anyhow::Result<Fasta> {
+    let mut writer = BufWriter::new(File::create(path.as_ref())?);
+
+    fasta
+        .iter()
+        .try_for_each(|sequence| writeln!(writer, "{}", mask_low_complexity(&sequence, params)))?;
+
+    writer.flush()?;
+    Fasta::from_path(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_low_complexity() -> anyhow::Result<()> {
+        let flank_a = "MKTAYIAKQRQISFVKSHFSRQLEERLGLIEVQAPILSRVGDGTQDNLSGAEKAVQVKVKALPDAQFEVV";
+        let repeat = "QQQQQQQQQQQQQQQQQQQQQQQQQ";
+        let flank_b = "HVVPCGWDNLRYELIHTVRNEAIKRGFNWLTCPHPYDEWTVEGLKGRALVLLKPDWSRIFAAGE";
+
+        let sequence = Sequence::from_utf8(format!("{flank_a}{repeat}{flank_b}").as_bytes())?;
+        let params = SegParams::default();
+
+        let regions = low_complexity_regions(&sequence, &params);
+        assert_eq!(regions.len(), 1);
+
+        let (start, end) = regions[0];
+        let repeat_start = flank_a.len() + 1;
+        let repeat_end = flank_a.len() + repeat.len();
+        assert!(start <= repeat_start && end >= repeat_end);
+        // the region can't extend further than a window past the repeat
+        assert!(start + params.window > repeat_start);
+        assert!(end < repeat_end + params.window);
+
+        let masked = mask_low_complexity(&sequence, &params);
+        assert_eq!(masked.length, sequence.length);
+        assert!(masked.utf8_bytes[start..=end].iter().all(|&b| b == UTF8_X));
+        assert_eq!(masked.utf8_bytes[1..start], sequence.utf8_bytes[1..start]);
+        assert_eq!(masked.utf8_bytes[end + 1..], sequence.utf8_bytes[end + 1..]);
+
+        let flank = Sequence::from_utf8(flank_a.as_bytes())?;
+        assert!(low_complexity_regions(&flank, &params).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_mask_fasta() -> anyhow::Result<()> {
+        let targets = Fasta::from_path("../fixtures/target.fa")?;
+        let params = SegParams::default();
+
+        let path = std::env::temp_dir().join(format!("nail-mask-{}.fa", std::process::id()));
+        let mut masked = mask_fasta(&targets, &params, &path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(masked.len(), targets.len());
+        targets
+            .iter()
+            .try_for_each(|target| -> anyhow::Result<()> {
+                let copy = masked.get(&target.name).unwrap();
+                let expected = mask_low_complexity(&target, &params);
+                assert_eq!(copy.utf8_bytes, expected.utf8_bytes);
+                assert_eq!(copy.digital_bytes, expected.digital_bytes);
+                Ok(())
+            })?;
+
+        Ok(())
+    }
+}
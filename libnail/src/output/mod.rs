@@ -1,3 +1,6 @@
+pub mod output_diagnostics;
+pub mod output_sam;
 pub mod output_standard;
 pub mod output_tabular;
+#[cfg(feature = "fs")]
 pub mod path_buf_ext;
@@ -0,0 +1,506 @@
+//! Per-pair diagnostics of the search pipeline.
+//!
+//! Each profile/target pair that was seeded produces one [`DiagnosticsRecord`],
+//! with a column for each of the stats of the bias filter, cloud search, and
+//! align stages. The columns are listed in [`DIAGNOSTICS_COLUMNS`]; a stage
+//! that didn't run leaves its columns empty (or null, in Parquet). Scores are
+//! in bits and times are in nanoseconds.
+
+use crate::search::{PipelineResult, StageResult};
+
+/// The type of the values in a diagnostics column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticsType {
+    Text,
+    UInt,
+    Float32,
+    Float64,
+    Bool,
+}
+
+/// A column of the diagnostics output.
+#[derive(Debug, Clone, Copy)]
+pub struct DiagnosticsColumn {
+    pub name: &'static str,
+    pub kind: DiagnosticsType,
+    pub description: &'static str,
+}
+
+const fn column(
+    name: &'static str,
+    kind: DiagnosticsType,
+    description: &'static str,
+) -> DiagnosticsColumn {
+    DiagnosticsColumn {
+        name,
+        kind,
+        description,
+    }
+}
+
+use DiagnosticsType::*;
+
+/// The columns of the diagnostics output, in order.
+#[rustfmt::skip]
+pub const DIAGNOSTICS_COLUMNS: [DiagnosticsColumn; 46] = [
+    column("query", Text, "the name of the query"),
+    column("target", Text, "the name of the target"),
+    column("query_length", UInt, "the length of the query profile"),
+    column("target_length", UInt, "the length of the target"),
+    column("seed_target_start", UInt, "the first target position of the seed"),
+    column("seed_target_end", UInt, "the last target position of the seed"),
+    column("seed_query_start", UInt, "the first query position of the seed"),
+    column("seed_query_end", UInt, "the last query position of the seed"),
+    column("seed_score", Float32, "the score of the seed"),
+    column("bias_status", Text, "passed or filtered, if the bias filter ran"),
+    column("bias_score", Float32, "the composition bias of the seed's target region"),
+    column("bias_corrected_score", Float32, "the seed score less the composition bias"),
+    column("bias_p_value", Float64, "the uncalibrated P-value of the corrected score"),
+    column("bias_time_ns", UInt, "the time spent in the bias filter"),
+    column("cloud_status", Text, "passed or filtered, if cloud search ran"),
+    column("cloud_score", Float32, "the cloud search score"),
+    column("cloud_p_value", Float64, "the P-value of the cloud search score"),
+    column("cloud_alpha", Float32, "the local pruning threshold that was used"),
+    column("cloud_beta", Float32, "the global pruning threshold that was used"),
+    column("cloud_forward_cells", UInt, "the cells computed by the forward pass"),
+    column("cloud_backward_cells", UInt, "the cells computed by the backward pass"),
+    column("cloud_trim_fallback", Text, "expanded or full, if trimming failed"),
+    column("cloud_full_dp_routed", Bool, "whether the pair skipped cloud search"),
+    column("cloud_cells_saved", UInt, "the estimated cells saved by skipping cloud search"),
+    column("cloud_memory_init_time_ns", UInt, "the time spent allocating matrices"),
+    column("cloud_forward_time_ns", UInt, "the time spent in the forward pass"),
+    column("cloud_backward_time_ns", UInt, "the time spent in the backward pass"),
+    column("cloud_merge_time_ns", UInt, "the time spent merging the two passes"),
+    column("cloud_trim_time_ns", UInt, "the time spent trimming the cloud"),
+    column("cloud_reorient_time_ns", UInt, "the time spent converting to row bounds"),
+    column("align_status", Text, "passed or filtered, if the align stage ran"),
+    column("forward_score", Float32, "the Forward score"),
+    column("forward_p_value", Float64, "the P-value of the Forward score"),
+    column("forward_cells", UInt, "the cells computed by Forward"),
+    column("backward_cells", UInt, "the cells computed by Backward"),
+    column("align_memory_init_time_ns", UInt, "the time spent allocating matrices"),
+    column("forward_time_ns", UInt, "the time spent in Forward"),
+    column("backward_time_ns", UInt, "the time spent in Backward"),
+    column("posterior_time_ns", UInt, "the time spent computing posteriors"),
+    column("optimal_accuracy_time_ns", UInt, "the time spent in optimal accuracy"),
+    column("viterbi_time_ns", UInt, "the time spent in Viterbi"),
+    column("traceback_time_ns", UInt, "the time spent in traceback"),
+    column("null_two_time_ns", UInt, "the time spent computing the bias correction"),
+    column("score", Float32, "the final score, if the pair was aligned"),
+    column("comp_bias", Float32, "the composition bias correction, if it was computed"),
+    column("e_value", Float64, "the E-value, if the pair was aligned"),
+];
+
+/// A value in a diagnostics column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticsValue {
+    Null,
+    Text(String),
+    UInt(u64),
+    Float32(f32),
+    Float64(f64),
+    Bool(bool),
+}
+
+impl DiagnosticsValue {
+    fn text(value: impl ToString) -> Self {
+        Self::Text(value.to_string())
+    }
+
+    fn uint(value: impl TryInto<u64>) -> Self {
+        match value.try_into() {
+            Ok(value) => Self::UInt(value),
+            Err(_) => Self::Null,
+        }
+    }
+
+    fn nanos(duration: std::time::Duration) -> Self {
+        Self::uint(duration.as_nanos())
+    }
+
+    /// The value as a TSV field; nulls are empty.
+    pub fn tsv_string(&self) -> String {
+        match self {
+            Self::Null => String::new(),
+            Self::Text(value) => value.clone(),
+            Self::UInt(value) => value.to_string(),
+            Self::Float32(value) => value.to_string(),
+            Self::Float64(value) => format!("{value:e}"),
+            Self::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+/// The diagnostics of one profile/target pair, with a value for each of the
+/// [`DIAGNOSTICS_COLUMNS`].
+#[derive(Debug, Clone)]
+pub struct DiagnosticsRecord {
+    pub values: Vec<DiagnosticsValue>,
+}
+
+fn status<D, S>(result: &StageResult<D, S>) -> DiagnosticsValue {
+    match result {
+        StageResult::Passed { .. } => DiagnosticsValue::text("passed"),
+        StageResult::Filtered { .. } => DiagnosticsValue::text("filtered"),
+    }
+}
+
+impl From<&PipelineResult> for DiagnosticsRecord {
+    fn from(result: &PipelineResult) -> Self {
+        use DiagnosticsValue as V;
+
+        let mut values = vec![
+            V::text(&result.profile_name),
+            V::text(&result.target_name),
+            V::uint(result.profile_length),
+            V::uint(result.target_length),
+            V::uint(result.seed.target_start),
+            V::uint(result.seed.target_end),
+            V::uint(result.seed.profile_start),
+            V::uint(result.seed.profile_end),
+            V::Float32(result.seed.score),
+        ];
+
+        match &result.bias_filter_result {
+            Some(bias) => {
+                let stats = bias.stats();
+                values.extend([
+                    status(bias),
+                    V::Float32(stats.bias.to_bits().value()),
+                    V::Float32(stats.corrected_score.value()),
+                    V::Float64(stats.p_value),
+                    V::nanos(stats.time),
+                ]);
+            }
+            None => values.extend(std::iter::repeat_n(V::Null, 5)),
+        }
+
+        match &result.cloud_result {
+            Some(cloud) => {
+                let stats = cloud.stats();
+                values.extend([
+                    status(cloud),
+                    match stats.score {
+                        Some(score) => V::Float32(score.to_bits().value()),
+                        None => V::Null,
+                    },
+                    match stats.p_value {
+                        Some(p_value) => V::Float64(p_value),
+                        None => V::Null,
+                    },
+                    V::Float32(stats.alpha),
+                    V::Float32(stats.beta),
+                    V::uint(stats.forward_cells),
+                    V::uint(stats.backward_cells),
+                    match stats.trim_fallback {
+                        Some(fallback) => V::text(fallback),
+                        None => V::Null,
+                    },
+                    V::Bool(stats.full_dp_routed),
+                    V::uint(stats.estimated_cells_saved),
+                    V::nanos(stats.memory_init_time),
+                    V::nanos(stats.forward_time),
+                    V::nanos(stats.backward_time),
+                    V::nanos(stats.merge_time),
+                    V::nanos(stats.trim_time),
+                    V::nanos(stats.reorient_time),
+                ]);
+            }
+            None => values.extend(std::iter::repeat_n(V::Null, 16)),
+        }
+
+        match &result.align_result {
+            Some(align) => {
+                let stats = align.stats();
+                values.extend([
+                    status(align),
+                    V::Float32(stats.score.value()),
+                    V::Float64(stats.p_value),
+                    V::uint(stats.forward_cells),
+                    V::uint(stats.backward_cells),
+                    V::nanos(stats.memory_init_time),
+                    V::nanos(stats.forward_time),
+                    V::nanos(stats.backward_time),
+                    V::nanos(stats.posterior_time),
+                    V::nanos(stats.optimal_accuracy_time),
+                    V::nanos(stats.viterbi_time),
+                    V::nanos(stats.traceback_time),
+                    V::nanos(stats.null_two_time),
+                ]);
+            }
+            None => values.extend(std::iter::repeat_n(V::Null, 13)),
+        }
+
+        match &result.align_result {
+            Some(StageResult::Passed { data: ali, .. }) => values.extend([
+                V::Float32(ali.scores.bit_score.value()),
+                match ali.scores.null_two_score {
+                    Some(score) => V::Float32(score.value()),
+                    None => V::Null,
+                },
+                V::Float64(ali.scores.e_value),
+            ]),
+            _ => values.extend(std::iter::repeat_n(V::Null, 3)),
+        }
+
+        debug_assert_eq!(values.len(), DIAGNOSTICS_COLUMNS.len());
+        Self { values }
+    }
+}
+
+impl DiagnosticsRecord {
+    /// The TSV header line, without a newline.
+    pub fn tsv_header() -> String {
+        DIAGNOSTICS_COLUMNS
+            .iter()
+            .map(|c| c.name)
+            .collect::<Vec<_>>()
+            .join("\t")
+    }
+
+    /// The record as a TSV line, without a newline.
+    pub fn tsv_string(&self) -> String {
+        self.values
+            .iter()
+            .map(DiagnosticsValue::tsv_string)
+            .collect::<Vec<_>>()
+            .join("\t")
+    }
+}
+
+#[cfg(feature = "parquet")]
+pub use parquet_writer::{DiagnosticsParquetWriter, ROW_GROUP_SIZE};
+
+#[cfg(feature = "parquet")]
+mod parquet_writer {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use anyhow::Context;
+    use arrow_array::{
+        builder::{BooleanBuilder, Float32Builder, Float64Builder, StringBuilder, UInt64Builder},
+        ArrayRef, RecordBatch,
+    };
+    use arrow_schema::{DataType, Field, Schema, SchemaRef};
+    use parquet::arrow::ArrowWriter;
+
+    use super::{DiagnosticsRecord, DiagnosticsType, DiagnosticsValue, DIAGNOSTICS_COLUMNS};
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(
+            DIAGNOSTICS_COLUMNS
+                .iter()
+                .map(|column| {
+                    let data_type = match column.kind {
+                        DiagnosticsType::Text => DataType::Utf8,
+                        DiagnosticsType::UInt => DataType::UInt64,
+                        DiagnosticsType::Float32 => DataType::Float32,
+                        DiagnosticsType::Float64 => DataType::Float64,
+                        DiagnosticsType::Bool => DataType::Boolean,
+                    };
+                    Field::new(column.name, data_type, true)
+                })
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    fn column_array(records: &[DiagnosticsRecord], idx: usize) -> ArrayRef {
+        let values = records.iter().map(|r| &r.values[idx]);
+
+        macro_rules! build {
+            ($builder:ty, $variant:ident) => {{
+                let mut builder = <$builder>::new();
+                values.for_each(|value| match value {
+                    DiagnosticsValue::$variant(value) => builder.append_value(value.to_owned()),
+                    _ => builder.append_null(),
+                });
+                Arc::new(builder.finish())
+            }};
+        }
+
+        match DIAGNOSTICS_COLUMNS[idx].kind {
+            DiagnosticsType::Text => build!(StringBuilder, Text),
+            DiagnosticsType::UInt => build!(UInt64Builder, UInt),
+            DiagnosticsType::Float32 => build!(Float32Builder, Float32),
+            DiagnosticsType::Float64 => build!(Float64Builder, Float64),
+            DiagnosticsType::Bool => build!(BooleanBuilder, Bool),
+        }
+    }
+
+    /// The number of records in each row group of a diagnostics Parquet file.
+    pub const ROW_GROUP_SIZE: usize = 65_536;
+
+    /// Writes diagnostics records to a Parquet file.
+    ///
+    /// Records are buffered and written in row groups of [`ROW_GROUP_SIZE`] records.
+    /// The last row group and the file footer are written by [`finish()`](Self::finish).
+    /// They are also written when the writer is dropped, but then any errors are ignored.
+    pub struct DiagnosticsParquetWriter<W: Write + Send> {
+        schema: SchemaRef,
+        inner: Option<ArrowWriter<W>>,
+        buffer: Vec<DiagnosticsRecord>,
+        row_group_size: usize,
+    }
+
+    impl<W: Write + Send> DiagnosticsParquetWriter<W> {
+        pub fn new(inner: W) -> anyhow::Result<Self> {
+            let schema = schema();
+            Ok(Self {
+                inner: Some(
+                    ArrowWriter::try_new(inner, schema.clone(), None)
+                        .context("failed to create Parquet writer")?,
+                ),
+                schema,
+                buffer: vec![],
+                row_group_size: ROW_GROUP_SIZE,
+            })
+        }
+
+        /// Set the number of records in each row group.
+        pub fn with_row_group_size(mut self, row_group_size: usize) -> Self {
+            self.row_group_size = row_group_size.max(1);
+            self
+        }
+
+        /// Buffer the records, writing a row group each time the buffer fills.
+        pub fn write(&mut self, records: &[DiagnosticsRecord]) -> anyhow::Result<()> {
+            if self.inner.is_none() {
+                anyhow::bail!("Parquet writer is already finished");
+            }
+
+            for record in records {
+                self.buffer.push(record.clone());
+                if self.buffer.len() >= self.row_group_size {
+                    self.flush_row_group()?;
+                }
+            }
+            Ok(())
+        }
+
+        fn flush_row_group(&mut self) -> anyhow::Result<()> {
+            if self.buffer.is_empty() {
+                return Ok(());
+            }
+
+            let writer = self
+                .inner
+                .as_mut()
+                .context("Parquet writer is already finished")?;
+
+            let columns = (0..DIAGNOSTICS_COLUMNS.len())
+                .map(|idx| column_array(&self.buffer, idx))
+                .collect();
+
+            writer.write(&RecordBatch::try_new(self.schema.clone(), columns)?)?;
+            writer.flush()?;
+            self.buffer.clear();
+            Ok(())
+        }
+
+        /// Write the buffered records and the file footer, and flush the inner writer.
+        pub fn finish(&mut self) -> anyhow::Result<()> {
+            if self.inner.is_some() {
+                self.flush_row_group()?;
+            }
+            if let Some(writer) = self.inner.take() {
+                writer
+                    .into_inner()
+                    .context("failed to write Parquet footer")?
+                    .flush()?;
+            }
+            Ok(())
+        }
+    }
+
+    impl<W: Write + Send> Drop for DiagnosticsParquetWriter<W> {
+        fn drop(&mut self) {
+            let _ = self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        align::structs::Seed,
+        search::{CloudStageStats, PipelineResult},
+    };
+
+    fn result() -> PipelineResult {
+        PipelineResult {
+            profile_name: "query".to_string(),
+            target_name: "target".to_string(),
+            profile_length: 10,
+            target_length: 20,
+            seed: Seed {
+                target_start: 2,
+                target_end: 12,
+                profile_start: 1,
+                profile_end: 10,
+                score: 21.5,
+            },
+            bias_filter_result: None,
+            cloud_result: Some(StageResult::Filtered {
+                stats: CloudStageStats {
+                    p_value: Some(0.25),
+                    ..Default::default()
+                },
+            }),
+            align_result: None,
+        }
+    }
+
+    #[test]
+    fn test_tsv_columns() {
+        let header = DiagnosticsRecord::tsv_header();
+        let record = DiagnosticsRecord::from(&result());
+        let row = record.tsv_string();
+
+        let fields: Vec<(&str, &str)> = header.split('\t').zip(row.split('\t')).collect();
+        assert_eq!(fields.len(), DIAGNOSTICS_COLUMNS.len());
+        assert_eq!(row.split('\t').count(), DIAGNOSTICS_COLUMNS.len());
+
+        let field = |name: &str| fields.iter().find(|(n, _)| *n == name).unwrap().1;
+        assert_eq!(field("query"), "query");
+        assert_eq!(field("seed_target_start"), "2");
+        assert_eq!(field("seed_score"), "21.5");
+        assert_eq!(field("bias_status"), "");
+        assert_eq!(field("cloud_status"), "filtered");
+        assert_eq!(field("cloud_p_value"), "2.5e-1");
+        assert_eq!(field("cloud_full_dp_routed"), "false");
+        assert_eq!(field("align_status"), "");
+        assert_eq!(field("e_value"), "");
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_round_trip() -> anyhow::Result<()> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let path = std::env::temp_dir().join("libnail-diagnostics-test.parquet");
+
+        let mut writer =
+            DiagnosticsParquetWriter::new(std::fs::File::create(&path)?)?.with_row_group_size(2);
+        let record = DiagnosticsRecord::from(&result());
+        // one record per query, as the pipeline writes them
+        for _ in 0..5 {
+            writer.write(std::slice::from_ref(&record))?;
+        }
+        writer.finish()?;
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path)?)?;
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 5);
+        // two full row groups and the remainder written by finish()
+        assert_eq!(metadata.num_row_groups(), 3);
+        assert_eq!(metadata.row_group(2).num_rows(), 1);
+        assert_eq!(
+            metadata.file_metadata().schema_descr().num_columns(),
+            DIAGNOSTICS_COLUMNS.len()
+        );
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,474 @@
+use crate::align::structs::{Alignment, Trace};
+
+use anyhow::bail;
+use flate2::{write::DeflateEncoder, Compression, Crc};
+use std::io::Write;
+
+/// A reference (target) sequence in the SAM/BAM header.
+#[derive(Clone, Debug)]
+pub struct SamReference {
+    pub name: String,
+    pub length: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CigarOp {
+    Match,
+    Insertion,
+    Deletion,
+    HardClip,
+}
+
+impl CigarOp {
+    fn char(&self) -> char {
+        match self {
+            CigarOp::Match => 'M',
+            CigarOp::Insertion => 'I',
+            CigarOp::Deletion => 'D',
+            CigarOp::HardClip => 'H',
+        }
+    }
+
+    fn bam_code(&self) -> u32 {
+        match self {
+            CigarOp::Match => 0,
+            CigarOp::Insertion => 1,
+            CigarOp::Deletion => 2,
+            CigarOp::HardClip => 5,
+        }
+    }
+
+    fn consumes_reference(&self) -> bool {
+        matches!(self, CigarOp::Match | CigarOp::Deletion)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SamTagValue {
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+/// A SAM record of a hit, where the reference is the target
+/// sequence and the query is the profile's consensus sequence.
+#[derive(Clone, Debug)]
+pub struct SamRecord {
+    pub query_name: String,
+    pub reference_name: String,
+    /// The 1-based position of the first aligned target residue
+    pub position: usize,
+    pub cigar: Vec<(usize, CigarOp)>,
+    /// The consensus residues of the aligned portion of the profile
+    pub sequence: String,
+    pub tags: Vec<([u8; 2], SamTagValue)>,
+}
+
+impl SamRecord {
+    const FLAG: u16 = 0;
+    // the mapping quality is not available
+    const MAPQ: u8 = 255;
+    /// The longest query name that a BAM record can hold
+    pub const MAX_QUERY_NAME_LENGTH: usize = 254;
+
+    /// Build a SAM record from an alignment, returning `None` if
+    /// the alignment is missing the trace or any of its metadata.
+    pub fn from_alignment(alignment: &Alignment) -> Option<Self> {
+        let boundaries = alignment.boundaries.as_ref()?;
+        let display = alignment.display_strings.as_ref()?;
+        let trace = alignment.trace.as_ref()?;
+
+        // SAM operations are relative to the reference (the target), so a
+        // profile insert state is a deletion from the query (the consensus)
+        // and a profile delete state is an insertion into the query
+        let mut cigar: Vec<(usize, CigarOp)> = vec![];
+
+        // the unaligned ends of the consensus are hard clipped
+        if boundaries.profile_start > 1 {
+            cigar.push((boundaries.profile_start - 1, CigarOp::HardClip));
+        }
+
+        trace
+            .iter()
+            .filter_map(|step| match step.state {
+                Trace::M_STATE => Some(CigarOp::Match),
+                Trace::I_STATE => Some(CigarOp::Deletion),
+                Trace::D_STATE => Some(CigarOp::Insertion),
+                _ => None,
+            })
+            .for_each(|op| match cigar.last_mut() {
+                Some((len, last_op)) if *last_op == op => *len += 1,
+                _ => cigar.push((1, op)),
+            });
+
+        let profile_length = alignment.profile_length?;
+        if profile_length > boundaries.profile_end {
+            cigar.push((profile_length - boundaries.profile_end, CigarOp::HardClip));
+        }
+
+        let sequence = display
+            .profile_string
+            .bytes()
+            .filter(|&b| b != Alignment::PROFILE_GAP_BYTE)
+            .map(|b| b as char)
+            .collect();
+
+        let tags = vec![
+            (
+                *b"AS",
+                SamTagValue::Int(alignment.scores.bit_score.value().round() as i32),
+            ),
+            (
+                *b"ZB",
+                SamTagValue::Float(alignment.scores.bit_score.value()),
+            ),
+            // E-values can underflow an f32, so we store them as a string
+            (
+                *b"ZE",
+                SamTagValue::String(format!("{:.2e}", alignment.scores.e_value)),
+            ),
+            (
+                *b"ZP",
+                SamTagValue::String(display.posterior_string.clone()),
+            ),
+        ];
+
+        Some(Self {
+            query_name: alignment.profile_name.clone()?,
+            reference_name: alignment.target_name.clone()?,
+            position: boundaries.target_start,
+            cigar,
+            sequence,
+            tags,
+        })
+    }
+
+    fn cigar_string(&self) -> String {
+        self.cigar
+            .iter()
+            .map(|(len, op)| format!("{len}{}", op.char()))
+            .collect()
+    }
+
+    /// The number of reference positions covered by the record.
+    fn reference_span(&self) -> usize {
+        self.cigar
+            .iter()
+            .filter(|(_, op)| op.consumes_reference())
+            .map(|(len, _)| len)
+            .sum()
+    }
+
+    pub fn sam_string(&self) -> String {
+        let mut fields = vec![
+            self.query_name.clone(),
+            Self::FLAG.to_string(),
+            self.reference_name.clone(),
+            self.position.to_string(),
+            Self::MAPQ.to_string(),
+            self.cigar_string(),
+            "*".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            self.sequence.clone(),
+            "*".to_string(),
+        ];
+
+        self.tags.iter().for_each(|(tag, value)| {
+            let tag = String::from_utf8_lossy(tag);
+            fields.push(match value {
+                SamTagValue::Int(v) => format!("{tag}:i:{v}"),
+                SamTagValue::Float(v) => format!("{tag}:f:{v}"),
+                SamTagValue::String(v) => format!("{tag}:Z:{v}"),
+            })
+        });
+
+        fields.join("\t")
+    }
+
+    /// Encode the record in the BAM format.
+    ///
+    /// BAM packs sequences into a 4-bit nucleotide alphabet, which can't
+    /// represent amino acids. Instead, the sequence is omitted and the
+    /// consensus residues are stored in a `ZC` tag.
+    ///
+    /// This fails if the query name is longer than BAM allows.
+    pub fn bam_bytes(&self, reference_idx: usize) -> anyhow::Result<Vec<u8>> {
+        // the name's length, including its nul terminator, is stored in one byte
+        if self.query_name.len() > Self::MAX_QUERY_NAME_LENGTH {
+            bail!(
+                "query name is longer than the BAM limit of {} bytes: {}",
+                Self::MAX_QUERY_NAME_LENGTH,
+                self.query_name
+            );
+        }
+
+        let mut record: Vec<u8> = vec![];
+
+        let position = self.position as i32 - 1;
+        let end = position + self.reference_span() as i32;
+
+        record.extend((reference_idx as i32).to_le_bytes());
+        record.extend(position.to_le_bytes());
+        record.push(self.query_name.len() as u8 + 1);
+        record.push(Self::MAPQ);
+        record.extend(bam_bin(position, end).to_le_bytes());
+        record.extend((self.cigar.len() as u16).to_le_bytes());
+        record.extend(Self::FLAG.to_le_bytes());
+        // the sequence length
+        record.extend(0i32.to_le_bytes());
+        // the mate reference, position, and template length
+        record.extend((-1i32).to_le_bytes());
+        record.extend((-1i32).to_le_bytes());
+        record.extend(0i32.to_le_bytes());
+
+        record.extend(self.query_name.as_bytes());
+        record.push(0);
+
+        self.cigar.iter().for_each(|(len, op)| {
+            record.extend(((*len as u32) << 4 | op.bam_code()).to_le_bytes())
+        });
+
+        let consensus_tag = (*b"ZC", SamTagValue::String(self.sequence.clone()));
+        self.tags
+            .iter()
+            .chain(std::iter::once(&consensus_tag))
+            .for_each(|(tag, value)| {
+                record.extend(tag);
+                match value {
+                    SamTagValue::Int(v) => {
+                        record.push(b'i');
+                        record.extend(v.to_le_bytes());
+                    }
+                    SamTagValue::Float(v) => {
+                        record.push(b'f');
+                        record.extend(v.to_le_bytes());
+                    }
+                    SamTagValue::String(v) => {
+                        record.push(b'Z');
+                        record.extend(v.as_bytes());
+                        record.push(0);
+                    }
+                }
+            });
+
+        let mut bytes = (record.len() as i32).to_le_bytes().to_vec();
+        bytes.extend(record);
+        Ok(bytes)
+    }
+}
+
+/// Compute the BAM index bin of a 0-based, end-exclusive region (from the SAM spec).
+fn bam_bin(start: i32, end: i32) -> u16 {
+    let end = end - 1;
+
+    // the first bin of each level, i.e. ((1 << (3 * level)) - 1) / 7,
+    // paired with the log2 of the level's bin size
+    let bin = [(4681, 14), (585, 17), (73, 20), (9, 23), (1, 26)]
+        .iter()
+        .find(|(_, shift)| start >> shift == end >> shift)
+        .map_or(0, |(offset, shift)| offset + (start >> shift));
+
+    bin as u16
+}
+
+/// The SAM header text, with an @SQ line for every reference.
+pub fn sam_header(references: &[SamReference]) -> String {
+    let mut header = String::from("@HD\tVN:1.6\tSO:unsorted\n");
+
+    references.iter().for_each(|r| {
+        header.push_str(&format!("@SQ\tSN:{}\tLN:{}\n", r.name, r.length));
+    });
+
+    header.push_str(&format!(
+        "@PG\tID:nail\tPN:nail\tVN:{}\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+
+    header
+}
+
+/// The BAM header: the SAM header text followed by the binary reference list.
+pub fn bam_header_bytes(references: &[SamReference]) -> Vec<u8> {
+    let text = sam_header(references);
+
+    let mut bytes = b"BAM\x01".to_vec();
+    bytes.extend((text.len() as i32).to_le_bytes());
+    bytes.extend(text.as_bytes());
+    bytes.extend((references.len() as i32).to_le_bytes());
+
+    references.iter().for_each(|r| {
+        bytes.extend((r.name.len() as i32 + 1).to_le_bytes());
+        bytes.extend(r.name.as_bytes());
+        bytes.push(0);
+        bytes.extend((r.length as i32).to_le_bytes());
+    });
+
+    bytes
+}
+
+/// A writer that compresses its output into BGZF blocks, as used by BAM.
+///
+/// Any buffered data and the BGZF end-of-file marker are written by
+/// [`finish()`](Self::finish). Like `BufWriter`, they are also written
+/// when the writer is dropped, but then any errors are ignored.
+pub struct BgzfWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    // HTSlib's limit, which guarantees that
+    // a compressed block fits in 64KB
+    const MAX_BLOCK_INPUT: usize = 0xff00;
+
+    const EOF_BLOCK: [u8; 28] = [
+        0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02,
+        0x00, 0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buffer: Vec::with_capacity(Self::MAX_BLOCK_INPUT),
+            finished: false,
+        }
+    }
+
+    fn write_block(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+
+        let mut crc = Crc::new();
+        crc.update(data);
+
+        // the gzip header (18 bytes) + data + the crc32 & input size (8 bytes)
+        let block_size = 18 + compressed.len() + 8;
+
+        self.inner.write_all(&[
+            0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, b'B', b'C',
+            0x02, 0x00,
+        ])?;
+        self.inner
+            .write_all(&((block_size - 1) as u16).to_le_bytes())?;
+        self.inner.write_all(&compressed)?;
+        self.inner.write_all(&crc.sum().to_le_bytes())?;
+        self.inner.write_all(&(data.len() as u32).to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_buffer(&mut self) -> std::io::Result<()> {
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            self.write_block(&buffer)?;
+            self.buffer = buffer;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Write any buffered data and the BGZF end-of-file marker.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if !self.finished {
+            self.write_buffer()?;
+            self.inner.write_all(&Self::EOF_BLOCK)?;
+            self.inner.flush()?;
+            self.finished = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(Self::MAX_BLOCK_INPUT - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        if self.buffer.len() == Self::MAX_BLOCK_INPUT {
+            self.write_buffer()?;
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_buffer()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for BgzfWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::MultiGzDecoder;
+    use std::io::Read;
+
+    fn record() -> SamRecord {
+        SamRecord {
+            query_name: "query".to_string(),
+            reference_name: "target".to_string(),
+            position: 10,
+            cigar: vec![
+                (2, CigarOp::HardClip),
+                (5, CigarOp::Match),
+                (1, CigarOp::Deletion),
+                (3, CigarOp::Match),
+                (1, CigarOp::Insertion),
+            ],
+            sequence: "ACDEFGHIK".to_string(),
+            tags: vec![(*b"AS", SamTagValue::Int(42))],
+        }
+    }
+
+    #[test]
+    fn test_sam_string() {
+        assert_eq!(
+            record().sam_string(),
+            "query\t0\ttarget\t10\t255\t2H5M1D3M1I\t*\t0\t0\tACDEFGHIK\t*\tAS:i:42"
+        );
+        assert_eq!(record().reference_span(), 9);
+    }
+
+    #[test]
+    fn test_bgzf_round_trip() -> anyhow::Result<()> {
+        let references = vec![SamReference {
+            name: "target".to_string(),
+            length: 100,
+        }];
+
+        let mut expected = bam_header_bytes(&references);
+        let record_bytes = record().bam_bytes(0)?;
+        // l_read_name counts the name's nul terminator
+        assert_eq!(record_bytes[12], "query".len() as u8 + 1);
+        expected.extend(record_bytes);
+
+        // the longest name that fits in l_read_name is 254 bytes
+        let mut long_name = record();
+        long_name.query_name = "q".repeat(SamRecord::MAX_QUERY_NAME_LENGTH);
+        assert_eq!(long_name.bam_bytes(0)?[12], 255);
+        long_name.query_name.push('q');
+        assert!(long_name.bam_bytes(0).is_err());
+
+        let mut compressed = vec![];
+        {
+            let mut writer = BgzfWriter::new(&mut compressed);
+            writer.write_all(&expected)?;
+        }
+
+        // a BGZF file is a valid multi-member gzip file
+        let mut decompressed = vec![];
+        MultiGzDecoder::new(compressed.as_slice()).read_to_end(&mut decompressed)?;
+        assert_eq!(decompressed, expected);
+        assert!(compressed.ends_with(&BgzfWriter::<Vec<u8>>::EOF_BLOCK));
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 use crate::align::{structs::Alignment, Bits};
 
 use anyhow::Context;
+use std::str::FromStr;
+use thiserror::Error;
 
 trait FieldString {
     fn field_string(&self) -> String;
@@ -24,32 +26,83 @@ impl FieldString for Bits {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Error, Debug)]
+#[error("unknown field: {name}")]
+pub struct UnknownFieldError {
+    name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Field {
     Target,
     Query,
+    Accession,
+    Description,
+    TargetLength,
+    QueryLength,
     TargetStart,
     TargetEnd,
     QueryStart,
     QueryEnd,
     Score,
+    ForwardScore,
     CompBias,
+    Pvalue,
     Evalue,
     CellFrac,
     CellCount,
 }
 
 impl Field {
+    pub const ALL: [Field; 17] = [
+        Field::Target,
+        Field::Query,
+        Field::Accession,
+        Field::Description,
+        Field::TargetLength,
+        Field::QueryLength,
+        Field::TargetStart,
+        Field::TargetEnd,
+        Field::QueryStart,
+        Field::QueryEnd,
+        Field::Score,
+        Field::ForwardScore,
+        Field::CompBias,
+        Field::Pvalue,
+        Field::Evalue,
+        Field::CellFrac,
+        Field::CellCount,
+    ];
+
+    /// The snake_case name of the field, e.g. "target_start".
+    pub fn name(&self) -> String {
+        // the Debug string for an enum produces the variant name
+        let mut name = String::new();
+        format!("{:?}", self).chars().for_each(|c| {
+            if c.is_ascii_uppercase() && !name.is_empty() {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+        });
+        name
+    }
+
     fn extract(&self, alignment: &Alignment) -> Option<String> {
         Some(match self {
             Field::Target => alignment.target_name.clone()?,
             Field::Query => alignment.profile_name.clone()?,
+            Field::Accession => alignment.profile_accession.clone()?,
+            Field::Description => alignment.target_description.clone()?,
+            Field::TargetLength => alignment.target_length?.to_string(),
+            Field::QueryLength => alignment.profile_length?.to_string(),
             Field::TargetStart => alignment.boundaries.as_ref()?.target_start.to_string(),
             Field::TargetEnd => alignment.boundaries.as_ref()?.target_end.to_string(),
             Field::QueryStart => alignment.boundaries.as_ref()?.profile_start.to_string(),
             Field::QueryEnd => alignment.boundaries.as_ref()?.profile_end.to_string(),
             Field::Score => alignment.scores.bit_score.field_string(),
+            Field::ForwardScore => alignment.scores.forward_score.field_string(),
             Field::CompBias => alignment.scores.null_two_score.as_ref()?.field_string(),
+            Field::Pvalue => alignment.scores.p_value.field_string(),
             Field::Evalue => alignment.scores.e_value.field_string(),
             Field::CellFrac => alignment.cell_stats.as_ref()?.fraction.field_string(),
             Field::CellCount => alignment.cell_stats.as_ref()?.count.to_string(),
@@ -62,6 +115,71 @@ impl Field {
             None => "-".to_string(),
         }
     }
+
+    /// The width of the widest value of the field for an alignment
+    /// between the sequences or profiles described by `extents`.
+    fn max_width(&self, extents: &TableExtents) -> usize {
+        let digits = |n: usize| n.max(1).ilog10() as usize + 1;
+
+        let width = match self {
+            Field::Target => extents.target_name,
+            Field::Query => extents.query_name,
+            Field::Accession => extents.query_accession,
+            Field::Description => extents.target_description,
+            Field::TargetLength | Field::TargetStart | Field::TargetEnd => {
+                digits(extents.target_length)
+            }
+            Field::QueryLength | Field::QueryStart | Field::QueryEnd => {
+                digits(extents.query_length)
+            }
+            Field::CellCount => digits(extents.target_length * extents.query_length),
+            // e.g. "-99999.9"
+            Field::Score | Field::ForwardScore | Field::CompBias => 8,
+            // e.g. "1.0e-300"
+            Field::Pvalue | Field::Evalue => 8,
+            // e.g. "1.000"
+            Field::CellFrac => 5,
+        };
+
+        // a missing value is written as "-"
+        width.max(1)
+    }
+}
+
+/// The longest name, description and length of the targets and queries
+/// of a search, which bound the widths of the table's columns.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TableExtents {
+    pub target_name: usize,
+    pub target_description: usize,
+    pub target_length: usize,
+    pub query_name: usize,
+    pub query_accession: usize,
+    pub query_length: usize,
+}
+
+impl FromStr for Field {
+    type Err = UnknownFieldError;
+
+    /// Parse a field from its name, ignoring case, underscores and dashes,
+    /// so that "target_start", "target-start" and "TargetStart" all match.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalize = |name: &str| {
+            name.chars()
+                .filter(|c| *c != '_' && *c != '-')
+                .map(|c| c.to_ascii_lowercase())
+                .collect::<String>()
+        };
+
+        let name = normalize(s);
+        Field::ALL
+            .iter()
+            .find(|field| normalize(&field.name()) == name)
+            .copied()
+            .ok_or(UnknownFieldError {
+                name: s.to_string(),
+            })
+    }
 }
 
 #[derive(Clone)]
@@ -134,6 +252,42 @@ impl TableFormat {
         });
     }
 
+    /// Update the column widths to fit rows of field strings, e.g. those read from a table.
+    pub fn update_widths_from_rows<T: AsRef<[String]>>(&mut self, rows: &[T]) {
+        rows.iter().for_each(|row| {
+            row.as_ref()
+                .iter()
+                .zip(self.widths.iter_mut())
+                .for_each(|(value, width)| *width = (*width).max(value.len()));
+        });
+    }
+
+    /// Produce a width-padded row from field strings, matching `Alignment::tab_string_formatted()`.
+    pub fn row_string(&self, values: &[String]) -> String {
+        values
+            .iter()
+            .zip(self.widths.iter())
+            .map(|(value, width)| format!("{value:width$}", width = width))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Fix the column widths to fit any alignment between the targets and
+    /// queries described by `extents`, so that every block of rows that is
+    /// written with this format lines up, no matter which alignments it holds.
+    pub fn fix_widths(&mut self, extents: &TableExtents) {
+        self.fields
+            .iter()
+            .zip(self.min_widths.iter())
+            .zip(self.widths.iter_mut())
+            .enumerate()
+            .for_each(|(idx, ((field, min_width), width))| {
+                // the first column has room for the "# " prefix of the header
+                let min_width = if idx == 0 { min_width + 2 } else { *min_width };
+                *width = min_width.max(field.max_width(extents));
+            });
+    }
+
     pub fn reset_widths(&mut self) {
         self.widths
             .iter_mut()
@@ -141,6 +295,67 @@ impl TableFormat {
             .for_each(|(width, min_width)| *width = *min_width);
     }
 
+    /// The header for strict tab-separated output: the field names, separated by tabs.
+    pub fn tsv_header(&self) -> String {
+        self.fields
+            .iter()
+            .map(|field| field.name())
+            .collect::<Vec<_>>()
+            .join("\t")
+    }
+
+    /// Recover the table format from a header produced by `tsv_header()`.
+    pub fn from_tsv_header(header: &str) -> anyhow::Result<Self> {
+        let fields = header
+            .trim_end()
+            .split('\t')
+            .map(|name| name.parse::<Field>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::new(&fields)
+    }
+
+    /// Recover the table format from the lines of a header produced by `header()`.
+    ///
+    /// The columns are located by the runs of dashes on the last line, and the
+    /// words above each run are joined to recover the name of the field.
+    pub fn from_header(header_lines: &[&str]) -> anyhow::Result<Self> {
+        let (dash_line, label_lines) =
+            header_lines.split_last().context("table header is empty")?;
+
+        let mut spans: Vec<(usize, usize)> = vec![];
+        let mut span_start: Option<usize> = None;
+        for (idx, c) in dash_line.char_indices().chain([(dash_line.len(), ' ')]) {
+            match (c, span_start) {
+                ('-', None) => span_start = Some(idx),
+                ('-', Some(_)) => {}
+                (_, Some(start)) => {
+                    spans.push((start, idx));
+                    span_start = None;
+                }
+                (_, None) => {}
+            }
+        }
+
+        let fields = spans
+            .iter()
+            .map(|&(start, end)| {
+                let name: String = label_lines
+                    .iter()
+                    .filter_map(|line| line.get(start..end.min(line.len())))
+                    .map(str::trim)
+                    .collect();
+                name.parse::<Field>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if fields.is_empty() {
+            anyhow::bail!("table header has no columns");
+        }
+
+        Self::new(&fields)
+    }
+
     pub fn header(&self) -> anyhow::Result<String> {
         // the number of rows in the header is
         // the max number of words in a field
@@ -185,3 +400,84 @@ impl TableFormat {
         Ok(header_row_strings.join("\n"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_names() -> anyhow::Result<()> {
+        assert_eq!(Field::TargetStart.name(), "target_start");
+        assert_eq!(Field::Evalue.name(), "evalue");
+
+        Field::ALL
+            .iter()
+            .try_for_each(|field| -> anyhow::Result<()> {
+                assert_eq!(field.name().parse::<Field>()?, *field);
+                Ok(())
+            })?;
+
+        assert_eq!("TargetStart".parse::<Field>()?, Field::TargetStart);
+        assert_eq!("cell-frac".parse::<Field>()?, Field::CellFrac);
+        assert!("target_middle".parse::<Field>().is_err());
+
+        let format = TableFormat::new(&[Field::Target, Field::QueryLength, Field::Pvalue])?;
+        assert_eq!(format.tsv_header(), "target\tquery_length\tpvalue");
+        assert_eq!(
+            TableFormat::from_tsv_header(&format.tsv_header())?.fields,
+            format.fields
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fix_widths() -> anyhow::Result<()> {
+        let mut format = TableFormat::new(&[
+            Field::Target,
+            Field::Accession,
+            Field::TargetStart,
+            Field::QueryEnd,
+            Field::CellCount,
+            Field::Evalue,
+        ])?;
+
+        format.fix_widths(&TableExtents {
+            target_name: 20,
+            target_description: 50,
+            target_length: 12_345,
+            query_name: 10,
+            query_accession: 0,
+            query_length: 99,
+        });
+        assert_eq!(format.widths, [20, 9, 6, 5, 7, 8]);
+
+        // the widths never drop below the header labels
+        format.fix_widths(&TableExtents::default());
+        assert_eq!(format.widths, [8, 9, 6, 5, 5, 8]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_round_trip() -> anyhow::Result<()> {
+        let mut format = TableFormat::new(&Field::ALL)?;
+
+        let rows = vec![Field::ALL
+            .iter()
+            .map(|field| format!("{}-value", field.name()))
+            .collect::<Vec<_>>()];
+        format.update_widths_from_rows(&rows);
+
+        let header = format.header()?;
+        let header_lines: Vec<&str> = header.lines().collect();
+        let parsed = TableFormat::from_header(&header_lines)?;
+        assert_eq!(parsed.fields, format.fields);
+
+        let row = format.row_string(&rows[0]);
+        assert_eq!(row.split_whitespace().count(), Field::ALL.len());
+        assert!(row.starts_with("target-value query-value accession-value"));
+
+        Ok(())
+    }
+}
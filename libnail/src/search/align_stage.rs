@@ -1,52 +1,23 @@
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+use web_time::Instant;
 
 use anyhow::bail;
 use derive_builder::Builder;
-use libnail::{
+
+use crate::{
     align::{
         backward, forward, null_one_score, null_two_score, optimal_accuracy, p_value, posterior,
         structs::{Alignment, AlignmentBuilder, DpMatrixSparse, RowBounds, Trace},
-        traceback, Bits,
+        traceback, viterbi, viterbi_traceback, Bits,
     },
     structs::{Profile, Sequence},
 };
 
-use crate::args::SearchArgs;
-
-use super::StageResult;
+use super::{SearchParams, StageResult};
 
 pub type AlignStageResult = StageResult<Alignment, AlignStageStats>;
 
-impl AlignStageResult {
-    pub fn tab_string(&self) -> String {
-        match self {
-            StageResult::Filtered { stats } => {
-                format!(
-                    "F {:.2}b {:.1e} {} {}",
-                    stats.score.value(),
-                    stats.p_value,
-                    stats.forward_cells,
-                    stats.forward_time.as_nanos(),
-                )
-            }
-            StageResult::Passed { stats, data: ali } => {
-                format!(
-                    "P {:.2}b {:.1e} {} {} {} {} {} {} {:.1e}",
-                    stats.score.value(),
-                    stats.p_value,
-                    stats.forward_cells,
-                    stats.forward_time.as_nanos(),
-                    stats.backward_time.as_nanos(),
-                    stats.posterior_time.as_nanos(),
-                    stats.optimal_accuracy_time.as_nanos(),
-                    stats.null_two_time.as_nanos(),
-                    ali.scores.e_value,
-                )
-            }
-        }
-    }
-}
-
 #[derive(Builder, Default)]
 #[builder(setter(strip_option), default)]
 pub struct AlignStageStats {
@@ -59,10 +30,28 @@ pub struct AlignStageStats {
     pub backward_time: Duration,
     pub posterior_time: Duration,
     pub optimal_accuracy_time: Duration,
+    pub viterbi_time: Duration,
     pub traceback_time: Duration,
     pub null_two_time: Duration,
 }
 
+impl AlignStageStats {
+    pub fn total_cells(&self) -> usize {
+        self.forward_cells + self.backward_cells
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.memory_init_time
+            + self.forward_time
+            + self.backward_time
+            + self.posterior_time
+            + self.optimal_accuracy_time
+            + self.viterbi_time
+            + self.traceback_time
+            + self.null_two_time
+    }
+}
+
 impl AlignStageStatsBuilder {
     fn add_memory_init_time(&mut self, duration: Duration) {
         match self.memory_init_time {
@@ -74,17 +63,40 @@ impl AlignStageStatsBuilder {
     }
 }
 
+/// The path through the DP matrix that is reported as the alignment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlignPath {
+    /// The path that maximizes the sum of posterior probabilities, as in HMMER
+    #[default]
+    OptimalAccuracy,
+    /// The maximum scoring (Viterbi) path
+    Viterbi,
+}
+
 #[derive(Clone)]
 pub struct AlignConfig {
     pub do_null_two: bool,
+    pub path: AlignPath,
+    /// Keep the core steps of the trace in each alignment
+    pub keep_trace: bool,
 }
 
 impl Default for AlignConfig {
     fn default() -> Self {
-        Self { do_null_two: true }
+        Self {
+            do_null_two: true,
+            path: AlignPath::default(),
+            keep_trace: false,
+        }
     }
 }
 
+/// A stage that aligns a profile to a target within the row bounds found by cloud search.
+///
+/// The profile is passed mutably so that it can be configured for the target
+/// length. A passing result holds the alignment, with its E-value computed
+/// against the target database size; the caller applies the E-value threshold.
+/// Stages are selected by name through the [`StageRegistry`](super::StageRegistry).
 pub trait AlignStage: dyn_clone::DynClone + Send + Sync {
     fn run(
         &mut self,
@@ -108,21 +120,29 @@ pub struct DefaultAlignStage {
 }
 
 impl DefaultAlignStage {
-    pub fn new(args: &SearchArgs) -> anyhow::Result<Self> {
+    pub fn new(params: &SearchParams) -> anyhow::Result<Self> {
         Ok(Self {
-            target_count: match args.expert_args.target_database_size {
+            target_count: match params.target_database_size {
                 Some(size) => size,
                 None => {
                     bail!("no target database size")
                 }
             },
-            forward_p_value_threshold: args.pipeline_args.forward_pvalue_threshold,
+            forward_p_value_threshold: params.forward_p_value_threshold,
             config: AlignConfig {
-                do_null_two: !args.expert_args.no_null_two,
+                do_null_two: params.null_two,
+                keep_trace: params.keep_trace,
+                ..Default::default()
             },
             ..Default::default()
         })
     }
+
+    /// Report the given path through the DP matrix, rather than the optimal accuracy path.
+    pub fn with_path(mut self, path: AlignPath) -> Self {
+        self.config.path = path;
+        self
+    }
 }
 
 impl AlignStage for DefaultAlignStage {
@@ -185,25 +205,47 @@ impl AlignStage for DefaultAlignStage {
         );
         stats.posterior_time(now.elapsed());
 
-        let now = Instant::now();
-        optimal_accuracy(
-            profile,
-            &self.posterior_matrix,
-            &mut self.optimal_matrix,
-            bounds,
-        );
-        stats.optimal_accuracy_time(now.elapsed());
-
-        let now = Instant::now();
         let mut trace = Trace::new(target.length, profile.length);
-        traceback(
-            profile,
-            &self.posterior_matrix,
-            &self.optimal_matrix,
-            &mut trace,
-            bounds.target_end,
-        );
-        stats.traceback_time(now.elapsed());
+
+        match self.config.path {
+            AlignPath::OptimalAccuracy => {
+                let now = Instant::now();
+                optimal_accuracy(
+                    profile,
+                    &self.posterior_matrix,
+                    &mut self.optimal_matrix,
+                    bounds,
+                );
+                stats.optimal_accuracy_time(now.elapsed());
+
+                let now = Instant::now();
+                traceback(
+                    profile,
+                    &self.posterior_matrix,
+                    &self.optimal_matrix,
+                    &mut trace,
+                    bounds.target_end,
+                );
+                stats.traceback_time(now.elapsed());
+            }
+            // the Viterbi scores are written to the optimal accuracy
+            // matrix, since only one of the two is ever filled
+            AlignPath::Viterbi => {
+                let now = Instant::now();
+                viterbi(profile, target, &mut self.optimal_matrix, bounds);
+                stats.viterbi_time(now.elapsed());
+
+                let now = Instant::now();
+                viterbi_traceback(
+                    profile,
+                    &self.posterior_matrix,
+                    &self.optimal_matrix,
+                    &mut trace,
+                    bounds,
+                );
+                stats.traceback_time(now.elapsed());
+            }
+        }
 
         let null_two_score = if self.config.do_null_two {
             let now = Instant::now();
@@ -227,6 +269,7 @@ impl AlignStage for DefaultAlignStage {
                 .with_cell_count(bounds.num_cells)
                 .with_forward_score(forward_score)
                 .with_trace(&trace)
+                .with_trace_steps(self.config.keep_trace)
                 .with_null_two(null_two_score)
                 .build()
                 .unwrap(),
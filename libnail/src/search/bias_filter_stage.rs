@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+use derive_builder::Builder;
+
+use crate::{
+    align::{bias_filter_score, p_value, structs::Seed, Bits, Nats},
+    structs::{Profile, Sequence},
+};
+
+use super::{SearchParams, StageResult};
+
+pub type BiasFilterStageResult = StageResult<(), BiasFilterStageStats>;
+
+#[derive(Builder, Default)]
+#[builder(setter(strip_option), default)]
+pub struct BiasFilterStageStats {
+    /// The composition bias of the seed's target region
+    pub bias: Nats,
+    /// The seed score after subtracting the composition bias
+    pub corrected_score: Bits,
+    /// The P-value of the corrected score under the profile's Forward tail
+    /// parameters; these were not fit to seed scores, so it is uncalibrated
+    pub p_value: f64,
+    pub time: Duration,
+}
+
+/// A stage that filters seeds before any DP is done.
+///
+/// The target may have been masked for low complexity. A stage is cloned
+/// for each worker thread, so any scratch space it holds is not shared.
+pub trait BiasFilterStage: dyn_clone::DynClone + Send + Sync {
+    fn run(&mut self, profile: &Profile, target: &Sequence, seed: &Seed) -> BiasFilterStageResult;
+}
+
+dyn_clone::clone_trait_object!(BiasFilterStage);
+
+/// Build the bias filter stage, if the search parameters enable it.
+///
+/// When the filter is disabled the stage is skipped entirely, so
+/// seeds have no bias filter result rather than a passing one.
+pub fn bias_filter_stage(params: &SearchParams) -> Option<Box<dyn BiasFilterStage>> {
+    match params.bias_filter {
+        true => Some(Box::new(DefaultBiasFilterStage::new(params))),
+        false => None,
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct DefaultBiasFilterStage {
+    p_value_threshold: f64,
+    // the composition of the most recent profile, so that we
+    // don't recompute it for every target the profile is aligned to
+    composition_cache: Option<(String, Vec<f32>)>,
+}
+
+impl DefaultBiasFilterStage {
+    pub fn new(params: &SearchParams) -> Self {
+        Self {
+            p_value_threshold: params.bias_p_value_threshold,
+            ..Default::default()
+        }
+    }
+
+    fn composition(&mut self, profile: &Profile) -> &[f32] {
+        let cached = matches!(&self.composition_cache, Some((name, _)) if *name == profile.name);
+
+        if !cached {
+            self.composition_cache = Some((profile.name.clone(), profile.composition()));
+        }
+
+        match &self.composition_cache {
+            Some((_, composition)) => composition,
+            None => unreachable!(),
+        }
+    }
+}
+
+impl BiasFilterStage for DefaultBiasFilterStage {
+    fn run(&mut self, profile: &Profile, target: &Sequence, seed: &Seed) -> BiasFilterStageResult {
+        let now = Instant::now();
+        let mut stats = BiasFilterStageStatsBuilder::default();
+
+        let bias = bias_filter_score(
+            self.composition(profile),
+            profile.length,
+            target,
+            seed.target_start,
+            seed.target_end,
+        );
+
+        // the seed score is the MMseqs2 bit score of the seed alignment, which
+        // we treat as a (rough) proxy for the Forward score of the seed region;
+        // the profile's Forward lambda and tau were never fit to seed scores,
+        // so the P-value is an uncalibrated heuristic, not a real P-value
+        // (see bias_filter_score() for the other approximations)
+        let corrected_score = Bits(seed.score) - bias.to_bits();
+        let p_value = p_value(corrected_score, profile.forward_lambda, profile.forward_tau);
+
+        stats.bias(bias);
+        stats.corrected_score(corrected_score);
+        stats.p_value(p_value);
+        stats.time(now.elapsed());
+
+        if p_value >= self.p_value_threshold {
+            StageResult::Filtered {
+                stats: stats.build().unwrap(),
+            }
+        } else {
+            StageResult::Passed {
+                data: (),
+                stats: stats.build().unwrap(),
+            }
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+use derive_builder::Builder;
+
+use crate::{
+    align::{
+        cloud_score, cloud_search_backward, cloud_search_forward, p_value,
+        structs::{AntiDiagonalBounds, CloudMatrixLinear, RowBounds, Seed},
+        CloudSearchParams, Nats,
+    },
+    structs::{Profile, Sequence},
+};
+
+use super::{SearchParams, StageResult};
+
+pub type CloudStageResult = StageResult<RowBounds, CloudStageStats>;
+
+/// Pairs with at most this many DP cells are aligned with
+/// full DP if the cloud search fails to produce valid bounds.
+pub const TRIM_FALLBACK_FULL_DP_MAX_CELLS: usize = 1 << 20;
+
+/// The recovery path taken when trimming the cloud's wings fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrimFallback {
+    /// The seed's target range was padded by the profile
+    /// length, and the entire profile range was filled
+    Expanded,
+    /// The entire DP matrix was filled
+    FullDp,
+}
+
+impl std::fmt::Display for TrimFallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrimFallback::Expanded => write!(f, "expanded"),
+            TrimFallback::FullDp => write!(f, "full"),
+        }
+    }
+}
+
+#[derive(Builder, Default)]
+#[builder(setter(strip_option), default)]
+pub struct CloudStageStats {
+    pub forward_cells: usize,
+    pub backward_cells: usize,
+    /// The cloud search score; this is `None` if cloud search didn't run
+    pub score: Option<Nats>,
+    /// The P-value of the cloud search score; this is `None` if cloud search didn't run
+    pub p_value: Option<f64>,
+    pub alpha: f32,
+    pub beta: f32,
+    pub trim_fallback: Option<TrimFallback>,
+    pub full_dp_routed: bool,
+    pub estimated_cells_saved: usize,
+    pub memory_init_time: Duration,
+    pub forward_time: Duration,
+    pub backward_time: Duration,
+    pub merge_time: Duration,
+    pub trim_time: Duration,
+    pub reorient_time: Duration,
+}
+
+impl CloudStageStats {
+    pub fn total_cells(&self) -> usize {
+        self.forward_cells + self.backward_cells
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.memory_init_time
+            + self.forward_time
+            + self.backward_time
+            + self.merge_time
+            + self.trim_time
+            + self.reorient_time
+    }
+}
+
+/// A stage that turns a seed into the region of the DP matrix that alignment is restricted to.
+///
+/// A passing result must hold row bounds that are [`valid`](RowBounds::valid)
+/// and lie within the target and profile; its `num_cells` must count the
+/// cells within them. A stage may filter a pair on its own score. Stages are
+/// selected by name through the [`StageRegistry`](super::StageRegistry).
+pub trait CloudSearchStage: dyn_clone::DynClone + Send + Sync {
+    fn run(&mut self, profile: &Profile, target: &Sequence, seed: &Seed) -> CloudStageResult;
+}
+
+dyn_clone::clone_trait_object!(CloudSearchStage);
+
+#[derive(Default, Clone)]
+pub struct FullDpCloudSearchStage {}
+
+impl CloudSearchStage for FullDpCloudSearchStage {
+    fn run(&mut self, profile: &Profile, target: &Sequence, _seed: &Seed) -> CloudStageResult {
+        let mut row_bounds = RowBounds::default();
+        row_bounds.fill_rectangle(1, 1, target.length, profile.length);
+
+        StageResult::Passed {
+            data: row_bounds,
+            stats: CloudStageStats::default(),
+        }
+    }
+}
+
+/// The default half-width of the band filled by [`BandedCloudSearchStage`].
+pub const DEFAULT_BAND_WIDTH: usize = 32;
+
+/// A stage that fills a fixed-width band around the seed's diagonal instead
+/// of running cloud search. This stage never filters a pair.
+#[derive(Clone)]
+pub struct BandedCloudSearchStage {
+    /// The number of cells on either side of the diagonal
+    pub width: usize,
+}
+
+impl Default for BandedCloudSearchStage {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_BAND_WIDTH,
+        }
+    }
+}
+
+impl BandedCloudSearchStage {
+    pub fn new(width: usize) -> Self {
+        Self { width }
+    }
+}
+
+impl CloudSearchStage for BandedCloudSearchStage {
+    fn run(&mut self, profile: &Profile, target: &Sequence, seed: &Seed) -> CloudStageResult {
+        let mut stats = CloudStageStatsBuilder::default();
+
+        let now = Instant::now();
+        let mut row_bounds = RowBounds::default();
+        row_bounds.fill_band(seed, target.length, profile.length, self.width);
+        stats.reorient_time(now.elapsed());
+
+        StageResult::Passed {
+            data: row_bounds,
+            stats: stats.build().unwrap(),
+        }
+    }
+}
+
+/// A cost model for deciding whether a pair is cheaper
+/// to align with full DP than with cloud search.
+#[derive(Default, Clone)]
+pub struct CloudCostModel {
+    /// The estimated cost of cloud search per anti-diagonal, in DP cells;
+    /// this covers both passes as well as the merge, trim, and reorientation
+    pub cells_per_anti_diagonal: usize,
+}
+
+impl CloudCostModel {
+    pub fn cloud_cost(&self, profile_length: usize, target_length: usize) -> usize {
+        self.cells_per_anti_diagonal * (profile_length + target_length)
+    }
+
+    pub fn full_dp_cost(&self, profile_length: usize, target_length: usize) -> usize {
+        profile_length * target_length
+    }
+
+    /// Returns the estimated number of cells saved by skipping cloud
+    /// search and filling the full DP matrix, if there are any savings.
+    pub fn full_dp_savings(&self, profile_length: usize, target_length: usize) -> Option<usize> {
+        self.cloud_cost(profile_length, target_length)
+            .checked_sub(self.full_dp_cost(profile_length, target_length))
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct DefaultCloudSearchStage {
+    cloud_matrix: CloudMatrixLinear,
+    forward_bounds: AntiDiagonalBounds,
+    reverse_bounds: AntiDiagonalBounds,
+    params: CloudSearchParams,
+    adaptive_pruning: bool,
+    // the relative entropy of the most recent profile, so that we
+    // don't recompute it for every target the profile is aligned to
+    relative_entropy_cache: Option<(String, f32)>,
+    p_value_threshold: f64,
+    cost_model: CloudCostModel,
+}
+
+impl DefaultCloudSearchStage {
+    pub fn new(params: &SearchParams) -> Self {
+        Self {
+            params: params.cloud_search.clone(),
+            adaptive_pruning: params.adaptive_pruning,
+            p_value_threshold: params.cloud_p_value_threshold,
+            cost_model: CloudCostModel {
+                cells_per_anti_diagonal: params.cloud_cost_per_anti_diagonal,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn relative_entropy(&mut self, profile: &Profile) -> f32 {
+        if let Some((name, relative_entropy)) = &self.relative_entropy_cache {
+            if *name == profile.name {
+                return *relative_entropy;
+            }
+        }
+
+        let relative_entropy = profile.relative_entropy();
+        self.relative_entropy_cache = Some((profile.name.clone(), relative_entropy));
+        relative_entropy
+    }
+
+    fn params_for(&mut self, profile: &Profile, target: &Sequence) -> CloudSearchParams {
+        if self.adaptive_pruning {
+            let relative_entropy = self.relative_entropy(profile);
+            self.params
+                .scaled(profile.length, target.length, relative_entropy)
+        } else {
+            self.params.clone()
+        }
+    }
+}
+
+impl CloudSearchStage for DefaultCloudSearchStage {
+    fn run(&mut self, profile: &Profile, target: &Sequence, seed: &Seed) -> CloudStageResult {
+        let mut stats = CloudStageStatsBuilder::default();
+
+        // for tiny pairs, the overhead of cloud search
+        // exceeds the cost of computing the whole matrix
+        //
+        // note: there is no cloud score for these pairs, so they
+        //       are only filtered by the align stage's Forward
+        //       threshold; the score and P-value are left unset
+        if let Some(savings) = self
+            .cost_model
+            .full_dp_savings(profile.length, target.length)
+        {
+            let now = Instant::now();
+            let mut row_bounds = RowBounds::new(target.length);
+            row_bounds.fill_rectangle(1, 1, target.length, profile.length);
+            stats.reorient_time(now.elapsed());
+
+            stats.full_dp_routed(true);
+            stats.estimated_cells_saved(savings);
+
+            return StageResult::Passed {
+                data: row_bounds,
+                stats: stats.build().unwrap(),
+            };
+        }
+
+        let params = self.params_for(profile, target);
+        stats.alpha(params.alpha);
+        stats.beta(params.beta);
+
+        let now = Instant::now();
+        self.cloud_matrix.reuse(profile.length);
+        self.forward_bounds.reuse(target.length, profile.length);
+        self.reverse_bounds.reuse(target.length, profile.length);
+        let mut row_bounds = RowBounds::new(target.length);
+        stats.memory_init_time(now.elapsed());
+
+        let now = Instant::now();
+        let forward_results = cloud_search_forward(
+            profile,
+            target,
+            seed,
+            &mut self.cloud_matrix,
+            &params,
+            &mut self.forward_bounds,
+        );
+        stats.forward_time(now.elapsed());
+        stats.forward_cells(forward_results.num_cells_computed);
+
+        let now = Instant::now();
+        let backward_results = cloud_search_backward(
+            profile,
+            target,
+            seed,
+            &mut self.cloud_matrix,
+            &params,
+            &mut self.reverse_bounds,
+        );
+        stats.backward_time(now.elapsed());
+        stats.backward_cells(backward_results.num_cells_computed);
+
+        let cloud_score = cloud_score(&forward_results, &backward_results);
+        let cloud_p_value = p_value(cloud_score, profile.forward_lambda, profile.forward_tau);
+
+        stats.score(cloud_score);
+        stats.p_value(cloud_p_value);
+
+        if cloud_p_value >= self.p_value_threshold {
+            return StageResult::Filtered {
+                stats: stats.build().unwrap(),
+            };
+        }
+
+        let now = Instant::now();
+        self.forward_bounds.merge(&self.reverse_bounds);
+        stats.merge_time(now.elapsed());
+
+        self.forward_bounds.square_corners();
+
+        fill_trimmed_row_bounds(
+            &mut self.forward_bounds,
+            &mut row_bounds,
+            profile.length,
+            target.length,
+            seed,
+            &mut stats,
+        );
+
+        StageResult::Passed {
+            data: row_bounds,
+            stats: stats.build().unwrap(),
+        }
+    }
+}
+
+/// Trim the wings of the cloud and fill the row bounds from it.
+///
+/// If trimming fails, the row bounds are filled with a fallback
+/// rectangle instead, and the fallback is recorded in the stats.
+fn fill_trimmed_row_bounds(
+    bounds: &mut AntiDiagonalBounds,
+    row_bounds: &mut RowBounds,
+    profile_length: usize,
+    target_length: usize,
+    seed: &Seed,
+    stats: &mut CloudStageStatsBuilder,
+) {
+    let now = Instant::now();
+    let trim_result = bounds.trim_wings();
+    stats.trim_time(now.elapsed());
+
+    let now = Instant::now();
+    match trim_result {
+        Ok(_) => row_bounds.fill_from_anti_diagonal_bounds(bounds),
+        // if trimming fails, the cloud's shape can't be trusted, so
+        // we fill a rectangle that is large enough to contain any
+        // reasonable alignment through the seed instead of the seed
+        // itself, which would likely truncate the alignment
+        Err(_) => {
+            if profile_length * target_length <= TRIM_FALLBACK_FULL_DP_MAX_CELLS {
+                row_bounds.fill_rectangle(1, 1, target_length, profile_length);
+                stats.trim_fallback(TrimFallback::FullDp);
+            } else {
+                row_bounds.fill_rectangle(
+                    seed.target_start.saturating_sub(profile_length).max(1),
+                    1,
+                    (seed.target_end + profile_length).min(target_length),
+                    profile_length,
+                );
+                stats.trim_fallback(TrimFallback::Expanded);
+            }
+        }
+    }
+    stats.reorient_time(now.elapsed());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Anti-diagonal bounds that can't be trimmed: the cloud
+    /// jumps two rows down between anti-diagonals 4 and 5.
+    fn untrimmable_bounds(target_length: usize, profile_length: usize) -> AntiDiagonalBounds {
+        let mut bounds = AntiDiagonalBounds::new(target_length, profile_length);
+        bounds.set(4, 2, 2, 2, 2);
+        bounds.set(5, 4, 1, 4, 1);
+        bounds.min_anti_diagonal_idx = 4;
+        bounds.max_anti_diagonal_idx = 5;
+        bounds
+    }
+
+    #[test]
+    fn test_trim_fallback() {
+        let seed = Seed {
+            target_start: 2,
+            target_end: 4,
+            profile_start: 1,
+            profile_end: 2,
+            score: 0.0,
+        };
+
+        // a small pair falls back to full DP
+        let (target_length, profile_length) = (10, 10);
+        let mut bounds = untrimmable_bounds(target_length, profile_length);
+        assert!(bounds.clone().trim_wings().is_err());
+
+        let mut row_bounds = RowBounds::new(target_length);
+        let mut stats = CloudStageStatsBuilder::default();
+        fill_trimmed_row_bounds(
+            &mut bounds,
+            &mut row_bounds,
+            profile_length,
+            target_length,
+            &seed,
+            &mut stats,
+        );
+
+        let stats = stats.build().unwrap();
+        assert_eq!(stats.trim_fallback, Some(TrimFallback::FullDp));
+        assert_eq!((row_bounds.target_start, row_bounds.target_end), (1, 10));
+        assert_eq!(row_bounds.num_cells, target_length * profile_length);
+        assert!(row_bounds.valid());
+
+        // a large pair falls back to the seed's rows padded by the profile length
+        let (target_length, profile_length) = (5000, 300);
+        let seed = Seed {
+            target_start: 2000,
+            target_end: 2100,
+            ..seed
+        };
+        let mut bounds = untrimmable_bounds(target_length, profile_length);
+
+        let mut row_bounds = RowBounds::new(target_length);
+        let mut stats = CloudStageStatsBuilder::default();
+        fill_trimmed_row_bounds(
+            &mut bounds,
+            &mut row_bounds,
+            profile_length,
+            target_length,
+            &seed,
+            &mut stats,
+        );
+
+        let stats = stats.build().unwrap();
+        assert_eq!(stats.trim_fallback, Some(TrimFallback::Expanded));
+        assert_eq!(
+            (row_bounds.target_start, row_bounds.target_end),
+            (1700, 2400)
+        );
+        assert_eq!(row_bounds.num_cells, 701 * profile_length);
+        assert!(row_bounds.valid());
+
+        // trimmable bounds don't fall back
+        let mut bounds = AntiDiagonalBounds::new(10, 10);
+        bounds.set(4, 2, 2, 2, 2);
+        bounds.set(5, 3, 2, 2, 3);
+        bounds.min_anti_diagonal_idx = 4;
+        bounds.max_anti_diagonal_idx = 5;
+
+        let mut row_bounds = RowBounds::new(10);
+        let mut stats = CloudStageStatsBuilder::default();
+        fill_trimmed_row_bounds(&mut bounds, &mut row_bounds, 10, 10, &seed, &mut stats);
+        assert_eq!(stats.build().unwrap().trim_fallback, None);
+    }
+}
@@ -0,0 +1,321 @@
+mod align_stage;
+pub use align_stage::*;
+
+mod bias_filter_stage;
+pub use bias_filter_stage::*;
+
+mod cloud_stage;
+pub use cloud_stage::*;
+
+mod registry;
+pub use registry::*;
+
+mod seed_stage;
+pub use seed_stage::*;
+
+#[cfg(feature = "fs")]
+mod searcher;
+#[cfg(feature = "fs")]
+pub use searcher::*;
+
+#[cfg(feature = "fs")]
+use std::{cell::RefCell, collections::HashMap};
+
+#[cfg(feature = "fs")]
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+#[cfg(feature = "fs")]
+use thread_local::ThreadLocal;
+
+#[cfg(feature = "fs")]
+use crate::io::SequenceDatabase;
+
+use crate::{
+    align::{
+        structs::{Alignment, Seed},
+        CloudSearchParams,
+    },
+    mask::{mask_low_complexity, SegParams},
+    structs::{Profile, Sequence},
+};
+
+pub enum StageResult<D, S> {
+    Filtered { stats: S },
+    Passed { data: D, stats: S },
+}
+
+impl<D, S> StageResult<D, S> {
+    pub fn stats(&self) -> &S {
+        match self {
+            StageResult::Passed { data: _, stats } => stats,
+            StageResult::Filtered { stats } => stats,
+        }
+    }
+}
+
+/// The results of each stage for one profile/target pair.
+pub struct PipelineResult {
+    pub profile_name: String,
+    pub target_name: String,
+    pub profile_length: usize,
+    pub target_length: usize,
+    pub seed: Seed,
+    pub bias_filter_result: Option<BiasFilterStageResult>,
+    pub cloud_result: Option<CloudStageResult>,
+    pub align_result: Option<AlignStageResult>,
+}
+
+/// The parameters of the search pipeline.
+///
+/// The defaults match the defaults of `nail search`.
+#[derive(Clone)]
+pub struct SearchParams {
+    /// The pruning parameters of cloud search
+    pub cloud_search: CloudSearchParams,
+    /// Scale the pruning parameters to each profile/target pair
+    pub adaptive_pruning: bool,
+    /// The estimated cost of cloud search per anti-diagonal, in DP cells;
+    /// pairs that are cheaper to align fully skip cloud search. Skipped
+    /// pairs aren't filtered by the cloud search P-value threshold, only
+    /// by the Forward threshold. If this is 0, cloud search always runs.
+    pub cloud_cost_per_anti_diagonal: usize,
+    /// Skip cloud search entirely and fill the full DP matrix of every pair
+    pub full_dp: bool,
+    /// Filter seeds by their composition bias corrected score
+    pub bias_filter: bool,
+    pub bias_p_value_threshold: f64,
+    pub cloud_p_value_threshold: f64,
+    pub forward_p_value_threshold: f64,
+    pub e_value_threshold: f64,
+    /// The number of comparisons used for E-value calculation;
+    /// if `None`, this is the number of sequences in the target database
+    pub target_database_size: Option<usize>,
+    /// Compute the composition bias score correction
+    pub null_two: bool,
+    /// If set, [`align_pair`] masks the target for low complexity before
+    /// alignment; [`AlignmentStages`] expect a target database that is
+    /// already masked, so that each target is only masked once
+    pub target_mask: Option<SegParams>,
+    /// The cloud search stage, looked up in the [`StageRegistry`];
+    /// this is overridden by `full_dp`
+    pub cloud_stage: StageSpec,
+    /// The align stage, looked up in the [`StageRegistry`]
+    pub align_stage: StageSpec,
+    /// Keep the core steps of each alignment's trace, which
+    /// are needed for JSON and SAM output, but not for display
+    pub keep_trace: bool,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            cloud_search: CloudSearchParams::default(),
+            adaptive_pruning: false,
+            cloud_cost_per_anti_diagonal: 0,
+            full_dp: false,
+            bias_filter: false,
+            bias_p_value_threshold: 0.02,
+            cloud_p_value_threshold: 1e-3,
+            forward_p_value_threshold: 1e-4,
+            e_value_threshold: 10.0,
+            target_database_size: None,
+            null_two: true,
+            target_mask: None,
+            cloud_stage: StageSpec::default(),
+            align_stage: StageSpec::default(),
+            keep_trace: false,
+        }
+    }
+}
+
+impl SearchParams {
+    /// The cloud search stage to run, taking `full_dp` into account.
+    pub fn cloud_stage_spec(&self) -> StageSpec {
+        match self.full_dp {
+            true => StageSpec::new("full"),
+            false => self.cloud_stage.clone(),
+        }
+    }
+}
+
+/// The stages of the search pipeline that follow seeding,
+/// along with the target database that they align against.
+#[cfg(feature = "fs")]
+pub struct AlignmentStages {
+    /// The targets are aligned as they are stored; to mask them for low
+    /// complexity, mask the database once up front, e.g. with [`mask_fasta`]
+    ///
+    /// [`mask_fasta`]: crate::mask::mask_fasta
+    pub targets: Box<dyn SequenceDatabase>,
+    /// If set, seeds are filtered for composition bias before cloud search
+    pub bias_filter: Option<Box<dyn BiasFilterStage>>,
+    pub cloud_search: Box<dyn CloudSearchStage>,
+    pub align: Box<dyn AlignStage>,
+    // each worker thread's copy of the stages, which hold scratch
+    // space; these are kept so that later queries can reuse it
+    workers: ThreadLocal<RefCell<AlignmentStages>>,
+}
+
+#[cfg(feature = "fs")]
+impl Clone for AlignmentStages {
+    /// The clone starts without any worker copies of the stages.
+    fn clone(&self) -> Self {
+        Self {
+            targets: self.targets.clone(),
+            bias_filter: self.bias_filter.clone(),
+            cloud_search: self.cloud_search.clone(),
+            align: self.align.clone(),
+            workers: ThreadLocal::new(),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl AlignmentStages {
+    /// Build the stages named by the search parameters from the default registry.
+    pub fn new(targets: Box<dyn SequenceDatabase>, params: &SearchParams) -> anyhow::Result<Self> {
+        Self::with_registry(targets, params, &StageRegistry::default())
+    }
+
+    /// Build the stages named by the search parameters from the given registry.
+    pub fn with_registry(
+        targets: Box<dyn SequenceDatabase>,
+        params: &SearchParams,
+        registry: &StageRegistry,
+    ) -> anyhow::Result<Self> {
+        let mut params = params.clone();
+        params.target_database_size = params.target_database_size.or(Some(targets.len()));
+
+        Ok(Self {
+            targets,
+            bias_filter: bias_filter_stage(&params),
+            cloud_search: registry.cloud_stage(&params.cloud_stage_spec(), &params)?,
+            align: registry.align_stage(&params.align_stage, &params)?,
+            workers: ThreadLocal::new(),
+        })
+    }
+
+    /// Run the stages for each of a profile's seeds.
+    ///
+    /// The seeds are split across worker threads.
+    pub fn run(&self, profile: &Profile, seeds: &HashMap<String, Seed>) -> Vec<PipelineResult> {
+        seeds
+            .par_iter()
+            // each job gets its own copy of the
+            // profile, which is configured for each target
+            .map_init(
+                || profile.clone(),
+                |profile, (target_name, seed)| {
+                    // the stages are never borrowed across a rayon call, so a thread
+                    // that picks up another query's seeds can't borrow them twice
+                    let mut worker = self
+                        .workers
+                        .get_or(|| RefCell::new(self.clone()))
+                        .borrow_mut();
+                    let stages = &mut *worker;
+
+                    let target = match stages.targets.get(target_name) {
+                        Some(target) => target,
+                        // TODO: probably return an error here instead
+                        None => return None,
+                    };
+
+                    let bias_filter_result = stages
+                        .bias_filter
+                        .as_mut()
+                        .map(|stage| stage.run(profile, &target, seed));
+
+                    let cloud_result = match bias_filter_result {
+                        Some(StageResult::Filtered { .. }) => None,
+                        _ => Some(stages.cloud_search.run(profile, &target, seed)),
+                    };
+
+                    let align_result = match cloud_result {
+                        Some(StageResult::Passed {
+                            data: ref bounds, ..
+                        }) => Some(stages.align.run(profile, &target, bounds)),
+                        _ => None,
+                    };
+
+                    Some(PipelineResult {
+                        profile_name: profile.name.clone(),
+                        target_name: target.name.clone(),
+                        profile_length: profile.length,
+                        target_length: target.length,
+                        seed: seed.clone(),
+                        bias_filter_result,
+                        cloud_result,
+                        align_result,
+                    })
+                },
+            )
+            .flatten()
+            .collect()
+    }
+}
+
+/// Run the stages of the search pipeline that follow seeding for a single
+/// profile/target pair, returning the alignment if it is reported.
+///
+/// If no seed is given, the full DP matrix is aligned. If the target
+/// database size is not set, E-values are computed for a single comparison.
+/// The stages are looked up in the default [`StageRegistry`].
+pub fn align_pair(
+    profile: &Profile,
+    target: &Sequence,
+    seed: Option<&Seed>,
+    params: &SearchParams,
+) -> anyhow::Result<Option<Alignment>> {
+    let mut params = params.clone();
+    params.target_database_size = params.target_database_size.or(Some(1));
+
+    let mut bias_filter = bias_filter_stage(&params);
+
+    let registry = StageRegistry::default();
+
+    let cloud_stage = match seed {
+        Some(_) => params.cloud_stage_spec(),
+        None => StageSpec::new("full"),
+    };
+    let mut cloud_search = registry.cloud_stage(&cloud_stage, &params)?;
+    let mut align = registry.align_stage(&params.align_stage, &params)?;
+
+    let seed = match seed {
+        Some(seed) => seed.clone(),
+        None => Seed {
+            target_start: 1,
+            target_end: target.length,
+            profile_start: 1,
+            profile_end: profile.length,
+            score: 0.0,
+        },
+    };
+
+    let masked;
+    let target = match params.target_mask {
+        Some(ref mask_params) => {
+            masked = mask_low_complexity(target, mask_params);
+            &masked
+        }
+        None => target,
+    };
+
+    let mut profile = profile.clone();
+
+    if let Some(ref mut bias_filter) = bias_filter {
+        if let StageResult::Filtered { .. } = bias_filter.run(&profile, target, &seed) {
+            return Ok(None);
+        }
+    }
+
+    let bounds = match cloud_search.run(&profile, target, &seed) {
+        StageResult::Passed { data, .. } => data,
+        StageResult::Filtered { .. } => return Ok(None),
+    };
+
+    match align.run(&mut profile, target, &bounds) {
+        StageResult::Passed { data, .. } if data.scores.e_value <= params.e_value_threshold => {
+            Ok(Some(data))
+        }
+        _ => Ok(None),
+    }
+}
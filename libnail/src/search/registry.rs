@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use thiserror::Error;
+
+use super::{
+    AlignPath, AlignStage, BandedCloudSearchStage, CloudSearchStage, DefaultAlignStage,
+    DefaultCloudSearchStage, FullDpCloudSearchStage, SearchParams, DEFAULT_BAND_WIDTH,
+};
+
+/// The name of the stage that is used when none is given.
+pub const DEFAULT_STAGE_NAME: &str = "default";
+
+/// A stage selected by name, along with its options.
+///
+/// This is parsed from strings of the form `name[:key=value,...]`,
+/// e.g. `banded:width=16`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageSpec {
+    pub name: String,
+    pub options: StageOptions,
+}
+
+impl StageSpec {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            options: StageOptions::default(),
+        }
+    }
+}
+
+impl Default for StageSpec {
+    fn default() -> Self {
+        Self::new(DEFAULT_STAGE_NAME)
+    }
+}
+
+impl FromStr for StageSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, options) = match s.split_once(':') {
+            Some((name, options)) => (name, Some(options)),
+            None => (s, None),
+        };
+
+        if name.is_empty() {
+            return Err("missing stage name".to_string());
+        }
+
+        let mut spec = Self::new(name);
+
+        for option in options.into_iter().flat_map(|o| o.split(',')) {
+            match option.split_once('=') {
+                Some((key, value)) if !key.is_empty() => {
+                    spec.options.0.insert(key.to_string(), value.to_string());
+                }
+                _ => return Err(format!("stage option \"{option}\" is not key=value")),
+            }
+        }
+
+        Ok(spec)
+    }
+}
+
+impl Display for StageSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        for (idx, (key, value)) in self.options.0.iter().enumerate() {
+            let separator = if idx == 0 { ':' } else { ',' };
+            write!(f, "{separator}{key}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The options that are passed to a stage's constructor.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StageOptions(BTreeMap<String, String>);
+
+impl StageOptions {
+    /// Parse the value of an option, if it was given.
+    pub fn get<T>(&self, key: &str) -> anyhow::Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        self.0
+            .get(key)
+            .map(|value| {
+                value
+                    .parse::<T>()
+                    .with_context(|| format!("invalid value \"{value}\" for stage option {key}"))
+            })
+            .transpose()
+    }
+
+    /// Fail if any option other than the given keys was set.
+    pub fn expect_keys(&self, keys: &[&str]) -> anyhow::Result<()> {
+        for key in self.0.keys() {
+            if !keys.contains(&key.as_str()) {
+                bail!("unknown stage option: {key}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("unknown {kind} stage \"{name}\"; expected one of: {}", available.join(", "))]
+pub struct UnknownStageError {
+    pub kind: &'static str,
+    pub name: String,
+    pub available: Vec<String>,
+}
+
+pub type CloudStageConstructor =
+    fn(&SearchParams, &StageOptions) -> anyhow::Result<Box<dyn CloudSearchStage>>;
+
+pub type AlignStageConstructor =
+    fn(&SearchParams, &StageOptions) -> anyhow::Result<Box<dyn AlignStage>>;
+
+#[derive(Clone)]
+pub struct RegisteredStage<C> {
+    pub name: String,
+    pub description: String,
+    pub constructor: C,
+}
+
+/// The named cloud search and align stages that a pipeline can be built from.
+///
+/// The default registry holds the built-in stages:
+///
+///   - cloud `default`: cloud search, as configured by the search parameters
+///   - cloud `full`: the full DP matrix
+///   - cloud `banded`: a band around the seed's diagonal; `width` sets its half-width
+///   - align `default`: the optimal accuracy alignment
+///   - align `viterbi`: the maximum scoring alignment
+#[derive(Clone)]
+pub struct StageRegistry {
+    cloud_stages: Vec<RegisteredStage<CloudStageConstructor>>,
+    align_stages: Vec<RegisteredStage<AlignStageConstructor>>,
+}
+
+impl Default for StageRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register_cloud_stage(
+            DEFAULT_STAGE_NAME,
+            "cloud search around the seed",
+            |params, options| {
+                options.expect_keys(&[])?;
+                Ok(Box::new(DefaultCloudSearchStage::new(params)))
+            },
+        );
+        registry.register_cloud_stage("full", "the full DP matrix", |_, options| {
+            options.expect_keys(&[])?;
+            Ok(Box::<FullDpCloudSearchStage>::default())
+        });
+        registry.register_cloud_stage(
+            "banded",
+            "a band around the seed's diagonal [width]",
+            |_, options| {
+                options.expect_keys(&["width"])?;
+                let width = options.get("width")?.unwrap_or(DEFAULT_BAND_WIDTH);
+                Ok(Box::new(BandedCloudSearchStage::new(width)))
+            },
+        );
+
+        registry.register_align_stage(
+            DEFAULT_STAGE_NAME,
+            "the optimal accuracy alignment",
+            |params, options| {
+                options.expect_keys(&[])?;
+                Ok(Box::new(DefaultAlignStage::new(params)?))
+            },
+        );
+        registry.register_align_stage(
+            "viterbi",
+            "the maximum scoring alignment",
+            |params, options| {
+                options.expect_keys(&[])?;
+                Ok(Box::new(
+                    DefaultAlignStage::new(params)?.with_path(AlignPath::Viterbi),
+                ))
+            },
+        );
+
+        registry
+    }
+}
+
+impl StageRegistry {
+    /// A registry with no stages.
+    pub fn empty() -> Self {
+        Self {
+            cloud_stages: vec![],
+            align_stages: vec![],
+        }
+    }
+
+    /// Register a cloud search stage, replacing any stage with the same name.
+    pub fn register_cloud_stage(
+        &mut self,
+        name: &str,
+        description: &str,
+        constructor: CloudStageConstructor,
+    ) {
+        register(&mut self.cloud_stages, name, description, constructor);
+    }
+
+    /// Register an align stage, replacing any stage with the same name.
+    pub fn register_align_stage(
+        &mut self,
+        name: &str,
+        description: &str,
+        constructor: AlignStageConstructor,
+    ) {
+        register(&mut self.align_stages, name, description, constructor);
+    }
+
+    pub fn cloud_stages(&self) -> &[RegisteredStage<CloudStageConstructor>] {
+        &self.cloud_stages
+    }
+
+    pub fn align_stages(&self) -> &[RegisteredStage<AlignStageConstructor>] {
+        &self.align_stages
+    }
+
+    /// Build the cloud search stage that the spec names.
+    pub fn cloud_stage(
+        &self,
+        spec: &StageSpec,
+        params: &SearchParams,
+    ) -> anyhow::Result<Box<dyn CloudSearchStage>> {
+        let stage = find(&self.cloud_stages, "cloud", &spec.name)?;
+        (stage.constructor)(params, &spec.options)
+            .with_context(|| format!("failed to build cloud stage: {spec}"))
+    }
+
+    /// Build the align stage that the spec names.
+    pub fn align_stage(
+        &self,
+        spec: &StageSpec,
+        params: &SearchParams,
+    ) -> anyhow::Result<Box<dyn AlignStage>> {
+        let stage = find(&self.align_stages, "align", &spec.name)?;
+        (stage.constructor)(params, &spec.options)
+            .with_context(|| format!("failed to build align stage: {spec}"))
+    }
+}
+
+fn register<C>(
+    stages: &mut Vec<RegisteredStage<C>>,
+    name: &str,
+    description: &str,
+    constructor: C,
+) {
+    let stage = RegisteredStage {
+        name: name.to_string(),
+        description: description.to_string(),
+        constructor,
+    };
+
+    match stages.iter_mut().find(|s| s.name == name) {
+        Some(existing) => *existing = stage,
+        None => stages.push(stage),
+    }
+}
+
+fn find<'a, C>(
+    stages: &'a [RegisteredStage<C>],
+    kind: &'static str,
+    name: &str,
+) -> Result<&'a RegisteredStage<C>, UnknownStageError> {
+    stages
+        .iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| UnknownStageError {
+            kind,
+            name: name.to_string(),
+            available: stages.iter().map(|s| s.name.clone()).collect(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::{
+        align::structs::Seed,
+        io::{Fasta, SequenceDatabase},
+        search::align_pair,
+        structs::{Hmm, Profile},
+    };
+
+    #[test]
+    fn test_stage_spec_parsing() {
+        let spec: StageSpec = "banded:width=16".parse().unwrap();
+        assert_eq!(spec.name, "banded");
+        assert_eq!(spec.options.get::<usize>("width").unwrap(), Some(16));
+        assert_eq!(spec.to_string(), "banded:width=16");
+
+        let spec: StageSpec = "viterbi".parse().unwrap();
+        assert_eq!(spec, StageSpec::new("viterbi"));
+
+        assert!("".parse::<StageSpec>().is_err());
+        assert!("banded:width".parse::<StageSpec>().is_err());
+    }
+
+    #[test]
+    fn test_registry_lookup() {
+        let registry = StageRegistry::default();
+        let params = SearchParams {
+            target_database_size: Some(1),
+            ..Default::default()
+        };
+
+        for spec in ["default", "full", "banded", "banded:width=8"] {
+            assert!(registry
+                .cloud_stage(&spec.parse().unwrap(), &params)
+                .is_ok());
+        }
+        for spec in ["default", "viterbi"] {
+            assert!(registry
+                .align_stage(&spec.parse().unwrap(), &params)
+                .is_ok());
+        }
+
+        let err = registry
+            .cloud_stage(&StageSpec::new("nope"), &params)
+            .err()
+            .unwrap();
+        assert!(err.downcast_ref::<UnknownStageError>().is_some());
+
+        // unknown and malformed options are rejected
+        assert!(registry
+            .cloud_stage(&"banded:depth=8".parse().unwrap(), &params)
+            .is_err());
+        assert!(registry
+            .cloud_stage(&"banded:width=wide".parse().unwrap(), &params)
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_registered_stages_align() -> anyhow::Result<()> {
+        let hmms = Hmm::from_p7hmm(std::fs::File::open("../fixtures/query.hmm")?)?;
+        let profile = Profile::new(&hmms[0]);
+
+        // the first target that is reported with full DP
+        let (target, optimal) = Fasta::from_path("../fixtures/target.fa")?
+            .iter()
+            .find_map(|target| {
+                align_pair(&profile, &target, None, &SearchParams::default())
+                    .unwrap()
+                    .map(|ali| (target, ali))
+            })
+            .unwrap();
+
+        let params = SearchParams {
+            align_stage: StageSpec::new("viterbi"),
+            ..Default::default()
+        };
+        let viterbi = align_pair(&profile, &target, None, &params)?.unwrap();
+
+        // the score comes from forward, so only the path differs
+        assert_eq!(
+            viterbi.scores.forward_score.value(),
+            optimal.scores.forward_score.value()
+        );
+        let bounds = viterbi.boundaries.as_ref().unwrap();
+        assert!(bounds.target_end <= target.length && bounds.profile_end <= profile.length);
+
+        let optimal_bounds = optimal.boundaries.as_ref().unwrap();
+        let seed = Seed {
+            target_start: optimal_bounds.target_start,
+            target_end: optimal_bounds.target_end,
+            profile_start: optimal_bounds.profile_start,
+            profile_end: optimal_bounds.profile_end,
+            score: 0.0,
+        };
+        let params = SearchParams {
+            cloud_stage: "banded:width=16".parse().unwrap(),
+            ..Default::default()
+        };
+        let banded = align_pair(&profile, &target, Some(&seed), &params)?.unwrap();
+
+        assert!(banded.cell_stats.as_ref().unwrap().fraction < 1.0);
+        assert!(
+            (banded.scores.bit_score - optimal.scores.bit_score)
+                .value()
+                .abs()
+                < 0.5
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::{align::structs::Alignment, io::SequenceDatabase, structs::Profile};
+
+use super::{AlignmentStages, SearchParams, SeedMap, StageRegistry, StageResult};
+
+#[derive(Error, Debug)]
+pub enum SearcherBuildError {
+    #[error("no target database was provided")]
+    MissingTargets,
+    #[error("no seeds were provided")]
+    MissingSeeds,
+}
+
+/// Builds a [`Searcher`] from a target database, seeds, and search parameters.
+#[derive(Default)]
+pub struct SearcherBuilder {
+    targets: Option<Box<dyn SequenceDatabase>>,
+    seeds: Option<SeedMap>,
+    params: SearchParams,
+    registry: StageRegistry,
+}
+
+impl SearcherBuilder {
+    /// The database of target sequences that the profiles are searched against.
+    pub fn targets(mut self, targets: impl SequenceDatabase + 'static) -> Self {
+        self.targets = Some(Box::new(targets));
+        self
+    }
+
+    /// The alignment seeds, keyed by profile name and then by target name.
+    pub fn seeds(mut self, seeds: SeedMap) -> Self {
+        self.seeds = Some(seeds);
+        self
+    }
+
+    /// Replace all of the search parameters.
+    pub fn params(mut self, params: SearchParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// The registry that the stages named by the search parameters are looked up in.
+    pub fn registry(mut self, registry: StageRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Only report alignments with an E-value at or below the threshold.
+    pub fn e_value_threshold(mut self, threshold: f64) -> Self {
+        self.params.e_value_threshold = threshold;
+        self
+    }
+
+    /// The number of comparisons used for E-value calculation.
+    pub fn target_database_size(mut self, size: usize) -> Self {
+        self.params.target_database_size = Some(size);
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<Searcher> {
+        let targets = self.targets.ok_or(SearcherBuildError::MissingTargets)?;
+        let seeds = self.seeds.ok_or(SearcherBuildError::MissingSeeds)?;
+
+        Ok(Searcher {
+            seeds: Arc::new(seeds),
+            stages: AlignmentStages::with_registry(targets, &self.params, &self.registry)?,
+            e_value_threshold: self.params.e_value_threshold,
+        })
+    }
+}
+
+/// Searches profiles against a target database, starting from alignment seeds.
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// use libnail::{io::Fasta, search::Searcher, search::SeedMap, structs::Profile};
+///
+/// let profiles: Vec<Profile> = vec![];
+/// let seeds = SeedMap::new();
+///
+/// let searcher = Searcher::builder()
+///     .targets(Fasta::from_path("target.fa")?)
+///     .seeds(seeds)
+///     .e_value_threshold(1e-3)
+///     .build()?;
+///
+/// for alignment in searcher.search(&profiles) {
+///     println!("{}", alignment.ali_string());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Searcher {
+    seeds: Arc<SeedMap>,
+    stages: AlignmentStages,
+    e_value_threshold: f64,
+}
+
+impl Searcher {
+    pub fn builder() -> SearcherBuilder {
+        SearcherBuilder::default()
+    }
+
+    /// Search one profile, returning its reported alignments sorted by E-value.
+    pub fn search_profile(&self, profile: &Profile) -> Vec<Alignment> {
+        let seeds = match self.seeds.get(&profile.name) {
+            Some(seeds) => seeds,
+            None => return vec![],
+        };
+
+        let mut alignments: Vec<Alignment> = self
+            .stages
+            .run(profile, seeds)
+            .into_iter()
+            .filter_map(|r| match r.align_result {
+                Some(StageResult::Passed { data, .. }) => Some(data),
+                _ => None,
+            })
+            .filter(|a| a.scores.e_value <= self.e_value_threshold)
+            .collect();
+
+        alignments.sort_by(|a, b| {
+            a.scores
+                .e_value
+                .total_cmp(&b.scores.e_value)
+                .then_with(|| a.target_name.cmp(&b.target_name))
+        });
+
+        alignments
+    }
+
+    /// Search each of the profiles in turn, yielding their reported alignments.
+    ///
+    /// The alignments of each profile are sorted by E-value.
+    pub fn search<'a>(&'a self, profiles: &'a [Profile]) -> impl Iterator<Item = Alignment> + 'a {
+        profiles
+            .iter()
+            .flat_map(move |profile| self.search_profile(profile))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs::File;
+
+    use super::*;
+    use crate::{align::structs::Seed, io::Fasta, structs::Hmm};
+
+    #[test]
+    fn test_searcher() -> anyhow::Result<()> {
+        let hmms = Hmm::from_p7hmm(File::open("../fixtures/query.hmm")?)?;
+        let profiles: Vec<Profile> = hmms.iter().map(Profile::new).collect();
+        let targets = Fasta::from_path("../fixtures/target.fa")?;
+
+        let profile = &profiles[0];
+        let target_seeds: HashMap<String, Seed> = targets
+            .iter()
+            .take(4)
+            .map(|target| {
+                let seed = Seed {
+                    target_start: 1,
+                    target_end: target.length,
+                    profile_start: 1,
+                    profile_end: profile.length,
+                    score: 0.0,
+                };
+                (target.name, seed)
+            })
+            .collect();
+
+        let mut seeds = SeedMap::new();
+        seeds.insert(profile.name.clone(), target_seeds);
+
+        let searcher = Searcher::builder()
+            .targets(targets)
+            .seeds(seeds)
+            .params(SearchParams {
+                full_dp: true,
+                ..Default::default()
+            })
+            .build()?;
+
+        let alignments: Vec<Alignment> = searcher.search(&profiles).collect();
+        assert!(!alignments.is_empty());
+        assert!(alignments
+            .windows(2)
+            .all(|w| w[0].scores.e_value <= w[1].scores.e_value));
+
+        let err = Searcher::builder().build().err().unwrap();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(SearcherBuildError::MissingTargets)
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{align::structs::Seed, structs::Profile};
+
+dyn_clone::clone_trait_object!(SeedStage);
+/// The first stage of the pipeline, which finds the targets that a profile is likely to align to.
+///
+/// Returns a map of target names to seeds for the profile,
+/// or `None` if the profile has no seeds.
+pub trait SeedStage: dyn_clone::DynClone + Send + Sync {
+    fn run(&mut self, profile: &Profile) -> Option<&HashMap<String, Seed>>;
+}
+
+pub type SeedMap = HashMap<String, HashMap<String, Seed>>;
+
+#[derive(Default, Clone)]
+pub struct DefaultSeedStage {
+    // shared, since the pipeline is cloned for each worker
+    seeds: Arc<SeedMap>,
+}
+
+impl DefaultSeedStage {
+    pub fn new(seeds: SeedMap) -> Self {
+        DefaultSeedStage {
+            seeds: Arc::new(seeds),
+        }
+    }
+}
+
+impl SeedStage for DefaultSeedStage {
+    fn run(&mut self, profile: &Profile) -> Option<&HashMap<String, Seed>> {
+        self.seeds.get(&profile.name)
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::{BufRead, BufReader, Read};
+use std::io::{BufRead, BufReader, Read, Write};
 
 use crate::alphabet::{AMINO_ALPHABET, AMINO_BACKGROUND_FREQUENCIES};
 use crate::structs::Profile;
@@ -12,7 +12,7 @@ use self::constants::{
     HMM_MATCH_TO_DELETE, HMM_MATCH_TO_INSERT, HMM_MATCH_TO_MATCH,
 };
 
-use super::Sequence;
+use super::{Sequence, SubstitutionMatrix};
 
 pub mod constants {
     // these constants describe indices of transitions
@@ -55,10 +55,10 @@ const P7_BODY_HMM_MODEL_START_FLAG: &str = "HMM";
 const P7_BODY_COMPO_FLAG: &str = "COMPO";
 const P7_BODY_END_FLAG: &str = "//";
 
-const BLOSUM_62_P_OPEN: f32 = 0.02;
-const BLOSUM_62_P_EXTEND: f32 = 0.4;
+pub const BLOSUM_62_P_OPEN: f32 = 0.02;
+pub const BLOSUM_62_P_EXTEND: f32 = 0.4;
 
-const BLOSUM_62_CONDITIONAL_PROB: [[f32; 29]; 29] = [
+pub(crate) const BLOSUM_62_CONDITIONAL_PROB: [[f32; 29]; 29] = [
     [
         0.2782, 0.0150, 0.0280, 0.0481, 0.0208, 0.0688, 0.0120, 0.0425, 0.0428, 0.0694, 0.0171,
         0.0217, 0.0348, 0.0285, 0.0390, 0.0929, 0.0535, 0.0666, 0.0044, 0.0159, 0.0000, 0.0498,
@@ -208,7 +208,7 @@ const BLOSUM_62_CONDITIONAL_PROB: [[f32; 29]; 29] = [
 
 // this static regex is used to find float strings
 lazy_static! {
-    static ref FLOAT_RE: Regex = Regex::new(r"-*\d\.*\d*").unwrap();
+    static ref FLOAT_RE: Regex = Regex::new(r"-?\d+(\.\d*)?([eE][-+]?\d+)?").unwrap();
 }
 
 enum ParserState {
@@ -272,7 +272,7 @@ pub struct Header {
 }
 
 /// This defines statistical scoring parameters for different pipeline stages.
-#[derive(Default)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Stats {
     pub msv_gumble_mu: f32,
     pub msv_gumble_lambda: f32,
@@ -323,6 +323,41 @@ impl Hmm {
     }
 
     pub fn from_blosum_62_and_sequence(seq: &Sequence) -> Result<Self> {
+        Self::from_sequence_with_matrix(
+            seq,
+            &SubstitutionMatrix::Blosum62,
+            BLOSUM_62_P_OPEN,
+            BLOSUM_62_P_EXTEND,
+        )
+    }
+
+    /// Make sure that the gap open and gap extend probabilities
+    /// describe valid transitions for a model built from a sequence.
+    pub fn check_gap_probabilities(p_open: f32, p_extend: f32) -> Result<()> {
+        if !(0.0..0.5).contains(&p_open) {
+            bail!("gap open probability must be in [0, 0.5): {p_open}");
+        }
+
+        if !(0.0..1.0).contains(&p_extend) {
+            bail!("gap extend probability must be in [0, 1): {p_extend}");
+        }
+
+        Ok(())
+    }
+
+    /// Build a model from a single sequence, with match emissions taken from the
+    /// conditional probabilities of a substitution matrix and uniform transitions
+    /// parameterized by the gap open and gap extend probabilities.
+    pub fn from_sequence_with_matrix(
+        seq: &Sequence,
+        matrix: &SubstitutionMatrix,
+        p_open: f32,
+        p_extend: f32,
+    ) -> Result<Self> {
+        Self::check_gap_probabilities(p_open, p_extend)?;
+
+        let conditional_probabilities = matrix.conditional_probabilities()?;
+
         let mut match_probabilities = vec![vec![0.0; 20]; seq.length + 1];
 
         seq.digital_bytes
@@ -330,23 +365,22 @@ impl Hmm {
             .enumerate()
             .skip(1)
             .for_each(|(pos, &residue)| {
-                match_probabilities[pos] =
-                    BLOSUM_62_CONDITIONAL_PROB[residue as usize][0..AMINO_ALPHABET.len()].to_vec();
+                match_probabilities[pos] = conditional_probabilities[residue as usize].clone();
             });
 
         // the transition probabilities are uniform
         let mut transitions = vec![0.0; 8];
-        transitions[HMM_MATCH_TO_MATCH] = 1.0 - 2.0 * BLOSUM_62_P_OPEN;
-        transitions[HMM_MATCH_TO_INSERT] = BLOSUM_62_P_OPEN;
-        transitions[HMM_MATCH_TO_DELETE] = BLOSUM_62_P_OPEN;
-        transitions[HMM_INSERT_TO_MATCH] = 1.0 - BLOSUM_62_P_EXTEND;
-        transitions[HMM_INSERT_TO_INSERT] = BLOSUM_62_P_EXTEND;
-        transitions[HMM_DELETE_TO_MATCH] = 1.0 - BLOSUM_62_P_EXTEND;
-        transitions[HMM_DELETE_TO_DELETE] = BLOSUM_62_P_EXTEND;
+        transitions[HMM_MATCH_TO_MATCH] = 1.0 - 2.0 * p_open;
+        transitions[HMM_MATCH_TO_INSERT] = p_open;
+        transitions[HMM_MATCH_TO_DELETE] = p_open;
+        transitions[HMM_INSERT_TO_MATCH] = 1.0 - p_extend;
+        transitions[HMM_INSERT_TO_INSERT] = p_extend;
+        transitions[HMM_DELETE_TO_MATCH] = 1.0 - p_extend;
+        transitions[HMM_DELETE_TO_DELETE] = p_extend;
 
         let mut transition_probabilities = vec![transitions; seq.length + 1];
         // except for a few modifications to the last position
-        transition_probabilities[seq.length][HMM_MATCH_TO_MATCH] = 1.0 - BLOSUM_62_P_OPEN;
+        transition_probabilities[seq.length][HMM_MATCH_TO_MATCH] = 1.0 - p_open;
         transition_probabilities[seq.length][HMM_MATCH_TO_DELETE] = 0.0;
         transition_probabilities[seq.length][HMM_DELETE_TO_MATCH] = 1.0;
         transition_probabilities[seq.length][HMM_DELETE_TO_DELETE] = 0.0;
@@ -692,6 +726,22 @@ impl Hmm {
                                             .with_context(error_context)?,
                                     );
 
+                                    // the match emission line ends with the
+                                    // MAP, CONS, RF, MM, and CS annotations
+                                    if current_hmm.header.has_reference_annotation {
+                                        current_hmm.model.reference_annotation.push_str(
+                                            get_token_as_str(&tokens, 23)
+                                                .with_context(error_context)?,
+                                        );
+                                    }
+
+                                    if current_hmm.header.has_consensus_structure {
+                                        current_hmm.model.consensus_structure.push_str(
+                                            get_token_as_str(&tokens, 25)
+                                                .with_context(error_context)?,
+                                        );
+                                    }
+
                                     body_parser_state = ModelParserState::InsertEmissions;
                                 } else {
                                     // TODO: error for mismatched line number for node
@@ -723,6 +773,77 @@ impl Hmm {
     }
 }
 
+/// Copy a p7hmm file, replacing the `STATS LOCAL` lines of each model
+/// with the provided stats. Models that are missing `STATS LOCAL`
+/// lines have them inserted immediately before the `HMM` line.
+pub fn rewrite_p7hmm_stats<R: Read, W: Write>(buf: R, stats: &[Stats], mut out: W) -> Result<()> {
+    let write_stats = |out: &mut W, stats: &Stats| -> Result<()> {
+        writeln!(
+            out,
+            "{P7_HEADER_STATS_FLAG} LOCAL {:<8} {:8.4} {:8.5}",
+            P7_HEADER_STATS_MSV_FLAG, stats.msv_gumble_mu, stats.msv_gumble_lambda
+        )?;
+        writeln!(
+            out,
+            "{P7_HEADER_STATS_FLAG} LOCAL {:<8} {:8.4} {:8.5}",
+            P7_HEADER_STATS_VITERBI_FLAG, stats.viterbi_gumble_mu, stats.viterbi_gumble_lambda
+        )?;
+        writeln!(
+            out,
+            "{P7_HEADER_STATS_FLAG} LOCAL {:<8} {:8.4} {:8.5}",
+            P7_HEADER_STATS_FORWARD_FLAG, stats.forward_tau, stats.forward_lambda
+        )?;
+        Ok(())
+    };
+
+    let mut model_idx: usize = 0;
+    // whether we are in a model's header, and haven't yet written its stats
+    let mut needs_stats = true;
+
+    for line in BufReader::new(buf).lines() {
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        let flag = tokens.next().unwrap_or("");
+
+        match flag {
+            P7_HEADER_STATS_FLAG if tokens.next() == Some("LOCAL") => {
+                if needs_stats {
+                    let model_stats = stats
+                        .get(model_idx)
+                        .with_context(|| format!("no stats provided for model {model_idx}"))?;
+                    write_stats(&mut out, model_stats)?;
+                    needs_stats = false;
+                }
+            }
+            P7_BODY_HMM_MODEL_START_FLAG => {
+                if needs_stats {
+                    let model_stats = stats
+                        .get(model_idx)
+                        .with_context(|| format!("no stats provided for model {model_idx}"))?;
+                    write_stats(&mut out, model_stats)?;
+                    needs_stats = false;
+                }
+                writeln!(out, "{line}")?;
+            }
+            P7_BODY_END_FLAG => {
+                model_idx += 1;
+                needs_stats = true;
+                writeln!(out, "{line}")?;
+            }
+            _ => writeln!(out, "{line}")?,
+        }
+    }
+
+    if model_idx != stats.len() {
+        bail!(
+            "provided stats for {} models, but found {model_idx}",
+            stats.len()
+        );
+    }
+
+    Ok(())
+}
+
 fn token_index_check(tokens: &Vec<&str>, idx: usize) -> Result<()> {
     if tokens.len() + 1 < idx {
         return Err(TokenIndexError.into());
@@ -833,4 +954,77 @@ mod tests {
         Profile::new(&hmm);
         Ok(())
     }
+
+    #[test]
+    fn test_token_as_f32() -> anyhow::Result<()> {
+        let tokens = vec!["-11.1245", "0.70253", "2.5e-3", "-1E+2", "7", "*", "x"];
+        assert_eq!(get_token_as_f32(&tokens, 0)?, -11.1245);
+        assert_eq!(get_token_as_f32(&tokens, 1)?, 0.70253);
+        assert_eq!(get_token_as_f32(&tokens, 2)?, 2.5e-3);
+        assert_eq!(get_token_as_f32(&tokens, 3)?, -100.0);
+        assert_eq!(get_token_as_f32(&tokens, 4)?, 7.0);
+        assert_eq!(get_token_as_f32(&tokens, 5)?, 0.0);
+        assert!(get_token_as_f32(&tokens, 6).is_err());
+
+        let hmm = Hmm::from_p7hmm(std::fs::File::open("../fixtures/query.hmm")?)?.remove(0);
+        assert_eq!(hmm.stats.msv_gumble_mu, -11.1245);
+        assert_eq!(hmm.stats.viterbi_gumble_mu, -11.9048);
+        assert_eq!(hmm.stats.forward_tau, -5.1502);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_p7hmm_stats() -> anyhow::Result<()> {
+        let original = std::fs::read_to_string("../fixtures/query.hmm")?;
+        let hmm = Hmm::from_p7hmm(original.as_bytes())?.remove(0);
+
+        // rewriting with the parsed stats should reproduce the file
+        let mut rewritten = vec![];
+        rewrite_p7hmm_stats(original.as_bytes(), &[hmm.stats], &mut rewritten)?;
+        assert_eq!(original, String::from_utf8(rewritten)?);
+
+        let stats = Stats {
+            msv_gumble_mu: -10.5,
+            msv_gumble_lambda: 0.7,
+            viterbi_gumble_mu: -11.25,
+            viterbi_gumble_lambda: 0.7,
+            forward_tau: -4.125,
+            forward_lambda: 0.7,
+        };
+
+        let mut rewritten = vec![];
+        rewrite_p7hmm_stats(original.as_bytes(), &[stats], &mut rewritten)?;
+        let parsed = Hmm::from_p7hmm(rewritten.as_slice())?.remove(0);
+        assert_eq!(parsed.stats.msv_gumble_mu, -10.5);
+        assert_eq!(parsed.stats.viterbi_gumble_mu, -11.25);
+        assert_eq!(parsed.stats.forward_tau, -4.125);
+
+        assert!(rewrite_p7hmm_stats(original.as_bytes(), &[], &mut vec![]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_p7hmm_annotations() -> anyhow::Result<()> {
+        let hmm_file = std::fs::File::open("../fixtures/query.hmm")?;
+        let hmm = Hmm::from_p7hmm(hmm_file)?.remove(0);
+
+        // the fixture has an RF annotation, but no CS annotation
+        assert_eq!(
+            hmm.model.reference_annotation.len(),
+            hmm.header.model_length
+        );
+        assert!(hmm.model.reference_annotation.starts_with("xx"));
+        assert!(hmm.model.consensus_structure.is_empty());
+
+        let profile = Profile::new(&hmm);
+        assert_eq!(
+            profile.reference_annotation_bytes_utf8.map(|a| a.len()),
+            Some(profile.length + 1)
+        );
+        assert!(profile.consensus_structure_bytes_utf8.is_none());
+
+        Ok(())
+    }
 }
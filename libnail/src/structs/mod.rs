@@ -6,3 +6,6 @@ pub use profile::Profile;
 
 pub mod sequence;
 pub use sequence::Sequence;
+
+pub mod substitution_matrix;
+pub use substitution_matrix::SubstitutionMatrix;
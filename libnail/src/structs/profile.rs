@@ -2,12 +2,12 @@ use anyhow::{anyhow, Context};
 use rand::SeedableRng;
 use rand_pcg::Pcg64;
 
-use crate::align::structs::{DpMatrixSparse, RowBounds, Trace};
-use crate::align::{forward, null_one_score};
+use crate::align::structs::Trace;
 use crate::alphabet::{
     AMINO_ALPHABET_WITH_DEGENERATE, AMINO_BACKGROUND_FREQUENCIES, AMINO_INVERSE_MAP,
     AMINO_INVERSE_MAP_LOWER, UTF8_SPACE,
 };
+use crate::calibration::{fit_tau, forward_scores};
 use crate::structs::hmm::constants::{
     HMM_DELETE_TO_DELETE, HMM_DELETE_TO_MATCH, HMM_INSERT_TO_INSERT, HMM_INSERT_TO_MATCH,
     HMM_MATCH_TO_DELETE, HMM_MATCH_TO_INSERT, HMM_MATCH_TO_MATCH,
@@ -20,14 +20,30 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Formatter;
 
-use super::Sequence;
-
 impl AsRef<Profile> for Profile {
     fn as_ref(&self) -> &Profile {
         self
     }
 }
 
+/// Convert a per-position annotation string to bytes that are
+/// buffered with a space so that indexing starts at 1.
+fn annotation_bytes(annotation: &str) -> Vec<u8> {
+    let mut bytes = vec![UTF8_SPACE];
+    bytes.extend_from_slice(annotation.as_bytes());
+    bytes
+}
+
+/// Whether a profile may align to a target more than once (via the J state).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HitMode {
+    /// At most one local alignment per target
+    #[default]
+    UniHit,
+    /// Any number of local alignments per target, as in HMMER
+    MultiHit,
+}
+
 #[derive(Clone, Default)]
 pub struct Profile {
     /// The name of the profile
@@ -52,6 +68,10 @@ pub struct Profile {
     pub expected_j_uses: f32,
     /// The profile's consensus sequence
     pub consensus_sequence_bytes_utf8: Vec<u8>,
+    /// The profile's reference (RF) annotation, if it has one
+    pub reference_annotation_bytes_utf8: Option<Vec<u8>>,
+    /// The profile's consensus structure (CS) annotation, if it has one
+    pub consensus_structure_bytes_utf8: Option<Vec<u8>>,
     /// The sequence alphabet
     pub alphabet: Alphabet,
     pub forward_tau: f32,
@@ -96,9 +116,9 @@ impl Profile {
     pub const MATCH_TO_INSERT_IDX: usize = 6;
     pub const INSERT_TO_INSERT_IDX: usize = 7;
 
-    pub fn relative_entropy(&self) -> f32 {
-        let probs: Vec<Vec<f32>> = self
-            .match_scores
+    /// The match emission probabilities at each model position (1-indexed).
+    fn match_probabilities(&self) -> Vec<Vec<f32>> {
+        self.match_scores
             .iter()
             .map(|scores| {
                 scores
@@ -108,11 +128,29 @@ impl Profile {
                     .map(|(idx, score)| score.exp() * AMINO_BACKGROUND_FREQUENCIES[idx])
                     .collect::<Vec<f32>>()
             })
-            .collect();
+            .collect()
+    }
 
+    pub fn relative_entropy(&self) -> f32 {
+        let probs = self.match_probabilities();
         mean_relative_entropy(&probs[1..], &AMINO_BACKGROUND_FREQUENCIES)
     }
 
+    /// The mean match emission distribution of the profile.
+    pub fn composition(&self) -> Vec<f32> {
+        let probs = self.match_probabilities();
+        let mut composition = vec![0.0f32; Profile::MAX_ALPHABET_SIZE];
+
+        probs[1..].iter().for_each(|p| {
+            composition
+                .iter_mut()
+                .zip(p)
+                .for_each(|(c, p)| *c += p / self.length as f32)
+        });
+
+        composition
+    }
+
     pub fn adjust_mean_relative_entropy(&mut self, target_mre: f32) -> anyhow::Result<f32> {
         const LOWER_PROB_LIMIT: f32 = 1e-3;
         const TARGET_TOLERANCE: f32 = 1e-3;
@@ -308,119 +346,20 @@ impl Profile {
         Ok(current_mre)
     }
 
+    /// Calibrate the Forward exponential tail origin tau, keeping the current lambda.
+    ///
+    /// **NOTE: HMMER uses multi-hit mode to calibrate Tau, but this
+    ///         samples in whatever hit mode the profile is configured
+    ///         in (uni-hit, unless it has been changed). For a full
+    ///         HMMER-style calibration, see `calibration::calibrate()`.
     pub fn calibrate_tau(&mut self, n: usize, target_length: usize, tail_probability: f32) {
-        self.configure_for_target_length(target_length);
-
-        let mut row_bounds = RowBounds::new(target_length);
-        row_bounds.fill_rectangle(1, 1, target_length, self.length);
-        let mut forward_matrix = DpMatrixSparse::new(target_length, self.length, &row_bounds);
-
-        // first we are going to generate n random
-        // sequences drawn from the background
-        // distribution and compute their forward scores
-        let mut scores = vec![0.0; n];
+        // we generate n random sequences drawn from the
+        // background distribution and compute their forward scores
         let mut rng = Pcg64::seed_from_u64(0);
-        (0..n).for_each(|seq_idx| {
-            forward_matrix.reuse(target_length, self.length, &row_bounds);
-            let seq = Sequence::random_amino(target_length, &mut rng);
-
-            // **NOTE: HMMER uses multi-hit mode to calibrate
-            //         Tau, but we are using uni-hit mode
-            let forward_score_nats = forward(self, &seq, &mut forward_matrix, &row_bounds);
-
-            let null_score_nats = null_one_score(target_length);
-
-            scores[seq_idx] = (forward_score_nats - null_score_nats).to_bits().value();
-        });
+        let scores = forward_scores(self, n, target_length, &mut rng);
 
-        /// This is some black magic from a textbook:
-        ///   Statistical Models and Methods for
-        ///   Lifetime Data by Joseph F. Lawless
-        ///   
-        /// From HMMER:
-        ///   Equation 4.1.6 from [Lawless82], pg. 143, and
-        ///   its first derivative with respect to lambda,
-        ///   for finding the ML fit to Gumbel lambda parameter.
-        ///   This equation gives a result of zero for the maximum
-        ///   likelihood lambda.
-        fn lawless416(samples: &[f32], lambda: f32) -> (f32, f32) {
-            // e_sum is the sum of e^(-lambda x_i)
-            let mut e_sum = 0.0f32;
-            // x_sum is the sum of x_i
-            let mut x_sum = 0.0f32;
-            // xe_sum is the sum of x_i * e^(-lambda x_i)
-            let mut xe_sum = 0.0f32;
-            // xe_sum is the sum of x_i^2 * e^(-lambda x_i)
-            let mut xxe_sum = 0.0f32;
-
-            samples.iter().for_each(|x| {
-                e_sum += (-lambda * x).exp();
-                x_sum += x;
-                xe_sum += x * (-lambda * x).exp();
-                xxe_sum += x.powi(2) * (-lambda * x).exp();
-            });
-
-            let fx = (1.0 / lambda) - (x_sum / samples.len() as f32) + (xe_sum / e_sum);
-            let dfx = (xe_sum / e_sum).powi(2) - (xxe_sum / e_sum) - (1.0 / (lambda.powi(2)));
-
-            (fx, dfx)
-        }
-
-        // now make an initial guess at lambda
-        //
-        // from hmmer:
-        //   (Evans/Hastings/Peacock, Statistical Distributions, 2000, p.86)
-        let sum: f32 = scores.iter().sum();
-        let squared_sum: f32 = scores.iter().map(|s| s.powi(2)).sum();
-        let sample_variance: f32 =
-            (squared_sum - sum * sum / scores.len() as f32) / (scores.len() as f32 - 1.0);
-        let mut gumbel_lambda: f32 = std::f32::consts::PI / (6.0 / sample_variance).sqrt();
-
-        // now we do this Newton/Raphson root finding
-        // thing until we have converged on lambda
-        let tolerance: f32 = 1e-5;
-        let mut newton_raphson_success = false;
-        for _ in 0..=100 {
-            let (fx, dfx) = lawless416(&scores, gumbel_lambda);
-            if fx.abs() < tolerance {
-                newton_raphson_success = true;
-                break;
-            }
-            gumbel_lambda -= fx / dfx;
-
-            if gumbel_lambda <= 0.0 {
-                gumbel_lambda = 0.001;
-            }
-        }
-
-        if !newton_raphson_success {
-            panic!("newton/raphson failed");
-        }
-
-        // this is apparently substituting into equation 4.1.5
-        // from Lawless[82] to solve for the mu parameter
-        let e_sum: f32 = scores.iter().map(|s| (-gumbel_lambda * s).exp()).sum();
-        let gumbel_mu = -(e_sum / scores.len() as f32).ln() / gumbel_lambda;
-
-        // now that we've fit the gumbel lambda, we are going to find our tau
-
-        /// Calculates the inverse CDF for a Gumbel distribution
-        /// with parameters <mu> and <lambda>. That is, returns
-        /// the quantile <x> at which the CDF is <p>.
-        fn gumbel_inverse_cdf(p: f32, mu: f32, lambda: f32) -> f32 {
-            mu - ((-p.ln()).ln() / lambda)
-        }
-
-        // basically, there is (maybe?) no good method for fitting an exponential,
-        // so instead we have fit a Gumbel that we are going to use to pick our tau
-        //
-        // from hmmer:
-        //   Explanation of the eqn below: first find the x at which the Gumbel tail
-        //   mass is predicted to be equal to tailp. Then back up from that x
-        //   by log(tailp)/lambda to set the origin of the exponential tail to 1.0
-        //   instead of tailp.
-        self.forward_tau = gumbel_inverse_cdf(1.0 - tail_probability, gumbel_mu, gumbel_lambda)
-            + (tail_probability.ln() / self.forward_lambda);
+        self.forward_tau =
+            fit_tau(&scores, tail_probability, self.forward_lambda).expect("newton/raphson failed");
     }
 
     pub fn new(hmm: &Hmm) -> Self {
@@ -443,6 +382,14 @@ impl Profile {
             expected_j_uses: 0.0,
             // buffered with a space so that indexing starts at 1
             consensus_sequence_bytes_utf8: vec![UTF8_SPACE],
+            reference_annotation_bytes_utf8: hmm
+                .header
+                .has_reference_annotation
+                .then(|| annotation_bytes(&hmm.model.reference_annotation)),
+            consensus_structure_bytes_utf8: hmm
+                .header
+                .has_consensus_structure
+                .then(|| annotation_bytes(&hmm.model.consensus_structure)),
             alphabet: Alphabet::Amino,
             forward_tau: hmm.stats.forward_tau,
             forward_lambda: hmm.stats.forward_lambda,
@@ -692,6 +639,21 @@ impl Profile {
         }
     }
 
+    /// Sets whether the profile may produce multiple local alignments to a target.
+    ///
+    /// This sets the E state transitions and the expected number of J state uses;
+    /// call `configure_for_target_length()` afterwards to update the N, J, C transitions.
+    pub fn configure_hit_mode(&mut self, hit_mode: HitMode) {
+        let (loop_score, move_score, expected_j_uses) = match hit_mode {
+            HitMode::UniHit => (-f32::INFINITY, 0.0, 0.0),
+            HitMode::MultiHit => (0.5f32.ln(), 0.5f32.ln(), 1.0),
+        };
+
+        self.special_transitions[Profile::SPECIAL_E_IDX][Profile::SPECIAL_LOOP_IDX] = loop_score;
+        self.special_transitions[Profile::SPECIAL_E_IDX][Profile::SPECIAL_MOVE_IDX] = move_score;
+        self.expected_j_uses = expected_j_uses;
+    }
+
     /// Sets the length of the current target sequence to which the profile will be aligned.
     ///
     /// This also adjusts the loop and move transition scores for the special states N, J, C.
@@ -1,14 +1,19 @@
 use rand::Rng;
 use rand_pcg::Lcg128Xsl64;
+#[cfg(feature = "fs")]
 use seq_io::fasta::{Reader, Record};
 use std::fmt::{Debug, Display, Formatter};
+#[cfg(feature = "fs")]
 use std::path::Path;
 
+#[cfg(feature = "fs")]
+use crate::alphabet::UTF8_SPACE;
 use crate::alphabet::{
-    AMINO_BACKGROUND_FREQUENCIES, AMINO_INVERSE_MAP, AMINO_SENTINEL, UTF8_SPACE,
-    UTF8_TO_DIGITAL_AMINO,
+    AMINO_BACKGROUND_FREQUENCIES, AMINO_INVERSE_MAP, AMINO_SENTINEL, UTF8_TO_DIGITAL_AMINO,
 };
-use anyhow::{Context, Result};
+#[cfg(feature = "fs")]
+use anyhow::Context;
+use anyhow::Result;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -24,7 +29,7 @@ pub struct UnknownDigitalSequenceByteError {
 }
 
 /// This holds the both the "digital" data and string data of a biological sequence.
-#[derive(Default, PartialEq)]
+#[derive(Clone, Default, PartialEq)]
 pub struct Sequence {
     /// The name of the sequence
     pub name: String,
@@ -43,13 +48,23 @@ pub struct Sequence {
 
 impl Sequence {
     pub fn random_amino(length: usize, rng: &mut Lcg128Xsl64) -> Self {
+        Self::random_amino_with_frequencies(length, &AMINO_BACKGROUND_FREQUENCIES, rng)
+    }
+
+    /// Generate a random sequence with residues drawn i.i.d. from
+    /// `frequencies`, which is indexed by digital amino residue.
+    pub fn random_amino_with_frequencies(
+        length: usize,
+        frequencies: &[f32],
+        rng: &mut Lcg128Xsl64,
+    ) -> Self {
         let mut digital_bytes = (0..=length)
             .map(|_| {
                 let roll: f32 = rng.gen();
 
                 let mut sum = 0.0f32;
                 let mut choice = 0u8;
-                for (residue_idx, p) in AMINO_BACKGROUND_FREQUENCIES.iter().enumerate() {
+                for (residue_idx, p) in frequencies.iter().enumerate() {
                     sum += p;
                     if roll <= sum {
                         choice = residue_idx as u8;
@@ -80,6 +95,29 @@ impl Sequence {
         }
     }
 
+    /// Produce a copy of the sequence with its residues randomly permuted.
+    pub fn shuffled(&self, rng: &mut Lcg128Xsl64) -> Self {
+        let mut shuffled = self.clone();
+
+        // Fisher-Yates, skipping the padding byte at index 0
+        for idx in (2..=self.length).rev() {
+            let swap_idx = rng.gen_range(1..=idx);
+            shuffled.digital_bytes.swap(idx, swap_idx);
+            shuffled.utf8_bytes.swap(idx, swap_idx);
+        }
+
+        shuffled
+    }
+
+    /// Produce a copy of the sequence with its residues in reverse order.
+    pub fn reversed(&self) -> Self {
+        let mut reversed = self.clone();
+        reversed.digital_bytes[1..].reverse();
+        reversed.utf8_bytes[1..].reverse();
+        reversed
+    }
+
+    #[cfg(feature = "fs")]
     pub fn amino_from_fasta<P: AsRef<Path>>(path: P) -> Result<Vec<Self>> {
         let mut seqs: Vec<Self> = vec![];
 
@@ -264,4 +302,26 @@ mod tests {
             });
         Ok(())
     }
+
+    #[test]
+    fn test_shuffled_and_reversed() -> anyhow::Result<()> {
+        let seq = Sequence::from_utf8(b"ACDEFGHIKLMNPQRSTVWYACDKK")?;
+        let mut rng = Pcg64::seed_from_u64(0);
+
+        let shuffled = seq.shuffled(&mut rng);
+        assert_eq!(seq.length, shuffled.length);
+        assert_ne!(seq.digital_bytes, shuffled.digital_bytes);
+
+        let mut a = seq.digital_bytes.clone();
+        let mut b = shuffled.digital_bytes.clone();
+        a.sort();
+        b.sort();
+        assert_eq!(a, b);
+
+        let reversed = seq.reversed();
+        assert_eq!(&reversed.utf8_bytes[1..], b"KKDCAYWVTSRQPNMLKIHGFEDCA");
+        assert_eq!(reversed.reversed(), seq);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,569 @@
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::alphabet::{AMINO_ALPHABET, AMINO_BACKGROUND_FREQUENCIES, UTF8_TO_DIGITAL_AMINO};
+use crate::structs::hmm::BLOSUM_62_CONDITIONAL_PROB;
+
+const BLOSUM_45: &str = "
+   A  R  N  D  C  Q  E  G  H  I  L  K  M  F  P  S  T  W  Y  V
+A  5 -2 -1 -2 -1 -1 -1  0 -2 -1 -1 -1 -1 -2 -1  1  0 -2 -2  0
+R -2  7  0 -1 -3  1  0 -2  0 -3 -2  3 -1 -2 -2 -1 -1 -2 -1 -2
+N -1  0  6  2 -2  0  0  0  1 -2 -3  0 -2 -2 -2  1  0 -4 -2 -3
+D -2 -1  2  7 -3  0  2 -1  0 -4 -3  0 -3 -4 -1  0 -1 -4 -2 -3
+C -1 -3 -2 -3 12 -3 -3 -3 -3 -3 -2 -3 -2 -2 -4 -1 -1 -5 -3 -1
+Q -1  1  0  0 -3  6  2 -2  1 -2 -2  1  0 -4 -1  0 -1 -2 -1 -3
+E -1  0  0  2 -3  2  6 -2  0 -3 -2  1 -2 -3  0  0 -1 -3 -2 -3
+G  0 -2  0 -1 -3 -2 -2  7 -2 -4 -3 -2 -2 -3 -2  0 -2 -2 -3 -3
+H -2  0  1  0 -3  1  0 -2 10 -3 -2 -1  0 -2 -2 -1 -2 -3  2 -3
+I -1 -3 -2 -4 -3 -2 -3 -4 -3  5  2 -3  2  0 -2 -2 -1 -2  0  3
+L -1 -2 -3 -3 -2 -2 -2 -3 -2  2  5 -3  2  1 -3 -3 -1 -2  0  1
+K -1  3  0  0 -3  1  1 -2 -1 -3 -3  5 -1 -3 -1 -1 -1 -2 -1 -2
+M -1 -1 -2 -3 -2  0 -2 -2  0  2  2 -1  6  0 -2 -2 -1 -2  0  1
+F -2 -2 -2 -4 -2 -4 -3 -3 -2  0  1 -3  0  8 -3 -2 -1  1  3  0
+P -1 -2 -2 -1 -4 -1  0 -2 -2 -2 -3 -1 -2 -3  9 -1 -1 -3 -3 -3
+S  1 -1  1  0 -1  0  0  0 -1 -2 -3 -1 -2 -2 -1  4  2 -4 -2 -1
+T  0 -1  0 -1 -1 -1 -1 -2 -2 -1 -1 -1 -1 -1 -1  2  5 -3 -1  0
+W -2 -2 -4 -4 -5 -2 -3 -2 -3 -2 -2 -2 -2  1 -3 -4 -3 15  3 -3
+Y -2 -1 -2 -2 -3 -1 -2 -3  2  0  0 -1  0  3 -3 -2 -1  3  8 -1
+V  0 -2 -3 -3 -1 -3 -3 -3 -3  3  1 -2  1  0 -3 -1  0 -3 -1  5
+";
+
+const BLOSUM_50: &str = "
+   A  R  N  D  C  Q  E  G  H  I  L  K  M  F  P  S  T  W  Y  V
+A  5 -2 -1 -2 -1 -1 -1  0 -2 -1 -2 -1 -1 -3 -1  1  0 -3 -2  0
+R -2  7 -1 -2 -4  1  0 -3  0 -4 -3  3 -2 -3 -3 -1 -1 -3 -1 -3
+N -1 -1  7  2 -2  0  0  0  1 -3 -4  0 -2 -4 -2  1  0 -4 -2 -3
+D -2 -2  2  8 -4  0  2 -1 -1 -4 -4 -1 -4 -5 -1  0 -1 -5 -3 -4
+C -1 -4 -2 -4 13 -3 -3 -3 -3 -2 -2 -3 -2 -2 -4 -1 -1 -5 -3 -1
+Q -1  1  0  0 -3  7  2 -2  1 -3 -2  2  0 -4 -1  0 -1 -1 -1 -3
+E -1  0  0  2 -3  2  6 -3  0 -4 -3  1 -2 -3 -1 -1 -1 -3 -2 -3
+G  0 -3  0 -1 -3 -2 -3  8 -2 -4 -4 -2 -3 -4 -2  0 -2 -3 -3 -4
+H -2  0  1 -1 -3  1  0 -2 10 -4 -3  0 -1 -1 -2 -1 -2 -3  2 -4
+I -1 -4 -3 -4 -2 -3 -4 -4 -4  5  2 -3  2  0 -3 -3 -1 -3 -1  4
+L -2 -3 -4 -4 -2 -2 -3 -4 -3  2  5 -3  3  1 -4 -3 -1 -2 -1  1
+K -1  3  0 -1 -3  2  1 -2  0 -3 -3  6 -2 -4 -1  0 -1 -3 -2 -3
+M -1 -2 -2 -4 -2  0 -2 -3 -1  2  3 -2  7  0 -3 -2 -1 -1  0  1
+F -3 -3 -4 -5 -2 -4 -3 -4 -1  0  1 -4  0  8 -4 -3 -2  1  4 -1
+P -1 -3 -2 -1 -4 -1 -1 -2 -2 -3 -4 -1 -3 -4 10 -1 -1 -4 -3 -3
+S  1 -1  1  0 -1  0 -1  0 -1 -3 -3  0 -2 -3 -1  5  2 -4 -2 -2
+T  0 -1  0 -1 -1 -1 -1 -2 -2 -1 -1 -1 -1 -2 -1  2  5 -3 -2  0
+W -3 -3 -4 -5 -5 -1 -3 -3 -3 -3 -2 -3 -1  1 -4 -4 -3 15  2 -3
+Y -2 -1 -2 -3 -3 -1 -2 -3  2 -1 -1 -2  0  4 -3 -2 -2  2  8 -1
+V  0 -3 -3 -4 -1 -3 -3 -4 -4  4  1 -3  1 -1 -3 -2  0 -3 -1  5
+";
+
+const BLOSUM_62: &str = "
+   A  R  N  D  C  Q  E  G  H  I  L  K  M  F  P  S  T  W  Y  V
+A  4 -1 -2 -2  0 -1 -1  0 -2 -1 -1 -1 -1 -2 -1  1  0 -3 -2  0
+R -1  5  0 -2 -3  1  0 -2  0 -3 -2  2 -1 -3 -2 -1 -1 -3 -2 -3
+N -2  0  6  1 -3  0  0  0  1 -3 -3  0 -2 -3 -2  1  0 -4 -2 -3
+D -2 -2  1  6 -3  0  2 -1 -1 -3 -4 -1 -3 -3 -1  0 -1 -4 -3 -3
+C  0 -3 -3 -3  9 -3 -4 -3 -3 -1 -1 -3 -1 -2 -3 -1 -1 -2 -2 -1
+Q -1  1  0  0 -3  5  2 -2  0 -3 -2  1  0 -3 -1  0 -1 -2 -1 -2
+E -1  0  0  2 -4  2  5 -2  0 -3 -3  1 -2 -3 -1  0 -1 -3 -2 -2
+G  0 -2  0 -1 -3 -2 -2  6 -2 -4 -4 -2 -3 -3 -2  0 -2 -2 -3 -3
+H -2  0  1 -1 -3  0  0 -2  8 -3 -3 -1 -2 -1 -2 -1 -2 -2  2 -3
+I -1 -3 -3 -3 -1 -3 -3 -4 -3  4  2 -3  1  0 -3 -2 -1 -3 -1  3
+L -1 -2 -3 -4 -1 -2 -3 -4 -3  2  4 -2  2  0 -3 -2 -1 -2 -1  1
+K -1  2  0 -1 -3  1  1 -2 -1 -3 -2  5 -1 -3 -1  0 -1 -3 -2 -2
+M -1 -1 -2 -3 -1  0 -2 -3 -2  1  2 -1  5  0 -2 -1 -1 -1 -1  1
+F -2 -3 -3 -3 -2 -3 -3 -3 -1  0  0 -3  0  6 -4 -2 -2  1  3 -1
+P -1 -2 -2 -1 -3 -1 -1 -2 -2 -3 -3 -1 -2 -4  7 -1 -1 -4 -3 -2
+S  1 -1  1  0 -1  0  0  0 -1 -2 -2  0 -1 -2 -1  4  1 -3 -2 -2
+T  0 -1  0 -1 -1 -1 -1 -2 -2 -1 -1 -1 -1 -2 -1  1  5 -2 -2  0
+W -3 -3 -4 -4 -2 -2 -3 -2 -2 -3 -2 -3 -1  1 -4 -3 -2 11  2 -3
+Y -2 -2 -2 -3 -2 -1 -2 -3  2 -1 -1 -2 -1  3 -3 -2 -2  2  7 -1
+V  0 -3 -3 -3 -1 -2 -2 -3 -3  3  1 -2  1 -1 -2 -2  0 -3 -1  4
+";
+
+const BLOSUM_80: &str = "
+   A  R  N  D  C  Q  E  G  H  I  L  K  M  F  P  S  T  W  Y  V
+A  5 -2 -2 -2 -1 -1 -1  0 -2 -2 -2 -1 -1 -3 -1  1  0 -3 -2  0
+R -2  6 -1 -2 -4  1 -1 -3  0 -3 -3  2 -2 -4 -2 -1 -1 -4 -3 -3
+N -2 -1  6  1 -3  0 -1 -1  0 -4 -4  0 -3 -4 -3  0  0 -4 -3 -4
+D -2 -2  1  6 -4 -1  1 -2 -2 -4 -5 -1 -4 -4 -2 -1 -1 -6 -4 -4
+C -1 -4 -3 -4  9 -4 -5 -4 -4 -2 -2 -4 -2 -3 -4 -2 -1 -3 -3 -1
+Q -1  1  0 -1 -4  6  2 -2  1 -3 -3  1  0 -4 -2  0 -1 -3 -2 -3
+E -1 -1 -1  1 -5  2  6 -3  0 -4 -4  1 -2 -4 -2  0 -1 -4 -3 -3
+G  0 -3 -1 -2 -4 -2 -3  6 -3 -5 -4 -2 -4 -4 -3 -1 -2 -4 -4 -4
+H -2  0  0 -2 -4  1  0 -3  8 -4 -3 -1 -2 -2 -3 -1 -2 -3  2 -4
+I -2 -3 -4 -4 -2 -3 -4 -5 -4  5  1 -3  1 -1 -4 -3 -1 -3 -2  3
+L -2 -3 -4 -5 -2 -3 -4 -4 -3  1  4 -3  2  0 -3 -3 -2 -2 -2  1
+K -1  2  0 -1 -4  1  1 -2 -1 -3 -3  5 -2 -4 -1 -1 -1 -4 -3 -3
+M -1 -2 -3 -4 -2  0 -2 -4 -2  1  2 -2  6  0 -3 -2 -1 -2 -2  1
+F -3 -4 -4 -4 -3 -4 -4 -4 -2 -1  0 -4  0  6 -4 -3 -2  0  3 -1
+P -1 -2 -3 -2 -4 -2 -2 -3 -3 -4 -3 -1 -3 -4  8 -1 -2 -5 -4 -3
+S  1 -1  0 -1 -2  0  0 -1 -1 -3 -3 -1 -2 -3 -1  5  1 -4 -2 -2
+T  0 -1  0 -1 -1 -1 -1 -2 -2 -1 -2 -1 -1 -2 -2  1  5 -4 -2  0
+W -3 -4 -4 -6 -3 -3 -4 -4 -3 -3 -2 -4 -2  0 -5 -4 -4 11  2 -3
+Y -2 -3 -3 -4 -3 -2 -3 -4  2 -2 -2 -3 -2  3 -4 -2 -2  2  7 -2
+V  0 -3 -4 -4 -1 -3 -3 -4 -4  3  1 -3  1 -1 -3 -2  0 -3 -2  4
+";
+
+const PAM_30: &str = "
+    A   R   N   D   C   Q   E   G   H   I   L   K   M   F   P   S   T   W   Y   V
+A   6  -7  -4  -3  -6  -4  -2  -2  -7  -5  -6  -7  -5  -8  -2   0  -1 -13  -8  -2
+R  -7   8  -6 -10  -8  -2  -9  -9  -2  -5  -8   0  -4  -9  -4  -3  -6  -2 -10  -8
+N  -4  -6   8   2 -11  -3  -2  -3   0  -5  -7  -1  -9  -9  -6   0  -2  -8  -4  -8
+D  -3 -10   2   8 -14  -2   2  -3  -4  -7 -12  -4 -11 -15  -8  -4  -5 -15 -11  -8
+C  -6  -8 -11 -14  10 -14 -14  -9  -7  -6 -15 -14 -13 -13  -8  -3  -8 -15  -4  -6
+Q  -4  -2  -3  -2 -14   8   1  -7   1  -8  -5  -3  -4 -13  -3  -5  -5 -13 -12  -7
+E  -2  -9  -2   2 -14   1   8  -4  -5  -5  -9  -4  -7 -14  -5  -4  -6 -17  -8  -6
+G  -2  -9  -3  -3  -9  -7  -4   6  -9 -11 -10  -7  -8  -9  -6  -2  -6 -15 -14  -5
+H  -7  -2   0  -4  -7   1  -5  -9   9  -9  -6  -6 -10  -6  -4  -6  -7  -7  -3  -6
+I  -5  -5  -5  -7  -6  -8  -5 -11  -9   8  -1  -6  -1  -2  -8  -7  -2 -14  -6   2
+L  -6  -8  -7 -12 -15  -5  -9 -10  -6  -1   7  -8   1  -3  -7  -8  -7  -6  -7  -2
+K  -7   0  -1  -4 -14  -3  -4  -7  -6  -6  -8   7  -2 -14  -6  -4  -3 -12  -9  -9
+M  -5  -4  -9 -11 -13  -4  -7  -8 -10  -1   1  -2  11  -4  -8  -5  -4 -13 -11  -1
+F  -8  -9  -9 -15 -13 -13 -14  -9  -6  -2  -3 -14  -4   9 -10  -6  -9  -4   2  -8
+P  -2  -4  -6  -8  -8  -3  -5  -6  -4  -8  -7  -6  -8 -10   8  -2  -4 -14 -13  -6
+S   0  -3   0  -4  -3  -5  -4  -2  -6  -7  -8  -4  -5  -6  -2   6   0  -5  -7  -6
+T  -1  -6  -2  -5  -8  -5  -6  -6  -7  -2  -7  -3  -4  -9  -4   0   7 -13  -6  -3
+W -13  -2  -8 -15 -15 -13 -17 -15  -7 -14  -6 -12 -13  -4 -14  -5 -13  13  -5 -15
+Y  -8 -10  -4 -11  -4 -12  -8 -14  -3  -6  -7  -9 -11   2 -13  -7  -6  -5  10  -7
+V  -2  -8  -8  -8  -6  -7  -6  -5  -6   2  -2  -9  -1  -8  -6  -6  -3 -15  -7   7
+";
+
+const PAM_70: &str = "
+    A   R   N   D   C   Q   E   G   H   I   L   K   M   F   P   S   T   W   Y   V
+A   5  -4  -2  -1  -4  -2  -1   0  -4  -2  -4  -4  -3  -6   0   1   1  -9  -5  -1
+R  -4   8  -3  -6  -5   0  -5  -6   0  -3  -6   2  -2  -7  -2  -1  -4   0  -7  -5
+N  -2  -3   6   3  -7  -1   0  -1   1  -3  -5   0  -5  -6  -3   1   0  -6  -3  -5
+D  -1  -6   3   6  -9   0   3  -1  -1  -5  -8  -2  -7 -10  -4  -1  -2 -10  -7  -5
+C  -4  -5  -7  -9   9  -9  -9  -6  -5  -4 -10  -9  -9  -8  -5  -1  -5 -11  -2  -4
+Q  -2   0  -1   0  -9   7   2  -4   2  -5  -3  -1  -2  -9  -1  -3  -3  -8  -8  -4
+E  -1  -5   0   3  -9   2   6  -2  -2  -4  -6  -2  -4  -9  -3  -2  -3 -11  -6  -4
+G   0  -6  -1  -1  -6  -4  -2   6  -6  -6  -7  -5  -6  -7  -3   0  -3 -10  -9  -3
+H  -4   0   1  -1  -5   2  -2  -6   8  -6  -4  -3  -6  -4  -2  -3  -4  -5  -1  -4
+I  -2  -3  -3  -5  -4  -5  -4  -6  -6   7   1  -4   1   0  -5  -4  -1  -9  -4   3
+L  -4  -6  -5  -8 -10  -3  -6  -7  -4   1   6  -5   2  -1  -5  -6  -4  -4  -4   0
+K  -4   2   0  -2  -9  -1  -2  -5  -3  -4  -5   6   0  -9  -4  -2  -1  -7  -7  -6
+M  -3  -2  -5  -7  -9  -2  -4  -6  -6   1   2   0  10  -2  -5  -3  -2  -8  -7   0
+F  -6  -7  -6 -10  -8  -9  -9  -7  -4   0  -1  -9  -2   8  -7  -4  -6  -2   4  -5
+P   0  -2  -3  -4  -5  -1  -3  -3  -2  -5  -5  -4  -5  -7   7   0  -2  -9  -9  -3
+S   1  -1   1  -1  -1  -3  -2   0  -3  -4  -6  -2  -3  -4   0   5   2  -3  -5  -3
+T   1  -4   0  -2  -5  -3  -3  -3  -4  -1  -4  -1  -2  -6  -2   2   6  -8  -4  -1
+W  -9   0  -6 -10 -11  -8 -11 -10  -5  -9  -4  -7  -8  -2  -9  -3  -8  13  -3 -10
+Y  -5  -7  -3  -7  -2  -8  -6  -9  -1  -4  -4  -7  -7   4  -9  -5  -4  -3   9  -5
+V  -1  -5  -5  -5  -4  -4  -4  -3  -4   3   0  -6   0  -5  -3  -3  -1 -10  -5   6
+";
+
+const PAM_250: &str = "
+   A  R  N  D  C  Q  E  G  H  I  L  K  M  F  P  S  T  W  Y  V
+A  2 -2  0  0 -2  0  0  1 -1 -1 -2 -1 -1 -3  1  1  1 -6 -3  0
+R -2  6  0 -1 -4  1 -1 -3  2 -2 -3  3  0 -4  0  0 -1  2 -4 -2
+N  0  0  2  2 -4  1  1  0  2 -2 -3  1 -2 -3  0  1  0 -4 -2 -2
+D  0 -1  2  4 -5  2  3  1  1 -2 -4  0 -3 -6 -1  0  0 -7 -4 -2
+C -2 -4 -4 -5 12 -5 -5 -3 -3 -2 -6 -5 -5 -4 -3  0 -2 -8  0 -2
+Q  0  1  1  2 -5  4  2 -1  3 -2 -2  1 -1 -5  0 -1 -1 -5 -4 -2
+E  0 -1  1  3 -5  2  4  0  1 -2 -3  0 -2 -5 -1  0  0 -7 -4 -2
+G  1 -3  0  1 -3 -1  0  5 -2 -3 -4 -2 -3 -5  0  1  0 -7 -5 -1
+H -1  2  2  1 -3  3  1 -2  6 -2 -2  0 -2 -2  0 -1 -1 -3  0 -2
+I -1 -2 -2 -2 -2 -2 -2 -3 -2  5  2 -2  2  1 -2 -1  0 -5 -1  4
+L -2 -3 -3 -4 -6 -2 -3 -4 -2  2  6 -3  4  2 -3 -3 -2 -2 -1  2
+K -1  3  1  0 -5  1  0 -2  0 -2 -3  5  0 -5 -1  0  0 -3 -4 -2
+M -1  0 -2 -3 -5 -1 -2 -3 -2  2  4  0  6  0 -2 -2 -1 -4 -2  2
+F -3 -4 -3 -6 -4 -5 -5 -5 -2  1  2 -5  0  9 -5 -3 -3  0  7 -1
+P  1  0  0 -1 -3  0 -1  0  0 -2 -3 -1 -2 -5  6  1  0 -6 -5 -1
+S  1  0  1  0  0 -1  0  1 -1 -1 -3  0 -2 -3  1  2  1 -2 -3 -1
+T  1 -1  0  0 -2 -1  0  0 -1  0 -2  0 -1 -3  0  1  3 -5 -3  0
+W -6  2 -4 -7 -8 -5 -7 -7 -3 -5 -2 -3 -4  0 -6 -2 -5 17  0 -6
+Y -3 -4 -2 -4  0 -4 -4 -5  0 -1 -1 -4 -2  7 -5 -3 -3  0 10 -2
+V  0 -2 -2 -2 -2 -2 -2 -1 -2  4  2 -2  2 -1 -1 -1  0 -6 -2  4
+";
+
+/// The canonical residues that each degenerate digital residue may represent.
+///
+/// Selenocysteine (U) and pyrrolysine (O) are treated
+/// as cysteine (C) and lysine (K) respectively.
+fn degenerate_members(residue: usize) -> Option<&'static [usize]> {
+    // A  C  D  E  F  G  H  I  K  L  M  N  P  Q  R  S  T  V  W  Y
+    // 0  1  2  3  4  5  6  7  8  9 10 11 12 13 14 15 16 17 18 19
+    match residue {
+        // O
+        20 => Some(&[8]),
+        // U
+        21 => Some(&[1]),
+        // X
+        22 => Some(&[
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+        ]),
+        // B
+        23 => Some(&[2, 11]),
+        // Z
+        24 => Some(&[3, 13]),
+        // J
+        25 => Some(&[7, 9]),
+        _ => None,
+    }
+}
+
+/// An amino acid substitution score matrix.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SubstitutionMatrix {
+    Blosum45,
+    Blosum50,
+    Blosum62,
+    Blosum80,
+    Pam30,
+    Pam70,
+    Pam250,
+    /// A matrix read from an NCBI-format file
+    Custom {
+        name: String,
+        /// The scores, indexed by digital residue
+        scores: Box<[[f32; 20]; 20]>,
+    },
+}
+
+impl std::str::FromStr for SubstitutionMatrix {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "BLOSUM45" => Ok(Self::Blosum45),
+            "BLOSUM50" => Ok(Self::Blosum50),
+            "BLOSUM62" => Ok(Self::Blosum62),
+            "BLOSUM80" => Ok(Self::Blosum80),
+            "PAM30" => Ok(Self::Pam30),
+            "PAM70" => Ok(Self::Pam70),
+            "PAM250" => Ok(Self::Pam250),
+            _ => bail!("unknown substitution matrix: {name}"),
+        }
+    }
+}
+
+impl SubstitutionMatrix {
+    /// Parse a substitution matrix in the NCBI format.
+    ///
+    /// Lines starting with '#' are ignored, the first remaining line names the
+    /// columns, and every following line names a row and lists its scores. Rows
+    /// and columns for anything other than the 20 canonical residues are ignored.
+    pub fn from_ncbi<R: Read>(name: &str, buf: R) -> Result<Self> {
+        let mut lines = BufReader::new(buf)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+
+        let header = lines.next().context("substitution matrix has no header")?;
+
+        let columns: Vec<Option<usize>> =
+            header.split_whitespace().map(canonical_residue).collect();
+
+        let mut scores = [[0.0f32; 20]; 20];
+        let mut seen = [[false; 20]; 20];
+
+        for line in lines {
+            let mut tokens = line.split_whitespace();
+
+            let row = match tokens.next().and_then(canonical_residue) {
+                Some(row) => row,
+                None => continue,
+            };
+
+            for (column, token) in columns.iter().zip(tokens) {
+                if let Some(column) = column {
+                    scores[row][*column] = token
+                        .parse::<f32>()
+                        .with_context(|| format!("invalid substitution score: {token}"))?;
+                    seen[row][*column] = true;
+                }
+            }
+        }
+
+        if seen.iter().flatten().any(|s| !s) {
+            bail!("substitution matrix is missing scores for some canonical residues");
+        }
+
+        Ok(Self::Custom {
+            name: name.to_string(),
+            scores: Box::new(scores),
+        })
+    }
+
+    /// Read an NCBI-format substitution matrix file.
+    #[cfg(feature = "fs")]
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| {
+            format!(
+                "failed to open substitution matrix: {}",
+                path.to_string_lossy()
+            )
+        })?;
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Self::from_ncbi(&name, file)
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Blosum45 => "BLOSUM45",
+            Self::Blosum50 => "BLOSUM50",
+            Self::Blosum62 => "BLOSUM62",
+            Self::Blosum80 => "BLOSUM80",
+            Self::Pam30 => "PAM30",
+            Self::Pam70 => "PAM70",
+            Self::Pam250 => "PAM250",
+            Self::Custom { name, .. } => name,
+        }
+    }
+
+    /// The scores, indexed by digital residue.
+    pub fn scores(&self) -> [[f32; 20]; 20] {
+        let text = match self {
+            Self::Blosum45 => BLOSUM_45,
+            Self::Blosum50 => BLOSUM_50,
+            Self::Blosum62 => BLOSUM_62,
+            Self::Blosum80 => BLOSUM_80,
+            Self::Pam30 => PAM_30,
+            Self::Pam70 => PAM_70,
+            Self::Pam250 => PAM_250,
+            Self::Custom { scores, .. } => return **scores,
+        };
+
+        match Self::from_ncbi(self.name(), text.as_bytes()) {
+            Ok(Self::Custom { scores, .. }) => *scores,
+            _ => unreachable!("failed to parse built-in substitution matrix"),
+        }
+    }
+
+    /// Compute the scale λ of the matrix, which is the positive solution to:
+    ///
+    ///   sum_ab f_a * f_b * exp(λ * s_ab) = 1
+    ///
+    /// where f are the background frequencies. The scores can then
+    /// be interpreted as log-odds ratios: s_ab = log(p_ab / f_a f_b) / λ
+    pub fn lambda(&self) -> Result<f32> {
+        let scores = self.scores();
+        let background = AMINO_BACKGROUND_FREQUENCIES;
+
+        let expected_score = |lambda: f64| -> f64 {
+            (0..AMINO_ALPHABET.len())
+                .flat_map(|a| (0..AMINO_ALPHABET.len()).map(move |b| (a, b)))
+                .map(|(a, b)| {
+                    background[a] as f64
+                        * background[b] as f64
+                        * (lambda * scores[a][b] as f64).exp()
+                })
+                .sum::<f64>()
+                - 1.0
+        };
+
+        let mean_score: f64 = (0..AMINO_ALPHABET.len())
+            .flat_map(|a| (0..AMINO_ALPHABET.len()).map(move |b| (a, b)))
+            .map(|(a, b)| background[a] as f64 * background[b] as f64 * scores[a][b] as f64)
+            .sum();
+
+        let max_score = scores.iter().flatten().cloned().fold(f32::MIN, f32::max);
+
+        if mean_score >= 0.0 || max_score <= 0.0 {
+            bail!(
+                "substitution matrix {} must have a negative expected score and a positive score",
+                self.name()
+            );
+        }
+
+        // the function is convex with a root at λ = 0, so
+        // we step out until we bracket the positive root
+        let mut lo = 1e-4;
+        while expected_score(2.0 * lo) < 0.0 {
+            lo *= 2.0;
+        }
+        let mut hi = 2.0 * lo;
+
+        for _ in 0..100 {
+            let mid = (lo + hi) / 2.0;
+            if expected_score(mid) < 0.0 {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        Ok(((lo + hi) / 2.0) as f32)
+    }
+
+    /// Compute the conditional probabilities P(b | a) of observing
+    /// each canonical residue b, given a (possibly degenerate) residue a.
+    ///
+    /// The result is indexed by digital residue; rows for gap and
+    /// other non-residue characters are all zero.
+    pub fn conditional_probabilities(&self) -> Result<Vec<Vec<f32>>> {
+        let mut conditionals = vec![vec![0.0f32; AMINO_ALPHABET.len()]; 29];
+
+        match self {
+            // nail has always used HMMER's BLOSUM62 probabilities, so we keep
+            // them to avoid perturbing existing results; HMMER's degenerate
+            // rows are in a different order, so those are derived below
+            Self::Blosum62 => {
+                (0..AMINO_ALPHABET.len()).for_each(|a| {
+                    conditionals[a] =
+                        BLOSUM_62_CONDITIONAL_PROB[a][0..AMINO_ALPHABET.len()].to_vec()
+                });
+            }
+            _ => {
+                let scores = self.scores();
+                let lambda = self.lambda()? as f64;
+
+                (0..AMINO_ALPHABET.len()).for_each(|a| {
+                    let row: Vec<f64> = (0..AMINO_ALPHABET.len())
+                        .map(|b| {
+                            AMINO_BACKGROUND_FREQUENCIES[b] as f64
+                                * (lambda * scores[a][b] as f64).exp()
+                        })
+                        .collect();
+
+                    let total: f64 = row.iter().sum();
+
+                    conditionals[a] = row.iter().map(|p| (p / total) as f32).collect();
+                });
+            }
+        }
+
+        // degenerate residues get the background-weighted
+        // average of the residues that they represent
+        (AMINO_ALPHABET.len()..conditionals.len()).for_each(|residue| {
+            if let Some(members) = degenerate_members(residue) {
+                let weight: f32 = members
+                    .iter()
+                    .map(|&m| AMINO_BACKGROUND_FREQUENCIES[m])
+                    .sum();
+
+                conditionals[residue] = (0..AMINO_ALPHABET.len())
+                    .map(|b| {
+                        members
+                            .iter()
+                            .map(|&m| AMINO_BACKGROUND_FREQUENCIES[m] * conditionals[m][b])
+                            .sum::<f32>()
+                            / weight
+                    })
+                    .collect();
+            }
+        });
+
+        Ok(conditionals)
+    }
+}
+
+fn canonical_residue(symbol: &str) -> Option<usize> {
+    match symbol.as_bytes() {
+        [byte] => UTF8_TO_DIGITAL_AMINO
+            .get(&byte.to_ascii_uppercase())
+            .map(|&residue| residue as usize)
+            .filter(|&residue| residue < AMINO_ALPHABET.len()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUILTINS: [SubstitutionMatrix; 7] = [
+        SubstitutionMatrix::Blosum45,
+        SubstitutionMatrix::Blosum50,
+        SubstitutionMatrix::Blosum62,
+        SubstitutionMatrix::Blosum80,
+        SubstitutionMatrix::Pam30,
+        SubstitutionMatrix::Pam70,
+        SubstitutionMatrix::Pam250,
+    ];
+
+    #[test]
+    fn test_builtin_matrices() -> Result<()> {
+        for matrix in BUILTINS {
+            let scores = matrix.scores();
+            for (a, row) in scores.iter().enumerate() {
+                for (b, score) in row.iter().enumerate() {
+                    assert_eq!(*score, scores[b][a], "{} is asymmetric", matrix.name());
+                }
+            }
+
+            let conditionals = matrix.conditional_probabilities()?;
+            for row in conditionals.iter().take(26) {
+                let total: f32 = row.iter().sum();
+                assert!((total - 1.0).abs() < 1e-3);
+            }
+
+            assert_eq!(matrix.name().parse::<SubstitutionMatrix>()?, matrix);
+        }
+
+        let lambda = SubstitutionMatrix::Blosum62.lambda()?;
+        assert!((lambda - 0.318).abs() < 1e-3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blosum62_conditionals_match_hmmer() -> Result<()> {
+        let custom = SubstitutionMatrix::from_ncbi("custom", BLOSUM_62.as_bytes())?;
+
+        let computed = custom.conditional_probabilities()?;
+        let precomputed = SubstitutionMatrix::Blosum62.conditional_probabilities()?;
+
+        computed
+            .iter()
+            .flatten()
+            .zip(precomputed.iter().flatten())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blosum62_degenerate_rows() -> Result<()> {
+        let conditionals = SubstitutionMatrix::Blosum62.conditional_probabilities()?;
+
+        let residue = |symbol: u8| UTF8_TO_DIGITAL_AMINO[&symbol] as usize;
+        let assert_close = |row: u8, column: u8, expected: f32| {
+            let p = conditionals[residue(row)][residue(column)];
+            assert!(
+                (p - expected).abs() < 1e-4,
+                "P({} | {}) = {p}, expected {expected}",
+                column as char,
+                row as char
+            );
+        };
+
+        // O and U are read as K and C
+        assert_eq!(conditionals[residue(b'O')], conditionals[residue(b'K')]);
+        assert_eq!(conditionals[residue(b'U')], conditionals[residue(b'C')]);
+
+        // the rest are background-weighted averages of their members
+        assert_close(b'B', b'D', 0.2290);
+        assert_close(b'B', b'N', 0.1497);
+        assert_close(b'Z', b'E', 0.2353);
+        assert_close(b'Z', b'Q', 0.1143);
+        assert_close(b'J', b'L', 0.2863);
+        assert_close(b'J', b'I', 0.1516);
+        assert_close(b'X', b'A', 0.0794);
+        assert_close(b'X', b'L', 0.0964);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_ncbi() -> Result<()> {
+        let text = format!(
+            "# a comment\n{}",
+            BLOSUM_62.replacen("Y  V", "Y  V  B  Z  X  *", 1)
+        );
+        let matrix = SubstitutionMatrix::from_ncbi("test", text.as_bytes())?;
+        assert_eq!(matrix.scores(), SubstitutionMatrix::Blosum62.scores());
+
+        let truncated: String = BLOSUM_62.lines().take(10).collect::<Vec<_>>().join("\n");
+        assert!(SubstitutionMatrix::from_ncbi("test", truncated.as_bytes()).is_err());
+
+        Ok(())
+    }
+}
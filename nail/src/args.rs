@@ -1,11 +1,28 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use libnail::{
+    align::{structs::Alignment, CloudSearchParams},
+    output::output_tabular::Field,
+    search::{SearchParams, StageSpec},
+    structs::hmm::{BLOSUM_62_P_EXTEND, BLOSUM_62_P_OPEN},
+};
+
+use crate::pipeline::alignment_target_mask;
 
 #[derive(Subcommand)]
 pub enum NailSubCommands {
     #[command(about = "Run nail's protein search pipeline")]
     Search(SearchArgs),
+    #[command(about = "Compare nail's pipeline against full dynamic programming")]
+    Bench(BenchArgs),
+    #[command(about = "Calibrate the score distributions of profile HMMs")]
+    Calibrate(CalibrateArgs),
+    #[command(about = "Check nail's E-values against searches of decoy targets")]
+    Validate(ValidateArgs),
+    #[command(about = "Merge the tabular output of sharded searches")]
+    Merge(MergeArgs),
 }
 
 #[derive(Parser)]
@@ -48,6 +65,10 @@ pub struct SearchArgs {
     #[clap(next_help_heading = "Pipeline options")]
     pub pipeline_args: PipelineArgs,
 
+    #[command(flatten)]
+    #[clap(next_help_heading = "Sequence query options")]
+    pub query_args: SequenceQueryArgs,
+
     /// Arguments that are passed to MMseqs2
     #[command(flatten)]
     #[clap(next_help_heading = "MMseqs2 options")]
@@ -62,16 +83,321 @@ pub struct SearchArgs {
     pub dev_args: DevArgs,
 }
 
+impl SearchArgs {
+    /// The parameters of the alignment stages of the pipeline.
+    pub fn search_params(&self) -> SearchParams {
+        SearchParams {
+            cloud_search: CloudSearchParams {
+                gamma: self.pipeline_args.gamma,
+                alpha: self.pipeline_args.alpha,
+                beta: self.pipeline_args.beta,
+            },
+            adaptive_pruning: self.pipeline_args.adaptive_pruning,
+            cloud_cost_per_anti_diagonal: self.pipeline_args.cloud_cost_per_anti_diagonal,
+            full_dp: self.dev_args.full_dp,
+            bias_filter: self.pipeline_args.bias_filter,
+            bias_p_value_threshold: self.pipeline_args.bias_pvalue_threshold,
+            cloud_p_value_threshold: self.pipeline_args.cloud_pvalue_threshold,
+            forward_p_value_threshold: self.pipeline_args.forward_pvalue_threshold,
+            e_value_threshold: self.pipeline_args.e_value_threshold,
+            target_database_size: self.expert_args.target_database_size,
+            null_two: !self.expert_args.no_null_two,
+            target_mask: alignment_target_mask(self),
+            cloud_stage: self.pipeline_args.cloud_stage.clone(),
+            align_stage: self.pipeline_args.align_stage.clone(),
+            keep_trace: self.io_args.json_results_path.is_some()
+                || self.io_args.sam_results_path.is_some()
+                || self.io_args.bam_results_path.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct BenchArgs {
+    #[command(flatten)]
+    pub search_args: SearchArgs,
+
+    #[command(flatten)]
+    #[clap(next_help_heading = "Benchmark options")]
+    pub bench_io_args: BenchIoArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    #[command(flatten)]
+    pub search_args: SearchArgs,
+
+    #[command(flatten)]
+    #[clap(next_help_heading = "Validation options")]
+    pub validate_args: DecoyArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct MergeArgs {
+    /// The tabular output files of each shard
+    #[arg(value_name = "SHARD.tbl", required = true)]
+    pub table_paths: Vec<PathBuf>,
+
+    /// The file where the merged table will be written
+    #[arg(
+        short = 'o',
+        long = "tbl-out",
+        default_value = "merged.tbl",
+        value_name = "PATH"
+    )]
+    pub merged_results_path: PathBuf,
+
+    /// Allow nail to overwrite files
+    #[arg(long = "allow-overwrite", default_value_t = false)]
+    pub allow_overwrite: bool,
+}
+
+/// One of N interleaved subsets of a database, written "i/N" (1-based).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    /// Whether the database entry at `idx` belongs to this shard.
+    pub fn contains(&self, idx: usize) -> bool {
+        idx % self.count == self.index - 1
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("expected a shard of the form i/N with 1 <= i <= N, got: {s}");
+
+        let (index, count) = s.split_once('/').ok_or_else(error)?;
+        let index: usize = index.trim().parse().map_err(|_| error())?;
+        let count: usize = count.trim().parse().map_err(|_| error())?;
+
+        if index == 0 || index > count {
+            return Err(error());
+        }
+
+        Ok(Self { index, count })
+    }
+}
+
+/// Which database is split by `--shard`.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShardBy {
+    /// Search a subset of the queries against every target
+    #[default]
+    Queries,
+    /// Search every query against a subset of the targets
+    Targets,
+}
+
+/// How decoy targets are generated from the target database.
+#[derive(ValueEnum, Debug, Clone, Copy, Default)]
+pub enum DecoyKind {
+    /// Randomly permute the residues of each target
+    #[default]
+    Shuffle,
+    /// Reverse the residues of each target
+    Reverse,
+    /// Draw residues i.i.d. from the database composition, matching target lengths
+    Random,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct DecoyArgs {
+    /// How decoy targets are generated
+    #[arg(long = "decoys", value_enum, default_value_t = DecoyKind::Shuffle)]
+    pub decoy_kind: DecoyKind,
+
+    /// The number of decoys generated from each target
+    #[arg(long = "decoy-copies", default_value_t = 1usize, value_name = "N")]
+    pub decoy_copies: usize,
+
+    /// The random number generator seed
+    #[arg(long = "decoy-seed", default_value_t = 42u64, value_name = "N")]
+    pub decoy_seed: u64,
+
+    /// The file where observed vs. expected false positive counts will be written
+    #[arg(
+        long = "validate-out",
+        default_value = "validate.tsv",
+        value_name = "PATH"
+    )]
+    pub validate_results_path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct CalibrateArgs {
+    /// The HMM file to calibrate
+    #[arg(value_name = "QUERY.hmm")]
+    pub hmm_path: PathBuf,
+
+    /// The number of threads that nail will use
+    #[arg(short = 't', default_value_t = 8usize, value_name = "N")]
+    pub num_threads: usize,
+
+    /// Where to write the calibrated HMM file [default: overwrite QUERY.hmm]
+    #[arg(short = 'o', long = "hmm-out", value_name = "PATH")]
+    pub hmm_output_path: Option<PathBuf>,
+
+    /// Allow nail to overwrite files
+    #[arg(long = "allow-overwrite", default_value_t = false)]
+    pub allow_overwrite: bool,
+
+    /// Calibrate in uni-hit mode, rather than HMMER's multi-hit mode
+    #[arg(long = "uni-hit", action)]
+    pub uni_hit: bool,
+
+    /// Fit lambda to the sampled scores, rather than estimating it from relative entropy
+    #[arg(long = "fit-lambda", action)]
+    pub fit_lambda: bool,
+
+    /// The random number generator seed
+    #[arg(long = "seed", default_value_t = 42u64, value_name = "N")]
+    pub seed: u64,
+
+    /// The number of random sequences used to fit the MSV Gumbel
+    #[arg(long = "msv-samples", default_value_t = 200usize, value_name = "N")]
+    pub msv_samples: usize,
+
+    /// The length of random sequences used to fit the MSV Gumbel
+    #[arg(long = "msv-length", default_value_t = 200usize, value_name = "N")]
+    pub msv_length: usize,
+
+    /// The number of random sequences used to fit the Viterbi Gumbel
+    #[arg(long = "viterbi-samples", default_value_t = 200usize, value_name = "N")]
+    pub viterbi_samples: usize,
+
+    /// The length of random sequences used to fit the Viterbi Gumbel
+    #[arg(long = "viterbi-length", default_value_t = 200usize, value_name = "N")]
+    pub viterbi_length: usize,
+
+    /// The number of random sequences used to fit the Forward exponential tail
+    #[arg(long = "forward-samples", default_value_t = 200usize, value_name = "N")]
+    pub forward_samples: usize,
+
+    /// The length of random sequences used to fit the Forward exponential tail
+    #[arg(long = "forward-length", default_value_t = 100usize, value_name = "N")]
+    pub forward_length: usize,
+
+    /// The tail mass used to fit the Forward exponential tail
+    #[arg(long = "forward-tail", default_value_t = 0.04f32, value_name = "X")]
+    pub forward_tail_probability: f32,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct BenchIoArgs {
+    /// The file where per-pair benchmark results will be written
+    #[arg(long = "bench-out", default_value = "bench.tsv", value_name = "PATH")]
+    pub bench_results_path: PathBuf,
+
+    /// A file of true positive query/target name pairs, one pair per line
+    #[arg(long = "truth", value_name = "PATH")]
+    pub truth_path: Option<PathBuf>,
+
+    /// The file where ROC-style true/false positive counts will be written
+    #[arg(long = "roc-out", value_name = "PATH", requires = "truth_path")]
+    pub roc_results_path: Option<PathBuf>,
+}
+
+/// How the columns of tabular output are delimited.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TableDelimiter {
+    /// Whitespace-aligned columns with a commented header
+    #[default]
+    Space,
+    /// Strict TSV without width padding
+    Tab,
+}
+
+/// The format of the diagnostics output.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiagnosticsFormat {
+    /// TSV with a header line
+    #[default]
+    Tsv,
+    /// Apache Parquet
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
 #[derive(Args, Debug, Clone, Default)]
 pub struct IoArgs {
     /// The file where tabular output will be written
     #[arg(long = "tbl-out", default_value = "results.tbl", value_name = "PATH")]
     pub tbl_results_path: Option<PathBuf>,
 
+    /// The columns of the tabular output
+    #[arg(
+        long = "tbl-columns",
+        value_delimiter = ',',
+        value_name = "FIELDS",
+        help = "Comma-separated tabular output columns, from:\n  \
+                target, query, accession, description,\n  \
+                target_length, query_length, target_start,\n  \
+                target_end, query_start, query_end, score,\n  \
+                forward_score, comp_bias, pvalue, evalue,\n  \
+                cell_frac, cell_count"
+    )]
+    pub tbl_columns: Vec<Field>,
+
+    /// The delimiter of the tabular output
+    #[arg(
+        long = "tbl-delim",
+        value_enum,
+        default_value_t = TableDelimiter::Space,
+        value_name = "DELIM"
+    )]
+    pub tbl_delimiter: TableDelimiter,
+
     /// The file where alignment output will be written
     #[arg(long = "ali-out", default_value = None, value_name = "PATH")]
     pub ali_results_path: Option<PathBuf>,
 
+    /// The file where JSON lines output (one record per hit) will be written
+    #[arg(long = "json-out", default_value = None, value_name = "PATH")]
+    pub json_results_path: Option<PathBuf>,
+
+    /// The file where SAM output will be written
+    #[arg(
+        long = "sam-out",
+        value_name = "PATH",
+        conflicts_with = "bam_results_path"
+    )]
+    pub sam_results_path: Option<PathBuf>,
+
+    /// The file where BAM output will be written
+    #[arg(long = "bam-out", value_name = "PATH")]
+    pub bam_results_path: Option<PathBuf>,
+
+    /// The file where the stage diagnostics of every seeded pair will be written
+    #[arg(
+        long = "diagnostics-out",
+        alias = "stats-results-path",
+        value_name = "PATH"
+    )]
+    pub diagnostics_path: Option<PathBuf>,
+
+    /// The format of the diagnostics output
+    #[arg(
+        long = "diagnostics-format",
+        value_enum,
+        default_value_t = DiagnosticsFormat::Tsv,
+        value_name = "FORMAT"
+    )]
+    pub diagnostics_format: DiagnosticsFormat,
+
+    /// The number of alignment columns per line of alignment output
+    #[arg(
+        long = "ali-width",
+        default_value_t = Alignment::DEFAULT_ALI_WIDTH,
+        value_name = "N"
+    )]
+    pub ali_width: usize,
+
     /// A file containing pre-computed alignment seeds
     #[arg(long = "seeds", value_name = "PATH")]
     pub seeds_input_path: Option<PathBuf>,
@@ -84,6 +410,32 @@ pub struct IoArgs {
     #[arg(long = "tmp-dir", default_value = "tmp/", value_name = "PATH")]
     pub temp_dir_path: PathBuf,
 
+    /// Only search shard i of N of the queries (or targets, see --shard-by)
+    #[arg(long = "shard", value_name = "i/N")]
+    pub shard: Option<Shard>,
+
+    /// Which database is split by --shard
+    #[arg(
+        long = "shard-by",
+        value_enum,
+        default_value_t = ShardBy::Queries,
+        requires = "shard"
+    )]
+    pub shard_by: ShardBy,
+
+    /// Record completed queries in a journal in the temp directory, and
+    /// skip them (appending to the existing output) if the search is re-run
+    #[arg(
+        long = "resume",
+        default_value_t = false,
+        conflicts_with = "bam_results_path"
+    )]
+    pub resume: bool,
+
+    /// Write results in query file order, regardless of thread scheduling
+    #[arg(long = "ordered-output", default_value_t = false)]
+    pub ordered_output: bool,
+
     /// Allow nail to overwrite files
     #[arg(long = "allow-overwrite", default_value_t = false)]
     pub allow_overwrite: bool,
@@ -121,6 +473,45 @@ pub struct PipelineArgs {
     )]
     pub gamma: usize,
 
+    /// Scale α & β to each profile's length and relative entropy
+    #[arg(long = "adaptive-pruning", action)]
+    pub adaptive_pruning: bool,
+
+    /// Full DP routing cost model parameter
+    #[arg(
+        long = "cloud-cost-per-antidiagonal",
+        default_value_t = 0usize,
+        value_name = "N",
+        help = "Cloud search cost per anti-diagonal (in DP cells):\n  \
+                pairs cheaper to align with full DP skip cloud search,\n  \
+                and are only filtered by the Forward threshold\n  \
+                (0: always run cloud search)"
+    )]
+    pub cloud_cost_per_anti_diagonal: usize,
+
+    /// The cloud search stage
+    #[arg(
+        long = "cloud-stage",
+        default_value = "default",
+        value_name = "STAGE",
+        help = "The cloud search stage, as name[:key=value,...]:\n  \
+                default: cloud search around the seed\n  \
+                full:    the full DP matrix\n  \
+                banded:  a band around the seed's diagonal [width=32]"
+    )]
+    pub cloud_stage: StageSpec,
+
+    /// The align stage
+    #[arg(
+        long = "align-stage",
+        default_value = "default",
+        value_name = "STAGE",
+        help = "The align stage, as name[:key=value,...]:\n  \
+                default: the optimal accuracy alignment\n  \
+                viterbi: the maximum scoring alignment"
+    )]
+    pub align_stage: StageSpec,
+
     /// Seeding filter threshold
     #[arg(
         short = 'S',
@@ -131,6 +522,41 @@ pub struct PipelineArgs {
     )]
     pub seed_pvalue_threshold: f64,
 
+    /// Mask low-complexity regions of target sequences
+    #[arg(
+        long = "mask-targets",
+        action,
+        help = "Mask low-complexity regions of targets with X (SEG)"
+    )]
+    pub mask_targets: bool,
+
+    /// Only mask targets for seeding
+    #[arg(
+        long = "mask-seeding-only",
+        action,
+        requires = "mask_targets",
+        help = "With --mask-targets, only mask for seeding:\n  \
+                align against the unmasked targets"
+    )]
+    pub mask_seeding_only: bool,
+
+    /// Filter seeds whose scores are mostly due to composition bias
+    #[arg(long = "bias-filter", action)]
+    pub bias_filter: bool,
+
+    /// Bias filter threshold
+    #[arg(
+        long = "bias-pvalue-threshold",
+        default_value_t = 0.02f64,
+        value_name = "X",
+        help = "Bias filter threshold (with --bias-filter):\n  \
+                filter hits with bias-corrected seed P-value > X\n  \
+                (an uncalibrated heuristic: the MMseqs2 seed score\n  \
+                is scored with the Forward tail parameters,\n  \
+                which were not fit to seed scores)"
+    )]
+    pub bias_pvalue_threshold: f64,
+
     /// Cloud search filter threshold
     #[arg(
         short = 'C',
@@ -170,6 +596,45 @@ pub struct PipelineArgs {
     pub only_seed: bool,
 }
 
+#[derive(Args, Debug, Clone, Default)]
+pub struct SequenceQueryArgs {
+    /// The substitution matrix used to build profiles from query sequences
+    #[arg(
+        long = "matrix",
+        default_value = "BLOSUM62",
+        value_name = "NAME|PATH",
+        help = "Substitution matrix for sequence queries:\n  \
+                BLOSUM45, BLOSUM50, BLOSUM62, BLOSUM80,\n  \
+                PAM30, PAM70, PAM250, or an NCBI-format file"
+    )]
+    pub matrix: String,
+
+    /// The gap open probability used to build profiles from query sequences
+    #[arg(long = "popen", default_value_t = BLOSUM_62_P_OPEN, value_name = "X")]
+    pub p_open: f32,
+
+    /// The gap extend probability used to build profiles from query sequences
+    #[arg(long = "pextend", default_value_t = BLOSUM_62_P_EXTEND, value_name = "X")]
+    pub p_extend: f32,
+
+    /// The file where calibrated query profiles are cached between runs
+    #[arg(
+        long = "profile-cache",
+        value_name = "PATH",
+        help = "Cache of calibrated sequence query profiles:\n  \
+                [default: <tmp-dir>/profile-cache.jsonl]"
+    )]
+    pub profile_cache_path: Option<PathBuf>,
+
+    /// Don't read or write the calibrated profile cache
+    #[arg(
+        long = "no-profile-cache",
+        action,
+        conflicts_with = "profile_cache_path"
+    )]
+    pub no_profile_cache: bool,
+}
+
 #[derive(Args, Debug, Clone, Default)]
 pub struct ExpertArgs {
     /// Override the number of comparisons used for E-value calculation
@@ -183,10 +648,6 @@ pub struct ExpertArgs {
 
 #[derive(Args, Debug, Clone, Default)]
 pub struct DevArgs {
-    /// Where to place stats output
-    #[arg(long, value_name = "PATH", hide = true)]
-    pub stats_results_path: Option<PathBuf>,
-
     /// Compute the full DP matrices
     #[arg(long, action, hide = true)]
     pub full_dp: bool,
@@ -0,0 +1,459 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{stdout, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::args::{BenchArgs, SearchArgs};
+use crate::pipeline::{
+    alignment_targets, profile_from_sequence, AlignmentStages, DefaultSeedStage, OutputStage,
+    Pipeline, PipelineResult, SearchParams, SeedMap, SequenceProfileParams, StageResult,
+};
+use crate::search::{read_queries, seed, Queries};
+use crate::stats::Stats;
+use crate::util::PathBufExt;
+
+use libnail::{io::Fasta, structs::Profile};
+
+use anyhow::Context;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+/// The outcome of a single query/target pair in one pipeline.
+#[derive(Default, Clone)]
+struct PairOutcome {
+    /// Whether the pair was reported under the E-value threshold
+    hit: bool,
+    /// The final bit score, if the pair produced an alignment
+    bit_score: Option<f32>,
+    /// The E-value, if the pair produced an alignment
+    e_value: Option<f64>,
+    /// The number of DP cells computed across all stages
+    cells: usize,
+    /// The time spent across all stages
+    time: Duration,
+}
+
+impl PairOutcome {
+    fn new(result: &PipelineResult, e_value_threshold: f64) -> Self {
+        let mut outcome = Self::default();
+
+        if let Some(bias_filter_result) = &result.bias_filter_result {
+            outcome.time += bias_filter_result.stats().time;
+        }
+
+        if let Some(cloud_result) = &result.cloud_result {
+            let stats = cloud_result.stats();
+            outcome.cells += stats.total_cells();
+            outcome.time += stats.total_time();
+        }
+
+        if let Some(align_result) = &result.align_result {
+            let stats = align_result.stats();
+            outcome.cells += stats.total_cells();
+            outcome.time += stats.total_time();
+
+            if let StageResult::Passed { data: ali, .. } = align_result {
+                outcome.bit_score = Some(ali.scores.bit_score.value());
+                outcome.e_value = Some(ali.scores.e_value);
+                outcome.hit = ali.scores.e_value <= e_value_threshold;
+            }
+        }
+
+        outcome
+    }
+}
+
+struct BenchRecord {
+    profile_name: String,
+    target_name: String,
+    default: PairOutcome,
+    full: PairOutcome,
+}
+
+impl BenchRecord {
+    const HEADER: &'static str = "query\ttarget\tdefault_hit\tfull_hit\t\
+                                  default_score\tfull_score\tscore_delta\t\
+                                  default_evalue\tfull_evalue\t\
+                                  default_cells\tfull_cells\tcell_frac\t\
+                                  default_time\tfull_time";
+
+    fn score_delta(&self) -> Option<f32> {
+        Some(self.default.bit_score? - self.full.bit_score?)
+    }
+
+    fn tab_string(&self) -> String {
+        fn or_dash<T: ToString>(value: Option<T>) -> String {
+            value.map_or("-".to_string(), |v| v.to_string())
+        }
+
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.profile_name,
+            self.target_name,
+            self.default.hit as u8,
+            self.full.hit as u8,
+            or_dash(self.default.bit_score.map(|s| format!("{s:.2}"))),
+            or_dash(self.full.bit_score.map(|s| format!("{s:.2}"))),
+            or_dash(self.score_delta().map(|d| format!("{d:.2}"))),
+            or_dash(self.default.e_value.map(|e| format!("{e:.1e}"))),
+            or_dash(self.full.e_value.map(|e| format!("{e:.1e}"))),
+            self.default.cells,
+            self.full.cells,
+            or_dash(fraction(self.default.cells as f64, self.full.cells as f64)),
+            self.default.time.as_nanos(),
+            self.full.time.as_nanos(),
+        )
+    }
+}
+
+fn fraction(numerator: f64, denominator: f64) -> Option<f64> {
+    if denominator > 0.0 {
+        Some(numerator / denominator)
+    } else {
+        None
+    }
+}
+
+/// Read a whitespace delimited file of true positive query/target pairs.
+fn read_truth(path: impl AsRef<Path>) -> anyhow::Result<HashSet<(String, String)>> {
+    let reader = BufReader::new(File::open(&path).context("failed to open truth file")?);
+    let mut truth = HashSet::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace();
+        match (tokens.next(), tokens.next()) {
+            (Some(query), Some(target)) => {
+                truth.insert((query.to_string(), target.to_string()));
+            }
+            _ => anyhow::bail!("malformed truth file line: {line}"),
+        }
+    }
+
+    Ok(truth)
+}
+
+/// Compute cumulative (E-value, true positives, false positives)
+/// points over a pipeline's hits, ordered by E-value.
+fn roc_points<'a>(
+    records: &'a [BenchRecord],
+    outcome: impl Fn(&'a BenchRecord) -> &'a PairOutcome,
+    truth: &HashSet<(String, String)>,
+) -> Vec<(f64, usize, usize)> {
+    let mut hits: Vec<(f64, bool)> = records
+        .iter()
+        .filter(|r| outcome(r).hit)
+        .filter_map(|r| {
+            let is_true = truth.contains(&(r.profile_name.clone(), r.target_name.clone()));
+            outcome(r).e_value.map(|e| (e, is_true))
+        })
+        .collect();
+
+    hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut true_positives = 0usize;
+    let mut false_positives = 0usize;
+
+    hits.iter()
+        .map(|(e_value, is_true)| {
+            match is_true {
+                true => true_positives += 1,
+                false => false_positives += 1,
+            }
+            (*e_value, true_positives, false_positives)
+        })
+        .collect()
+}
+
+fn build_pipelines(
+    args: &SearchArgs,
+    targets: Fasta,
+    seeds: SeedMap,
+    stats: Stats,
+) -> anyhow::Result<(Pipeline, Pipeline)> {
+    // the default pipeline always runs cloud search
+    let params = SearchParams {
+        full_dp: false,
+        ..args.search_params()
+    };
+
+    let targets = alignment_targets(&targets, args, "targets.masked.fasta")?;
+
+    let default = Pipeline {
+        seed: Box::new(DefaultSeedStage::new(seeds.clone())),
+        stages: Arc::new(
+            AlignmentStages::new(Box::new(targets.clone()), &params)
+                .context("failed to create alignment stages")?,
+        ),
+        output: OutputStage::new(args).context("failed to create OutputStage")?,
+        stats: stats.clone(),
+    };
+
+    // the full DP pipeline uses the same seeds, so any
+    // difference in the results is due to cloud search
+    let full = Pipeline {
+        seed: Box::new(DefaultSeedStage::new(seeds)),
+        stages: Arc::new(
+            AlignmentStages::new(
+                Box::new(targets),
+                &SearchParams {
+                    full_dp: true,
+                    ..params
+                },
+            )
+            .context("failed to create alignment stages")?,
+        ),
+        output: OutputStage::new(args).context("failed to create OutputStage")?,
+        stats,
+    };
+
+    Ok((default, full))
+}
+
+fn run_bench(
+    profiles: &[Profile],
+    default: &Pipeline,
+    full: &Pipeline,
+    e_value_threshold: f64,
+) -> Vec<BenchRecord> {
+    profiles
+        .par_iter()
+        .panic_fuse()
+        .map_init(
+            || (default.clone(), full.clone()),
+            |(default, full), profile| {
+                let default_results = default.align(profile).unwrap_or_default();
+                let mut full_outcomes: HashMap<String, PairOutcome> = full
+                    .align(profile)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|r| {
+                        (
+                            r.target_name.clone(),
+                            PairOutcome::new(r, e_value_threshold),
+                        )
+                    })
+                    .collect();
+
+                default_results
+                    .iter()
+                    .map(|r| BenchRecord {
+                        profile_name: r.profile_name.clone(),
+                        target_name: r.target_name.clone(),
+                        default: PairOutcome::new(r, e_value_threshold),
+                        full: full_outcomes.remove(&r.target_name).unwrap_or_default(),
+                    })
+                    .collect::<Vec<_>>()
+            },
+        )
+        .flatten_iter()
+        .collect()
+}
+
+fn write_summary(
+    out: &mut impl Write,
+    records: &[BenchRecord],
+    truth: Option<&HashSet<(String, String)>>,
+) -> anyhow::Result<()> {
+    let default_hits = records.iter().filter(|r| r.default.hit).count();
+    let full_hits = records.iter().filter(|r| r.full.hit).count();
+    let shared_hits = records
+        .iter()
+        .filter(|r| r.default.hit && r.full.hit)
+        .count();
+
+    let deltas: Vec<f32> = records.iter().filter_map(|r| r.score_delta()).collect();
+    let mean_delta = fraction(deltas.iter().sum::<f32>() as f64, deltas.len() as f64);
+    let max_delta = deltas.iter().map(|d| d.abs()).reduce(f32::max);
+
+    let default_cells: usize = records.iter().map(|r| r.default.cells).sum();
+    let full_cells: usize = records.iter().map(|r| r.full.cells).sum();
+
+    let default_time: Duration = records.iter().map(|r| r.default.time).sum();
+    let full_time: Duration = records.iter().map(|r| r.full.time).sum();
+
+    let or_dash = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{v:.4}"));
+
+    writeln!(out, "benchmark summary")?;
+    writeln!(out, "  pairs:         {}", records.len())?;
+    writeln!(out, "  full DP hits:  {full_hits}")?;
+    writeln!(
+        out,
+        "  nail hits:     {default_hits} ({shared_hits} shared)"
+    )?;
+    writeln!(
+        out,
+        "  recall:        {}",
+        or_dash(fraction(shared_hits as f64, full_hits as f64))
+    )?;
+    writeln!(
+        out,
+        "  score delta:   mean {} bits, max |Δ| {} bits",
+        or_dash(mean_delta),
+        or_dash(max_delta.map(|d| d as f64))
+    )?;
+    writeln!(
+        out,
+        "  cell fraction: {}",
+        or_dash(fraction(default_cells as f64, full_cells as f64))
+    )?;
+    writeln!(
+        out,
+        "  time:          nail {:.2}s, full DP {:.2}s (speedup {})",
+        default_time.as_secs_f64(),
+        full_time.as_secs_f64(),
+        or_dash(fraction(
+            full_time.as_secs_f64(),
+            default_time.as_secs_f64()
+        ))
+    )?;
+
+    if let Some(truth) = truth {
+        let count = |hit: fn(&BenchRecord) -> bool, is_true: bool| {
+            records
+                .iter()
+                .filter(|r| hit(r))
+                .filter(|r| {
+                    truth.contains(&(r.profile_name.clone(), r.target_name.clone())) == is_true
+                })
+                .count()
+        };
+
+        writeln!(
+            out,
+            "  nail TP/FP:    {}/{}",
+            count(|r| r.default.hit, true),
+            count(|r| r.default.hit, false)
+        )?;
+        writeln!(
+            out,
+            "  full DP TP/FP: {}/{}",
+            count(|r| r.full.hit, true),
+            count(|r| r.full.hit, false)
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn bench(mut args: BenchArgs) -> anyhow::Result<()> {
+    let start_time = Instant::now();
+
+    // the benchmark collects pipeline results directly,
+    // so none of the search output writers are used
+    let search_args = &mut args.search_args;
+    search_args.ali_to_stdout = false;
+    search_args.io_args.tbl_results_path = None;
+    search_args.io_args.ali_results_path = None;
+    search_args.io_args.json_results_path = None;
+    search_args.io_args.sam_results_path = None;
+    search_args.io_args.bam_results_path = None;
+    search_args.io_args.diagnostics_path = None;
+    search_args.io_args.resume = false;
+
+    let allow_overwrite = search_args.io_args.allow_overwrite;
+
+    let mut bench_writer = args
+        .bench_io_args
+        .bench_results_path
+        .open(allow_overwrite)?;
+
+    let mut roc_writer = match &args.bench_io_args.roc_results_path {
+        Some(path) => Some(path.open(allow_overwrite)?),
+        None => None,
+    };
+
+    let truth = match &args.bench_io_args.truth_path {
+        Some(path) => Some(read_truth(path)?),
+        None => None,
+    };
+
+    let now = Instant::now();
+    println!("reading query database...");
+    let queries = read_queries(&search_args.query_path)?;
+    println!(
+        "\x1b[Areading query database...   done ({:.2}s)",
+        now.elapsed().as_secs_f64()
+    );
+
+    let sequence_profile_params = SequenceProfileParams::new(search_args, &queries)?;
+
+    let now = Instant::now();
+    println!("indexing target database...");
+    let targets =
+        Fasta::from_path(&search_args.target_path).context("failed to read target fasta")?;
+    println!(
+        "\x1b[Aindexing target database... done ({:.2}s)",
+        now.elapsed().as_secs_f64()
+    );
+
+    let mut stats = Stats::new(&queries, &targets);
+
+    if search_args.expert_args.target_database_size.is_none() {
+        search_args.expert_args.target_database_size = Some(targets.len());
+    }
+
+    let seeds = seed(&queries, &targets, search_args, &mut stats)?;
+
+    let profiles: Vec<Profile> = match queries {
+        Queries::Sequence(queries) => queries
+            .par_iter()
+            .map(|sequence| profile_from_sequence(&sequence, &sequence_profile_params))
+            .collect::<anyhow::Result<_>>()
+            .context("failed to build profile from sequence")?,
+        Queries::Profile(queries) => queries,
+    };
+
+    let (default, full) = build_pipelines(search_args, targets, seeds, stats)?;
+
+    println!("running benchmark...");
+    let now = Instant::now();
+    let mut records = run_bench(
+        &profiles,
+        &default,
+        &full,
+        search_args.pipeline_args.e_value_threshold,
+    );
+    println!(
+        "\x1b[Arunning benchmark...        done ({:.2}s)\n",
+        now.elapsed().as_secs_f64()
+    );
+
+    records
+        .sort_by(|a, b| (&a.profile_name, &a.target_name).cmp(&(&b.profile_name, &b.target_name)));
+
+    writeln!(bench_writer, "{}", BenchRecord::HEADER)?;
+    records
+        .iter()
+        .try_for_each(|r| writeln!(bench_writer, "{}", r.tab_string()))
+        .context("failed to write benchmark results")?;
+
+    if let (Some(writer), Some(truth)) = (&mut roc_writer, &truth) {
+        writeln!(writer, "pipeline\tevalue\ttrue_positives\tfalse_positives")?;
+
+        for (name, points) in [
+            ("nail", roc_points(&records, |r| &r.default, truth)),
+            ("full", roc_points(&records, |r| &r.full, truth)),
+        ] {
+            points.iter().try_for_each(|(e_value, tp, fp)| {
+                writeln!(writer, "{name}\t{e_value:.1e}\t{tp}\t{fp}")
+            })?;
+        }
+    }
+
+    write_summary(&mut stdout(), &records, truth.as_ref())?;
+
+    println!(
+        "\ntotal benchmark time: {:.2}s",
+        start_time.elapsed().as_secs_f64()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::args::CalibrateArgs;
+use crate::util::PathBufExt;
+
+use libnail::calibration::{self, CalibrationParams};
+use libnail::structs::hmm::{rewrite_p7hmm_stats, Stats};
+use libnail::structs::profile::HitMode;
+use libnail::structs::{Hmm, Profile};
+
+use anyhow::Context;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+pub fn calibrate(args: CalibrateArgs) -> anyhow::Result<()> {
+    let params = CalibrationParams {
+        hit_mode: match args.uni_hit {
+            true => HitMode::UniHit,
+            false => HitMode::MultiHit,
+        },
+        msv_samples: args.msv_samples,
+        msv_length: args.msv_length,
+        viterbi_samples: args.viterbi_samples,
+        viterbi_length: args.viterbi_length,
+        forward_samples: args.forward_samples,
+        forward_length: args.forward_length,
+        forward_tail_probability: args.forward_tail_probability,
+        fit_lambda: args.fit_lambda,
+        seed: args.seed,
+    };
+
+    let now = Instant::now();
+    println!("reading hmm file...");
+    let hmms = Hmm::from_p7hmm(File::open(&args.hmm_path)?).context("failed to read hmm")?;
+    println!(
+        "\x1b[Areading hmm file...         done ({:.2}s)",
+        now.elapsed().as_secs_f64()
+    );
+
+    let now = Instant::now();
+    println!("calibrating profiles...");
+    let stats: Vec<Stats> = hmms
+        .par_iter()
+        .map(|hmm| {
+            calibration::calibrate(&Profile::new(hmm), &params)
+                .with_context(|| format!("failed to calibrate: {}", hmm.header.name))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    println!(
+        "\x1b[Acalibrating profiles...     done ({:.2}s)",
+        now.elapsed().as_secs_f64()
+    );
+
+    // when rewriting in place, we write to a temporary
+    // file first so that a failure can't truncate the input
+    let (output_path, in_place) = match args.hmm_output_path {
+        Some(path) => (path, false),
+        None => {
+            let mut path = args.hmm_path.clone().into_os_string();
+            path.push(".tmp");
+            (PathBuf::from(path), true)
+        }
+    };
+
+    let mut writer = output_path.open(args.allow_overwrite || in_place)?;
+    rewrite_p7hmm_stats(File::open(&args.hmm_path)?, &stats, &mut writer)?;
+    writer.flush()?;
+
+    if in_place {
+        std::fs::rename(&output_path, &args.hmm_path)?;
+    }
+
+    Ok(())
+}
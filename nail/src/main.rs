@@ -1,14 +1,21 @@
 mod args;
-mod io;
+mod bench;
+mod calibrate;
+mod merge;
 mod mmseqs;
 mod pipeline;
 mod search;
 mod stats;
 mod util;
+mod validate;
 
 use args::{NailCli, NailSubCommands};
+use bench::bench;
+use calibrate::calibrate;
+use merge::merge;
 use search::search;
 use util::{check_mmseqs_installed, set_threads};
+use validate::validate;
 
 use clap::Parser;
 
@@ -26,6 +33,23 @@ fn main() -> anyhow::Result<()> {
             set_threads(args.num_threads)?;
             search(args)?;
         }
+        NailSubCommands::Bench(args) => {
+            check_mmseqs_installed()?;
+            set_threads(args.search_args.num_threads)?;
+            bench(args)?;
+        }
+        NailSubCommands::Calibrate(args) => {
+            set_threads(args.num_threads)?;
+            calibrate(args)?;
+        }
+        NailSubCommands::Validate(args) => {
+            check_mmseqs_installed()?;
+            set_threads(args.search_args.num_threads)?;
+            validate(args)?;
+        }
+        NailSubCommands::Merge(args) => {
+            merge(args)?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,274 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::args::MergeArgs;
+use crate::util::PathBufExt;
+
+use anyhow::{bail, Context};
+use indexmap::IndexMap;
+use libnail::output::output_tabular::{Field, TableFormat};
+
+/// The tabular output of one shard of a search.
+struct ShardTable {
+    format: TableFormat,
+    tsv: bool,
+    rows: Vec<Vec<String>>,
+}
+
+/// Read a table written by `nail search`, in either the commented or the TSV format.
+///
+/// Returns `None` if the table is empty, which happens when a shard has no queries.
+fn read_table(path: &Path) -> anyhow::Result<Option<ShardTable>> {
+    let contents = std::fs::read_to_string(path)
+        .context(format!("failed to read table: {}", path.to_string_lossy()))?;
+
+    let lines: Vec<&str> = contents.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.is_empty() {
+        return Ok(None);
+    }
+
+    let tsv = !lines[0].starts_with('#');
+    let (format, num_header_lines) = match tsv {
+        true => (TableFormat::from_tsv_header(lines[0])?, 1),
+        false => {
+            let header_lines: Vec<&str> = lines
+                .iter()
+                .take_while(|l| l.starts_with('#'))
+                .copied()
+                .collect();
+            (TableFormat::from_header(&header_lines)?, header_lines.len())
+        }
+    };
+
+    let num_fields = format.fields.len();
+    let description_idx = format.fields.iter().position(|f| *f == Field::Description);
+
+    let rows = lines
+        .iter()
+        .skip(num_header_lines)
+        .map(|line| {
+            let mut tokens: Vec<String> = match tsv {
+                true => line.split('\t').map(str::to_string).collect(),
+                false => line.split_whitespace().map(str::to_string).collect(),
+            };
+
+            // target descriptions may contain spaces,
+            // which split them across multiple tokens
+            if let (false, Some(idx)) = (tsv, description_idx) {
+                if tokens.len() > num_fields {
+                    let extra = tokens.len() - num_fields;
+                    let description = tokens.drain(idx..=idx + extra).collect::<Vec<_>>();
+                    tokens.insert(idx, description.join(" "));
+                }
+            }
+
+            match tokens.len() == num_fields {
+                true => Ok(tokens),
+                false => Err(anyhow::anyhow!(
+                    "expected {num_fields} fields in {}, found: {line}",
+                    path.to_string_lossy()
+                )),
+            }
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(Some(ShardTable { format, tsv, rows }))
+}
+
+pub fn merge(args: MergeArgs) -> anyhow::Result<()> {
+    let mut tables = vec![];
+    for path in &args.table_paths {
+        if let Some(table) = read_table(path)? {
+            tables.push(table);
+        }
+    }
+
+    let first = match tables.first() {
+        Some(table) => table,
+        None => bail!("all of the shard tables are empty"),
+    };
+
+    if tables
+        .iter()
+        .any(|t| t.format.fields != first.format.fields || t.tsv != first.tsv)
+    {
+        bail!("the shard tables have different columns or delimiters");
+    }
+
+    let mut format = first.format.clone();
+    let tsv = first.tsv;
+
+    let column = |field: Field| {
+        format
+            .fields
+            .iter()
+            .position(|f| *f == field)
+            .context(format!("merging requires the {} column", field.name()))
+    };
+    let query_idx = column(Field::Query)?;
+    let e_value_idx = column(Field::Evalue)?;
+    let target_idx = column(Field::Target).ok();
+
+    // the queries are kept in the order they first appear
+    let mut hits_by_query: IndexMap<String, Vec<(f64, Vec<String>)>> = IndexMap::new();
+    for row in tables.into_iter().flat_map(|t| t.rows) {
+        let e_value: f64 = row[e_value_idx]
+            .parse()
+            .context(format!("failed to parse E-value: {}", row[e_value_idx]))?;
+
+        hits_by_query
+            .entry(row[query_idx].clone())
+            .or_default()
+            .push((e_value, row));
+    }
+
+    hits_by_query.values_mut().for_each(|hits| {
+        hits.sort_by(|(a_e_value, a), (b_e_value, b)| {
+            a_e_value.total_cmp(b_e_value).then_with(|| {
+                target_idx.map_or(std::cmp::Ordering::Equal, |idx| a[idx].cmp(&b[idx]))
+            })
+        })
+    });
+
+    let rows: Vec<&Vec<String>> = hits_by_query
+        .values()
+        .flat_map(|hits| hits.iter().map(|(_, row)| row))
+        .collect();
+
+    let mut writer = args.merged_results_path.open(args.allow_overwrite)?;
+
+    match tsv {
+        true => {
+            writeln!(writer, "{}", format.tsv_header())?;
+            rows.iter()
+                .try_for_each(|row| writeln!(writer, "{}", row.join("\t")))?;
+        }
+        false => {
+            format.update_widths_from_rows(&rows);
+            writeln!(writer, "{}", format.header()?)?;
+            rows.iter()
+                .try_for_each(|row| writeln!(writer, "{}", format.row_string(row)))?;
+        }
+    }
+
+    writer.flush()?;
+
+    println!(
+        "merged {} hits for {} queries from {} tables",
+        rows.len(),
+        hits_by_query.len(),
+        args.table_paths.len()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const FIELDS: [Field; 4] = [Field::Target, Field::Query, Field::Score, Field::Evalue];
+
+    /// Write a table in the format of `nail search`, with widths fit to its own rows.
+    fn write_table(path: &Path, rows: &[[&str; 4]]) -> anyhow::Result<()> {
+        let rows: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| row.iter().map(|v| v.to_string()).collect())
+            .collect();
+
+        let mut format = TableFormat::new(&FIELDS)?;
+        format.update_widths_from_rows(&rows);
+
+        let mut table = format!("{}\n", format.header()?);
+        rows.iter()
+            .for_each(|row| table.push_str(&format!("{}\n", format.row_string(row))));
+
+        std::fs::write(path, table)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("nail-merge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let shards: Vec<PathBuf> = (1..=2).map(|i| dir.join(format!("{i}.tbl"))).collect();
+        write_table(
+            &shards[0],
+            &[
+                ["t1", "q1", "50.0", "1.0e-12"],
+                ["t3", "q1", "20.0", "1.0e-3"],
+                ["t2", "q2", "30.0", "1.0e-6"],
+            ],
+        )?;
+        // the second shard has a much longer target name, so the
+        // first shard's rows must be padded to fit it when merged
+        write_table(
+            &shards[1],
+            &[
+                ["a_much_longer_target", "q1", "30.0", "1.0e-3"],
+                ["t4", "q2", "100.0", "1.0e-30"],
+                ["t5", "q1", "10.0", "NaN"],
+                ["t6", "q1", "40.0", "1.0e-8"],
+            ],
+        )?;
+
+        let merged_path = dir.join("merged.tbl");
+        merge(MergeArgs {
+            table_paths: shards,
+            merged_results_path: merged_path.clone(),
+            allow_overwrite: true,
+        })?;
+
+        let merged = std::fs::read_to_string(&merged_path)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        let header_lines: Vec<&str> = merged.lines().take_while(|l| l.starts_with('#')).collect();
+        let dash_line = header_lines.last().unwrap();
+
+        // the column spans are given by the dashes under each label
+        let mut spans: Vec<(usize, usize)> = vec![];
+        let mut start = None;
+        for (idx, c) in dash_line.char_indices().chain([(dash_line.len(), ' ')]) {
+            match (c, start) {
+                ('-', None) => start = Some(idx),
+                (' ', Some(s)) => {
+                    spans.push((s, idx));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(spans.len(), FIELDS.len());
+        // the first column's dashes follow the "# "
+        spans[0].0 = 0;
+        assert!(spans[0].1 >= "a_much_longer_target".len());
+
+        let rows: Vec<Vec<&str>> = merged
+            .lines()
+            .skip(header_lines.len())
+            .map(|line| {
+                spans
+                    .iter()
+                    .map(|&(start, end)| line.get(start..end.min(line.len())).unwrap().trim())
+                    .collect()
+            })
+            .collect();
+
+        // queries stay in the order they first appear, and the hits of each
+        // are sorted by E-value, with ties broken by target; NaN sorts last
+        let expected = vec![
+            ["t1", "q1", "50.0", "1.0e-12"],
+            ["t6", "q1", "40.0", "1.0e-8"],
+            ["a_much_longer_target", "q1", "30.0", "1.0e-3"],
+            ["t3", "q1", "20.0", "1.0e-3"],
+            ["t5", "q1", "10.0", "NaN"],
+            ["t4", "q2", "100.0", "1.0e-30"],
+            ["t2", "q2", "30.0", "1.0e-6"],
+        ];
+        assert_eq!(rows, expected);
+
+        Ok(())
+    }
+}
@@ -13,12 +13,13 @@ use anyhow::{anyhow, Context};
 use libnail::{
     align::{structs::Seed, Nats},
     alphabet::UTF8_TO_DIGITAL_AMINO,
+    io::{Fasta, SequenceDatabase},
+    mask::{mask_low_complexity, SegParams},
     structs::Profile,
 };
 
 use crate::{
     args::SearchArgs,
-    io::{Fasta, SequenceDatabase},
     pipeline::SeedMap,
     util::{CommandExt, PathBufExt},
 };
@@ -50,9 +51,12 @@ impl MmseqsDbPaths {
     }
 }
 
+/// Write a sequence database for MMseqs2, masking low-complexity
+/// regions of the sequences if masking parameters are provided.
 pub fn write_mmseqs_sequence_database(
     sequences: &Fasta,
     path: impl AsRef<Path>,
+    mask: Option<&SegParams>,
 ) -> anyhow::Result<()> {
     let db_path = path.as_ref().to_owned();
     let db_name = db_path.file_name().unwrap().to_str().unwrap();
@@ -78,6 +82,11 @@ pub fn write_mmseqs_sequence_database(
     let mut header_offset = 0usize;
 
     for (seq_count, seq) in sequences.iter().enumerate() {
+        let seq = match mask {
+            Some(params) => mask_low_complexity(&seq, params),
+            None => seq,
+        };
+
         db.write_all(&seq.utf8_bytes[1..])?;
         db.write_all(&[10u8, 0u8])?;
 
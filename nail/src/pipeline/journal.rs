@@ -0,0 +1,175 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context};
+use thiserror::Error;
+
+use crate::{args::SearchArgs, util::PathBufExt};
+
+#[derive(Error, Debug)]
+#[error("journal {path} was written by a search of different databases, remove it to start over")]
+pub struct JournalMismatchError {
+    path: String,
+}
+
+/// The number of bytes that have been written to each of the output files.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OutputOffsets {
+    pub alignment: u64,
+    pub table: u64,
+    pub json: u64,
+    pub sam: u64,
+    pub diagnostics: u64,
+}
+
+impl OutputOffsets {
+    fn add(&mut self, other: &OutputOffsets) {
+        self.alignment += other.alignment;
+        self.table += other.table;
+        self.json += other.json;
+        self.sam += other.sam;
+        self.diagnostics += other.diagnostics;
+    }
+}
+
+/// A record of the queries whose output has been completely written.
+///
+/// Each line of the journal holds the index and name of a query, followed
+/// by the size of each output file once that query's output was flushed.
+/// When a search is resumed, the output files are truncated to the sizes
+/// in the last line, which discards the output of any unfinished queries.
+pub struct Journal {
+    writer: BufWriter<File>,
+    completed: HashSet<usize>,
+    pub offsets: OutputOffsets,
+}
+
+impl Journal {
+    const HEADER: &'static str = "# nail search journal";
+
+    pub fn path(args: &SearchArgs) -> PathBuf {
+        args.io_args.temp_dir_path.join("search.journal")
+    }
+
+    fn databases_line(args: &SearchArgs) -> String {
+        format!(
+            "# query: {} target: {}",
+            args.query_path.to_string_lossy(),
+            args.target_path.to_string_lossy()
+        )
+    }
+
+    /// Open the journal of a search, reading the
+    /// queries completed by a previous run if it exists.
+    pub fn open(args: &SearchArgs) -> anyhow::Result<Self> {
+        let path = Self::path(args);
+        let databases_line = Self::databases_line(args);
+
+        let mut completed = HashSet::new();
+        let mut offsets = OutputOffsets::default();
+
+        if !path.exists() {
+            std::fs::create_dir_all(&args.io_args.temp_dir_path)?;
+
+            let mut writer = path.open(true)?;
+            writeln!(writer, "{}", Self::HEADER)?;
+            writeln!(writer, "{databases_line}")?;
+            writer.flush()?;
+
+            return Ok(Self {
+                writer,
+                completed,
+                offsets,
+            });
+        }
+
+        let contents = std::fs::read_to_string(&path).context(format!(
+            "failed to read journal: {}",
+            path.to_string_lossy()
+        ))?;
+
+        let mut lines = contents.split_inclusive('\n');
+        let mismatch = || JournalMismatchError {
+            path: path.to_string_lossy().to_string(),
+        };
+
+        if lines.next().map(str::trim_end) != Some(Self::HEADER)
+            || lines.next().map(str::trim_end) != Some(&databases_line)
+        {
+            return Err(mismatch().into());
+        }
+
+        let mut valid_len = (Self::HEADER.len() + databases_line.len() + 2) as u64;
+
+        for line in lines {
+            // a line without a newline was interrupted while it was being written
+            if !line.ends_with('\n') {
+                break;
+            }
+
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() != 7 {
+                bail!("malformed journal line: {}", line.trim_end());
+            }
+
+            let parse = |token: &str| -> anyhow::Result<u64> {
+                token
+                    .parse::<u64>()
+                    .context(format!("malformed journal line: {}", line.trim_end()))
+            };
+
+            completed.insert(parse(tokens[0])? as usize);
+            offsets = OutputOffsets {
+                alignment: parse(tokens[2])?,
+                table: parse(tokens[3])?,
+                json: parse(tokens[4])?,
+                sam: parse(tokens[5])?,
+                diagnostics: parse(tokens[6])?,
+            };
+
+            valid_len += line.len() as u64;
+        }
+
+        Ok(Self {
+            writer: path.open_at(valid_len)?,
+            completed,
+            offsets,
+        })
+    }
+
+    pub fn is_completed(&self, query_idx: usize) -> bool {
+        self.completed.contains(&query_idx)
+    }
+
+    /// Account for bytes that were written outside of a query's output.
+    pub fn add_offsets(&mut self, written: &OutputOffsets) {
+        self.offsets.add(written);
+    }
+
+    /// Record that the output of a query has been written and flushed.
+    pub fn record(
+        &mut self,
+        query_idx: usize,
+        query_name: &str,
+        written: &OutputOffsets,
+    ) -> anyhow::Result<()> {
+        self.offsets.add(written);
+        self.completed.insert(query_idx);
+
+        writeln!(
+            self.writer,
+            "{query_idx}\t{query_name}\t{}\t{}\t{}\t{}\t{}",
+            self.offsets.alignment,
+            self.offsets.table,
+            self.offsets.json,
+            self.offsets.sam,
+            self.offsets.diagnostics,
+        )?;
+
+        self.writer.flush().context("failed to write to journal")
+    }
+}
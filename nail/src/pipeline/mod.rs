@@ -1,26 +1,32 @@
-mod cloud_stage;
-pub use cloud_stage::*;
-
 mod seed_stage;
 pub use seed_stage::*;
 
-mod align_stage;
-pub use align_stage::*;
-
 mod output_stage;
 pub use output_stage::*;
 
-use std::cell::RefCell;
+mod journal;
+pub use journal::*;
+
+mod profile_cache;
+pub use profile_cache::*;
+
+use std::sync::Arc;
 use std::time::Instant;
 
-use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use anyhow::Context;
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use thiserror::Error;
-use thread_local::ThreadLocal;
 
-use libnail::structs::{Hmm, Profile};
+pub use libnail::search::*;
+use libnail::{
+    io::Fasta,
+    mask::{mask_fasta, SegParams},
+    structs::{Hmm, Profile, Sequence, SubstitutionMatrix},
+};
 
 use crate::{
-    io::Fasta,
+    args::SearchArgs,
+    search::Queries,
     stats::{Stats, ThreadedTimed},
 };
 
@@ -36,142 +42,217 @@ pub struct TargetNotFoundError {
     target_name: String,
 }
 
-pub enum StageResult<D, S> {
-    Filtered { stats: S },
-    Passed { data: D, stats: S },
-}
-
-impl<D, S> StageResult<D, S> {
-    pub fn stats(&self) -> &S {
-        match self {
-            StageResult::Passed { data: _, stats } => stats,
-            StageResult::Filtered { stats } => stats,
-        }
-    }
-}
-
-pub struct PipelineResult {
-    pub profile_name: String,
-    pub target_name: String,
-    pub profile_length: usize,
-    pub target_length: usize,
-    pub cloud_result: Option<CloudStageResult>,
-    pub align_result: Option<AlignStageResult>,
-}
-
-impl PipelineResult {
-    pub fn tab_string(&self) -> String {
-        format!(
-            "({} {} {} {}) ({}) ({})",
-            self.profile_name,
-            self.target_name,
-            self.profile_length,
-            self.target_length,
-            match self.cloud_result.as_ref() {
-                Some(result) => result.tab_string(),
-                None => "".to_string(),
-            },
-            match self.align_result.as_ref() {
-                Some(result) => result.tab_string(),
-                None => "".to_string(),
-            },
-        )
-    }
-}
-
 #[derive(Clone)]
 pub struct Pipeline {
-    pub targets: Fasta,
     pub seed: Box<dyn SeedStage>,
-    pub cloud_search: Box<dyn CloudSearchStage>,
-    pub align: Box<dyn AlignStage>,
+    // the stages are shared by every clone of the pipeline,
+    // since they keep scratch space for each worker thread
+    pub stages: Arc<AlignmentStages>,
     pub output: OutputStage,
     pub stats: Stats,
 }
 
 impl Pipeline {
-    fn run(&mut self, profile: &mut Profile) -> anyhow::Result<()> {
-        let seeds = self.seed.run(profile);
-
-        let pipeline_results: Vec<PipelineResult> = match seeds {
-            None => return Ok(()),
-            Some(seeds) => seeds
-                .iter()
-                .filter_map(|(target_name, seed)| {
-                    let target = match self.targets.get(target_name) {
-                        Some(target) => target,
-                        // TODO: probably return an error here instead
-                        None => return None,
-                    };
-
-                    let cloud_result = self.cloud_search.run(profile, &target, seed);
-
-                    let align_result = match cloud_result {
-                        StageResult::Passed {
-                            data: ref bounds, ..
-                        } => Some(self.align.run(profile, &target, bounds)),
-                        StageResult::Filtered { .. } => None,
-                    };
-
-                    Some(PipelineResult {
-                        profile_name: profile.name.clone(),
-                        target_name: target.name.clone(),
-                        profile_length: profile.length,
-                        target_length: target.length,
-                        cloud_result: Some(cloud_result),
-                        align_result,
-                    })
-                })
-                .collect(),
-        };
+    /// Run the seed, cloud search, and align stages for a profile,
+    /// returning `None` if the profile produced no seeds.
+    pub fn align(&mut self, profile: &Profile) -> Option<Vec<PipelineResult>> {
+        let seeds = self.seed.run(profile)?;
+        Some(self.stages.run(profile, seeds))
+    }
+
+    fn run(&mut self, query_idx: usize, profile: &Profile) -> anyhow::Result<()> {
+        if self.output.is_completed(query_idx) {
+            self.output.skip(query_idx)?;
+            return Ok(());
+        }
 
-        let output_stats = self.output.run(&pipeline_results)?;
+        // a query without seeds still runs the output stage,
+        // since ordered output waits on every query index
+        let pipeline_results = self.align(profile).unwrap_or_default();
+
+        let output_stats = self
+            .output
+            .run(query_idx, &profile.name, &pipeline_results)?;
         self.stats.add_sample(&pipeline_results, &output_stats);
 
         Ok(())
     }
 }
 
-pub fn run_pipeline_profile_to_sequence(queries: &mut [Profile], pipeline: &mut Pipeline) {
-    let thread_local_pipeline: ThreadLocal<RefCell<Pipeline>> = ThreadLocal::new();
+/// The low-complexity masking parameters for targets written to the seeding database.
+pub fn seeding_target_mask(args: &SearchArgs) -> Option<SegParams> {
+    match args.pipeline_args.mask_targets {
+        true => Some(SegParams::default()),
+        false => None,
+    }
+}
 
-    queries.par_iter_mut().panic_fuse().for_each(|profile| {
-        let now = Instant::now();
-        let mut pipeline = thread_local_pipeline
-            .get_or(|| RefCell::new(pipeline.clone()))
-            .borrow_mut();
+/// The low-complexity masking parameters for targets that are aligned by the pipeline.
+pub fn alignment_target_mask(args: &SearchArgs) -> Option<SegParams> {
+    match args.pipeline_args.mask_seeding_only {
+        true => None,
+        false => seeding_target_mask(args),
+    }
+}
 
-        let _ = pipeline.run(profile);
+/// The target database that the pipeline aligns against.
+///
+/// If targets are masked for alignment, this is a masked copy written to
+/// the temp directory under the given file name, so that each target is
+/// masked once rather than every time a query is aligned to it.
+pub fn alignment_targets(targets: &Fasta, args: &SearchArgs, name: &str) -> anyhow::Result<Fasta> {
+    match alignment_target_mask(args) {
+        Some(params) => {
+            std::fs::create_dir_all(&args.io_args.temp_dir_path)?;
+            let path = args.io_args.temp_dir_path.join(name);
+            mask_fasta(targets, &params, path).context("failed to mask target database")
+        }
+        None => Ok(targets.clone()),
+    }
+}
 
-        pipeline
-            .stats
-            .add_threaded_time(ThreadedTimed::Total, now.elapsed())
-    });
+/// The parameters used to build profiles from single query sequences.
+#[derive(Clone)]
+pub struct SequenceProfileParams {
+    pub matrix: SubstitutionMatrix,
+    pub p_open: f32,
+    pub p_extend: f32,
+    pub cache: Option<Arc<ProfileCache>>,
 }
 
-pub fn run_pipeline_sequence_to_sequence(queries: &Fasta, pipeline: &mut Pipeline) {
-    let thread_local_pipeline: ThreadLocal<RefCell<Pipeline>> = ThreadLocal::new();
+impl SequenceProfileParams {
+    /// The profile cache is only opened if the queries are sequences.
+    pub fn new(args: &SearchArgs, queries: &Queries) -> anyhow::Result<Self> {
+        // a built-in matrix name takes precedence over a file path
+        let matrix = match args.query_args.matrix.parse::<SubstitutionMatrix>() {
+            Ok(matrix) => matrix,
+            Err(_) => SubstitutionMatrix::from_path(&args.query_args.matrix)?,
+        };
+
+        // the profiles are built inside the pipeline, so
+        // bad parameters need to be caught up front
+        Hmm::check_gap_probabilities(args.query_args.p_open, args.query_args.p_extend)?;
+        matrix.conditional_probabilities()?;
+
+        let cache = match queries {
+            Queries::Profile(_) => None,
+            Queries::Sequence(_) if args.query_args.no_profile_cache => None,
+            Queries::Sequence(_) => {
+                let path = match args.query_args.profile_cache_path {
+                    Some(ref path) => path.clone(),
+                    None => args.io_args.temp_dir_path.join("profile-cache.jsonl"),
+                };
+                Some(Arc::new(ProfileCache::open(path)?))
+            }
+        };
+
+        Ok(Self {
+            matrix,
+            p_open: args.query_args.p_open,
+            p_extend: args.query_args.p_extend,
+            cache,
+        })
+    }
+}
 
-    queries.par_iter().panic_fuse().for_each(|sequence| {
-        let now = Instant::now();
+/// Build a calibrated profile from a single query sequence,
+/// using the profile cache to skip calibration if possible.
+pub fn profile_from_sequence(
+    sequence: &Sequence,
+    params: &SequenceProfileParams,
+) -> anyhow::Result<Profile> {
+    let mut profile =
+        Hmm::from_sequence_with_matrix(sequence, &params.matrix, params.p_open, params.p_extend)
+            .map(|h| Profile::new(&h))?;
+
+    let cached = params
+        .cache
+        .as_ref()
+        .map(|cache| (cache, ProfileCache::key(sequence, params)));
+
+    if let Some((cache, key)) = &cached {
+        if cache.load(key, &mut profile) {
+            // calibration leaves the profile configured for
+            // the calibration length, so we do the same here
+            profile.configure_for_target_length(TAU_CALIBRATION_TARGET_LENGTH);
+            return Ok(profile);
+        }
+    }
 
-        let mut pipeline = thread_local_pipeline
-            .get_or(|| RefCell::new(pipeline.clone()))
-            .borrow_mut();
+    profile.calibrate_tau(
+        TAU_CALIBRATION_SAMPLES,
+        TAU_CALIBRATION_TARGET_LENGTH,
+        TAU_CALIBRATION_TAIL_PROBABILITY,
+    );
 
-        let mut profile = Hmm::from_blosum_62_and_sequence(&sequence)
-            .map(|h| Profile::new(&h))
-            .expect("failed to build profile from sequence");
-        profile.calibrate_tau(200, 100, 0.04);
+    if let Some((cache, key)) = cached {
+        cache.store(key, &profile)?;
+    }
 
-        pipeline
-            .stats
-            .add_threaded_time(ThreadedTimed::HmmBuild, now.elapsed());
+    Ok(profile)
+}
 
-        let _ = pipeline.run(&mut profile);
+// each rayon job gets its own clone of the pipeline, rather than each thread: a
+// thread that is waiting on the seeds of one query may pick up another query;
+// the clones share the alignment stages, which hold per-thread scratch space
+
+pub fn run_pipeline_profile_to_sequence(
+    queries: &[Profile],
+    pipeline: &mut Pipeline,
+) -> anyhow::Result<()> {
+    queries
+        .par_iter()
+        .enumerate()
+        .panic_fuse()
+        .try_for_each_init(
+            || pipeline.clone(),
+            |pipeline, (query_idx, profile)| {
+                let now = Instant::now();
+
+                pipeline
+                    .run(query_idx, profile)
+                    .with_context(|| format!("failed to search query: {}", profile.name))?;
+
+                pipeline
+                    .stats
+                    .add_threaded_time(ThreadedTimed::Total, now.elapsed());
+
+                Ok(())
+            },
+        )
+}
 
-        pipeline
-            .stats
-            .add_threaded_time(ThreadedTimed::Total, now.elapsed())
-    });
+pub fn run_pipeline_sequence_to_sequence(
+    queries: &Fasta,
+    params: &SequenceProfileParams,
+    pipeline: &mut Pipeline,
+) -> anyhow::Result<()> {
+    queries
+        .par_iter()
+        .enumerate()
+        .panic_fuse()
+        .try_for_each_init(
+            || pipeline.clone(),
+            |pipeline, (query_idx, sequence)| {
+                let now = Instant::now();
+
+                let profile = profile_from_sequence(&sequence, params).with_context(|| {
+                    format!("failed to build profile from sequence: {}", sequence.name)
+                })?;
+
+                pipeline
+                    .stats
+                    .add_threaded_time(ThreadedTimed::HmmBuild, now.elapsed());
+
+                pipeline
+                    .run(query_idx, &profile)
+                    .with_context(|| format!("failed to search query: {}", profile.name))?;
+
+                pipeline
+                    .stats
+                    .add_threaded_time(ThreadedTimed::Total, now.elapsed());
+
+                Ok(())
+            },
+        )
 }
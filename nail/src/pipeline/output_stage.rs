@@ -1,5 +1,7 @@
 use std::{
-    io::{stdout, Write},
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{stdout, BufWriter, Stdout, Write},
     sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
@@ -8,12 +10,24 @@ use anyhow::{anyhow, Context};
 use derive_builder::Builder;
 use libnail::{
     align::structs::Alignment,
-    output::output_tabular::{Field, TableFormat},
+    io::SequenceDatabase,
+    output::{
+        output_diagnostics::DiagnosticsRecord,
+        output_sam::{bam_header_bytes, sam_header, BgzfWriter, SamRecord, SamReference},
+        output_tabular::{Field, TableExtents, TableFormat},
+    },
 };
 
-use crate::{args::SearchArgs, util::PathBufExt};
+#[cfg(feature = "parquet")]
+use libnail::output::output_diagnostics::DiagnosticsParquetWriter;
 
-use super::PipelineResult;
+use crate::{
+    args::{DiagnosticsFormat, SearchArgs, TableDelimiter},
+    search::Queries,
+    util::PathBufExt,
+};
+
+use super::{Journal, OutputOffsets, PipelineResult};
 
 pub const DEFAULT_COLUMNS: [Field; 10] = [
     Field::Target,
@@ -61,50 +75,344 @@ impl OutputStageStatsBuilder {
     }
 }
 
-type ResultsWriter = Option<Arc<Mutex<Box<dyn Write + Send>>>>;
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SamFormat {
+    Sam,
+    Bam,
+}
+
+/// The output of one query, rendered for each of the writers.
+#[derive(Default)]
+struct RenderedOutput {
+    query_name: String,
+    alignments: Vec<u8>,
+    table_header: String,
+    table: Vec<u8>,
+    json: Vec<u8>,
+    sam: Vec<u8>,
+    diagnostics: Vec<u8>,
+    #[cfg(feature = "parquet")]
+    diagnostics_records: Vec<DiagnosticsRecord>,
+}
+
+/// Rendered output that is waiting on the output of earlier queries.
+#[derive(Default)]
+struct ReorderBuffer {
+    next_query_idx: usize,
+    // skipped queries have no output
+    pending: BTreeMap<usize, Option<RenderedOutput>>,
+}
+
+impl ReorderBuffer {
+    fn insert(&mut self, query_idx: usize, rendered: Option<RenderedOutput>) {
+        self.pending.insert(query_idx, rendered);
+    }
+
+    /// Take the output of the next query in order, if it's ready.
+    ///
+    /// The next index is advanced as the output is taken, so that
+    /// a failure to write it doesn't hold up the queries after it.
+    fn pop_ready(&mut self) -> Option<(usize, Option<RenderedOutput>)> {
+        let query_idx = self.next_query_idx;
+        let rendered = self.pending.remove(&query_idx)?;
+        self.next_query_idx += 1;
+        Some((query_idx, rendered))
+    }
+}
+
+/// The diagnostics of each of a query's pipeline results, sorted by target name.
+fn diagnostics_records(pipeline_results: &[PipelineResult]) -> Vec<DiagnosticsRecord> {
+    let mut results: Vec<&PipelineResult> = pipeline_results.iter().collect();
+    results.sort_by(|a, b| a.target_name.cmp(&b.target_name));
+    results.into_iter().map(DiagnosticsRecord::from).collect()
+}
+
+/// A writer of one of the output files.
+trait ResultsWrite: Write + Send {
+    /// Write any buffered output, along with anything
+    /// the format needs at the end of the file.
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.flush()
+    }
+}
+
+impl ResultsWrite for Stdout {}
+
+impl ResultsWrite for BufWriter<File> {}
+
+impl<W: Write + Send> ResultsWrite for BgzfWriter<W> {
+    fn finish(&mut self) -> std::io::Result<()> {
+        BgzfWriter::finish(self)
+    }
+}
+
+type ResultsWriter = Option<Arc<Mutex<Box<dyn ResultsWrite>>>>;
+
+#[cfg(feature = "parquet")]
+type ParquetWriter = Option<Arc<Mutex<DiagnosticsParquetWriter<BufWriter<File>>>>>;
+
 #[derive(Clone)]
 pub struct OutputStage {
     alignment_writer: ResultsWriter,
     table_writer: ResultsWriter,
-    stats_writer: ResultsWriter,
+    json_writer: ResultsWriter,
+    sam_writer: ResultsWriter,
+    sam_format: SamFormat,
+    // the index of each target in the BAM header
+    sam_reference_indices: Arc<HashMap<String, usize>>,
+    diagnostics_writer: ResultsWriter,
+    #[cfg(feature = "parquet")]
+    diagnostics_parquet_writer: ParquetWriter,
     e_value_threshold: f64,
+    ali_width: usize,
     table_format: TableFormat,
+    table_delimiter: TableDelimiter,
+    // whether the table's column widths are fixed for every query
+    fixed_table_widths: bool,
     header_status: Arc<Mutex<HeaderStatus>>,
+    reorder_buffer: Option<Arc<Mutex<ReorderBuffer>>>,
+    journal: Option<Arc<Mutex<Journal>>>,
 }
 
 impl OutputStage {
     pub fn new(args: &SearchArgs) -> anyhow::Result<Self> {
+        let mut journal = match args.io_args.resume {
+            true => Some(Journal::open(args).context("failed to open journal")?),
+            false => None,
+        };
+
+        // when resuming, the output files are truncated to the end
+        // of the output of the last query recorded in the journal
+        let offsets = journal.as_ref().map(|j| j.offsets).unwrap_or_default();
+
         let alignment_writer: ResultsWriter = if args.ali_to_stdout {
             Some(Arc::new(Mutex::new(Box::new(stdout()))))
         } else {
             match &args.io_args.ali_results_path {
-                Some(path) => Some(Arc::new(Mutex::new(Box::new(path.open(true)?)))),
+                Some(path) => Some(Arc::new(Mutex::new(Box::new(
+                    path.open_at(offsets.alignment)?,
+                )))),
                 None => None,
             }
         };
 
         let table_writer: ResultsWriter = match &args.io_args.tbl_results_path {
-            Some(path) => Some(Arc::new(Mutex::new(Box::new(path.open(true)?)))),
+            Some(path) => Some(Arc::new(Mutex::new(Box::new(path.open_at(offsets.table)?)))),
             None => None,
         };
 
-        let stats_writer: ResultsWriter = match &args.dev_args.stats_results_path {
-            Some(path) => Some(Arc::new(Mutex::new(Box::new(path.open(true)?)))),
+        let json_writer: ResultsWriter = match &args.io_args.json_results_path {
+            Some(path) => Some(Arc::new(Mutex::new(Box::new(path.open_at(offsets.json)?)))),
             None => None,
         };
 
+        let (sam_writer, sam_format): (ResultsWriter, SamFormat) = match (
+            &args.io_args.sam_results_path,
+            &args.io_args.bam_results_path,
+        ) {
+            (_, Some(path)) => (
+                Some(Arc::new(Mutex::new(Box::new(BgzfWriter::new(
+                    path.open(true)?,
+                ))))),
+                SamFormat::Bam,
+            ),
+            (Some(path), None) => (
+                Some(Arc::new(Mutex::new(Box::new(path.open_at(offsets.sam)?)))),
+                SamFormat::Sam,
+            ),
+            (None, None) => (None, SamFormat::Sam),
+        };
+
+        let diagnostics_writer: ResultsWriter = match (
+            &args.io_args.diagnostics_path,
+            args.io_args.diagnostics_format,
+        ) {
+            (Some(path), DiagnosticsFormat::Tsv) => {
+                let mut writer: Box<dyn ResultsWrite> =
+                    Box::new(path.open_at(offsets.diagnostics)?);
+
+                // a resumed search has already written the header
+                if offsets.diagnostics == 0 {
+                    let header = format!("{}\n", DiagnosticsRecord::tsv_header());
+                    writer.write_all(header.as_bytes())?;
+
+                    if let Some(ref mut journal) = journal {
+                        journal.add_offsets(&OutputOffsets {
+                            diagnostics: header.len() as u64,
+                            ..Default::default()
+                        });
+                    }
+                }
+
+                Some(Arc::new(Mutex::new(writer)))
+            }
+            _ => None,
+        };
+
+        #[cfg(feature = "parquet")]
+        let diagnostics_parquet_writer: ParquetWriter = match (
+            &args.io_args.diagnostics_path,
+            args.io_args.diagnostics_format,
+        ) {
+            (Some(path), DiagnosticsFormat::Parquet) => {
+                // the Parquet footer is only written when the file is
+                // closed, so there is no way to append to it on resume
+                if args.io_args.resume {
+                    return Err(anyhow!(
+                        "Parquet diagnostics output can't be used with --resume"
+                    ));
+                }
+
+                Some(Arc::new(Mutex::new(DiagnosticsParquetWriter::new(
+                    path.open(true)?,
+                )?)))
+            }
+            _ => None,
+        };
+
         Ok(Self {
             alignment_writer,
             table_writer,
-            table_format: TableFormat::new(&DEFAULT_COLUMNS)?,
+            json_writer,
+            sam_writer,
+            sam_format,
+            sam_reference_indices: Arc::new(HashMap::new()),
+            table_format: match args.io_args.tbl_columns.is_empty() {
+                true => TableFormat::new(&DEFAULT_COLUMNS)?,
+                false => TableFormat::new(&args.io_args.tbl_columns)?,
+            },
+            table_delimiter: args.io_args.tbl_delimiter,
+            fixed_table_widths: false,
             e_value_threshold: args.pipeline_args.e_value_threshold,
-            header_status: Arc::new(Mutex::new(HeaderStatus::Unwritten)),
-            stats_writer,
+            ali_width: args.io_args.ali_width,
+            header_status: Arc::new(Mutex::new(match offsets.table {
+                0 => HeaderStatus::Unwritten,
+                _ => HeaderStatus::Written,
+            })),
+            reorder_buffer: match args.io_args.ordered_output {
+                true => Some(Arc::new(Mutex::new(ReorderBuffer::default()))),
+                false => None,
+            },
+            journal: journal.map(|j| Arc::new(Mutex::new(j))),
+            diagnostics_writer,
+            #[cfg(feature = "parquet")]
+            diagnostics_parquet_writer,
         })
     }
 
-    pub fn run(&mut self, pipeline_results: &[PipelineResult]) -> anyhow::Result<OutputStageStats> {
-        let mut stats = OutputStageStatsBuilder::default();
+    /// Whether the output of a query was written by a previous run of a resumed search.
+    pub fn is_completed(&self, query_idx: usize) -> bool {
+        match &self.journal {
+            Some(journal) => journal
+                .lock()
+                .map(|j| j.is_completed(query_idx))
+                .unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Write the SAM/BAM header, which lists every target sequence.
+    ///
+    /// This must be called before the output stage is run (or cloned).
+    pub fn write_sam_header(&mut self, targets: &dyn SequenceDatabase) -> anyhow::Result<()> {
+        let writer = match &self.sam_writer {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+
+        let references: Vec<SamReference> = targets
+            .iter()
+            .map(|target| SamReference {
+                name: target.name,
+                length: target.length,
+            })
+            .collect();
+
+        self.sam_reference_indices = Arc::new(
+            references
+                .iter()
+                .enumerate()
+                .map(|(idx, r)| (r.name.clone(), idx))
+                .collect(),
+        );
+
+        let header = match self.sam_format {
+            SamFormat::Sam => sam_header(&references).into_bytes(),
+            SamFormat::Bam => bam_header_bytes(&references),
+        };
+
+        if let Some(journal) = &self.journal {
+            let mut journal = journal
+                .lock()
+                .map_err(|_| anyhow!("journal mutex poisoned"))?;
+
+            // a resumed search has already written the header
+            if journal.offsets.sam > 0 {
+                return Ok(());
+            }
+
+            journal.add_offsets(&OutputOffsets {
+                sam: header.len() as u64,
+                ..Default::default()
+            });
+        }
+
+        match writer.lock() {
+            Ok(mut guard) => guard
+                .write_all(&header)
+                .context("failed to write to SAM writer"),
+            Err(_) => Err(anyhow!("SAM writer mutex poisoned")),
+        }
+    }
+
+    /// Fix the column widths of a space-delimited table in ordered mode,
+    /// so that the rows of every query line up under the one header.
+    ///
+    /// This must be called before the output stage is run (or cloned).
+    pub fn fix_table_widths(&mut self, queries: &Queries, targets: &dyn SequenceDatabase) {
+        if self.reorder_buffer.is_none()
+            || self.table_writer.is_none()
+            || self.table_delimiter == TableDelimiter::Tab
+        {
+            return;
+        }
+
+        let mut extents = TableExtents::default();
+
+        targets.iter().for_each(|target| {
+            extents.target_name = extents.target_name.max(target.name.len());
+            extents.target_description = extents
+                .target_description
+                .max(target.details.as_ref().map_or(0, |details| details.len()));
+            extents.target_length = extents.target_length.max(target.length);
+        });
+
+        match queries {
+            Queries::Sequence(queries) => queries.iter().for_each(|query| {
+                extents.query_name = extents.query_name.max(query.name.len());
+                extents.query_length = extents.query_length.max(query.length);
+            }),
+            Queries::Profile(queries) => queries.iter().for_each(|query| {
+                extents.query_name = extents.query_name.max(query.name.len());
+                extents.query_accession = extents.query_accession.max(query.accession.len());
+                extents.query_length = extents.query_length.max(query.length);
+            }),
+        }
+
+        self.table_format.fix_widths(&extents);
+        self.fixed_table_widths = true;
+    }
+
+    /// Render the output of one query for each of the writers.
+    fn render(
+        &mut self,
+        query_name: &str,
+        pipeline_results: &[PipelineResult],
+    ) -> anyhow::Result<RenderedOutput> {
+        let mut rendered = RenderedOutput {
+            query_name: query_name.to_string(),
+            ..Default::default()
+        };
 
         let mut reported: Vec<&Alignment> = pipeline_results
             .iter()
@@ -116,36 +424,157 @@ impl OutputStage {
             .filter(|a| a.scores.e_value <= self.e_value_threshold)
             .collect();
 
-        reported.sort_by(|a, b| a.scores.e_value.partial_cmp(&b.scores.e_value).unwrap());
+        // ties are broken by target name so that the
+        // order doesn't depend on the order of the seeds
+        reported.sort_by(|a, b| {
+            a.scores
+                .e_value
+                .partial_cmp(&b.scores.e_value)
+                .unwrap()
+                .then_with(|| a.target_name.cmp(&b.target_name))
+        });
 
-        if let Some(writer) = &self.alignment_writer {
-            let now = Instant::now();
-            match writer.lock() {
-                Ok(mut guard) => {
-                    stats.add_lock_time(now.elapsed());
+        if self.alignment_writer.is_some() {
+            reported.iter().try_for_each(|ali| {
+                writeln!(
+                    rendered.alignments,
+                    "{}\n",
+                    ali.ali_string_with_width(self.ali_width)
+                )
+            })?;
+        }
 
-                    let now = Instant::now();
-                    reported
-                        .iter()
-                        .try_for_each(|ali| writeln!(guard, "{}\n", ali.ali_string()))
-                        .with_context(|| "failed to write to alignment writer")?;
+        if self.table_writer.is_some() {
+            let tsv = self.table_delimiter == TableDelimiter::Tab;
 
-                    stats.add_write_time(now.elapsed());
-                    Ok(())
+            if !tsv && !self.fixed_table_widths {
+                self.table_format.reset_widths();
+                self.table_format.update_widths(&reported);
+            }
+
+            rendered.table_header = match tsv {
+                true => self.table_format.tsv_header(),
+                false => TableFormat::header(&self.table_format)?,
+            };
+
+            reported.iter().try_for_each(|ali| {
+                let row = match tsv {
+                    true => ali.tsv_string(&self.table_format),
+                    false => ali.tab_string_formatted(&self.table_format),
+                };
+                writeln!(rendered.table, "{row}")
+            })?;
+        }
+
+        if self.json_writer.is_some() {
+            reported.iter().try_for_each(|ali| {
+                serde_json::to_writer(&mut rendered.json, ali)?;
+                writeln!(rendered.json)?;
+                anyhow::Ok(())
+            })?;
+        }
+
+        if self.sam_writer.is_some() {
+            reported.iter().try_for_each(|ali| {
+                let record =
+                    SamRecord::from_alignment(ali).context("failed to build SAM record")?;
+
+                match self.sam_format {
+                    SamFormat::Sam => writeln!(rendered.sam, "{}", record.sam_string())?,
+                    SamFormat::Bam => {
+                        let reference_idx = self
+                            .sam_reference_indices
+                            .get(&record.reference_name)
+                            .context("SAM header is missing a target")?;
+                        rendered.sam.extend(record.bam_bytes(*reference_idx)?);
+                    }
                 }
-                Err(_) => Err(anyhow!("alignment writer mutex poisoned")),
-            }?;
+
+                anyhow::Ok(())
+            })?;
         }
 
+        if self.diagnostics_writer.is_some() {
+            diagnostics_records(pipeline_results)
+                .iter()
+                .try_for_each(|r| writeln!(rendered.diagnostics, "{}", r.tsv_string()))?;
+        }
+
+        #[cfg(feature = "parquet")]
+        if self.diagnostics_parquet_writer.is_some() {
+            rendered.diagnostics_records = diagnostics_records(pipeline_results);
+        }
+
+        Ok(rendered)
+    }
+
+    /// Write the rendered output of one query.
+    ///
+    /// When resuming is enabled, the writes of each query are serialized
+    /// and flushed, and then the query is recorded in the journal.
+    fn write(
+        &self,
+        query_idx: usize,
+        rendered: &RenderedOutput,
+        stats: &mut OutputStageStatsBuilder,
+    ) -> anyhow::Result<()> {
+        let mut journal = match &self.journal {
+            Some(journal) => Some(
+                journal
+                    .lock()
+                    .map_err(|_| anyhow!("journal mutex poisoned"))?,
+            ),
+            None => None,
+        };
+        let flush = journal.is_some();
+
+        let write_all = |writer: &ResultsWriter,
+                         bytes: &[u8],
+                         name: &str,
+                         stats: &mut OutputStageStatsBuilder|
+         -> anyhow::Result<u64> {
+            if let Some(writer) = writer {
+                let now = Instant::now();
+                match writer.lock() {
+                    Ok(mut guard) => {
+                        stats.add_lock_time(now.elapsed());
+
+                        let now = Instant::now();
+                        guard
+                            .write_all(bytes)
+                            .with_context(|| format!("failed to write to {name} writer"))?;
+
+                        if flush {
+                            guard
+                                .flush()
+                                .with_context(|| format!("failed to flush {name} writer"))?;
+                        }
+
+                        stats.add_write_time(now.elapsed());
+                        Ok(())
+                    }
+                    Err(_) => Err(anyhow!("{name} writer mutex poisoned")),
+                }?;
+            }
+            Ok(bytes.len() as u64)
+        };
+
+        let mut written = OutputOffsets {
+            alignment: write_all(
+                &self.alignment_writer,
+                &rendered.alignments,
+                "alignment",
+                stats,
+            )?,
+            ..Default::default()
+        };
+
         if let Some(writer) = &self.table_writer {
             let now = Instant::now();
             match writer.lock() {
                 Ok(mut writer_guard) => {
                     stats.add_lock_time(now.elapsed());
 
-                    self.table_format.reset_widths();
-                    self.table_format.update_widths(&reported);
-
                     // TODO: it's a bit messy to put the header status in a
                     //       mutex I'd like to come up with a better way to
                     //       write the header just one time, but the problem
@@ -154,8 +583,8 @@ impl OutputStage {
                     match self.header_status.lock() {
                         Ok(mut header_status_guard) => {
                             if let HeaderStatus::Unwritten = *header_status_guard {
-                                let header = TableFormat::header(&self.table_format)?;
-                                writeln!(writer_guard, "{header}")?;
+                                writeln!(writer_guard, "{}", rendered.table_header)?;
+                                written.table += rendered.table_header.len() as u64 + 1;
                                 *header_status_guard = HeaderStatus::Written;
                             }
                             Ok(())
@@ -164,14 +593,16 @@ impl OutputStage {
                     }?;
 
                     let now = Instant::now();
-                    reported.iter().try_for_each(|ali| {
-                        writeln!(
-                            writer_guard,
-                            "{}",
-                            ali.tab_string_formatted(&self.table_format)
-                        )
-                        .with_context(|| "failed to write to table writer")
-                    })?;
+                    writer_guard
+                        .write_all(&rendered.table)
+                        .with_context(|| "failed to write to table writer")?;
+                    written.table += rendered.table.len() as u64;
+
+                    if flush {
+                        writer_guard
+                            .flush()
+                            .with_context(|| "failed to flush table writer")?;
+                    }
 
                     stats.add_write_time(now.elapsed());
                     Ok(())
@@ -180,24 +611,186 @@ impl OutputStage {
             }?;
         }
 
-        if let Some(writer) = &self.stats_writer {
+        written.json = write_all(&self.json_writer, &rendered.json, "json", stats)?;
+        written.sam = write_all(&self.sam_writer, &rendered.sam, "SAM", stats)?;
+        written.diagnostics = write_all(
+            &self.diagnostics_writer,
+            &rendered.diagnostics,
+            "diagnostics",
+            stats,
+        )?;
+
+        #[cfg(feature = "parquet")]
+        if let Some(writer) = &self.diagnostics_parquet_writer {
             let now = Instant::now();
-            match writer.lock() {
-                Ok(mut guard) => {
-                    stats.add_lock_time(now.elapsed());
+            let mut guard = writer
+                .lock()
+                .map_err(|_| anyhow!("diagnostics writer mutex poisoned"))?;
+            stats.add_lock_time(now.elapsed());
 
-                    let now = Instant::now();
-                    pipeline_results
-                        .iter()
-                        .try_for_each(|r| writeln!(guard, "{}", r.tab_string()))?;
+            let now = Instant::now();
+            guard
+                .write(&rendered.diagnostics_records)
+                .context("failed to write to diagnostics writer")?;
+            stats.add_write_time(now.elapsed());
+        }
 
-                    stats.add_write_time(now.elapsed());
-                    Ok(())
+        if let Some(ref mut journal) = journal {
+            journal.record(query_idx, &rendered.query_name, &written)?;
+        }
+
+        Ok(())
+    }
+
+    /// Pass the output of a query (if it has any) to the writers,
+    /// through the reorder buffer if output is ordered.
+    fn commit(
+        &self,
+        query_idx: usize,
+        rendered: Option<RenderedOutput>,
+        stats: &mut OutputStageStatsBuilder,
+    ) -> anyhow::Result<()> {
+        match &self.reorder_buffer {
+            None => {
+                if let Some(rendered) = rendered {
+                    self.write(query_idx, &rendered, stats)?;
                 }
-                Err(_) => Err(anyhow!("stats writer mutex poisoned")),
-            }?;
+            }
+            Some(buffer) => {
+                let now = Instant::now();
+                let mut guard = buffer
+                    .lock()
+                    .map_err(|_| anyhow!("reorder buffer mutex poisoned"))?;
+                stats.add_lock_time(now.elapsed());
+
+                guard.insert(query_idx, rendered);
+
+                while let Some((query_idx, rendered)) = guard.pop_ready() {
+                    if let Some(rendered) = rendered {
+                        self.write(query_idx, &rendered, stats)?;
+                    }
+                }
+            }
         }
 
+        Ok(())
+    }
+
+    /// Finish writing every output file.
+    ///
+    /// This must be called after the output of the last query has been
+    /// written; otherwise, buffered output, the end of the BAM file and the
+    /// Parquet footer are only written when the writers are dropped, and any
+    /// errors are lost.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        #[cfg(feature = "parquet")]
+        if let Some(writer) = &self.diagnostics_parquet_writer {
+            writer
+                .lock()
+                .map_err(|_| anyhow!("diagnostics writer mutex poisoned"))?
+                .finish()
+                .context("failed to finish Parquet diagnostics writer")?;
+        }
+
+        [
+            (&self.alignment_writer, "alignment"),
+            (&self.table_writer, "table"),
+            (&self.json_writer, "json"),
+            (&self.sam_writer, "SAM"),
+            (&self.diagnostics_writer, "diagnostics"),
+        ]
+        .into_iter()
+        .try_for_each(|(writer, name)| match writer {
+            Some(writer) => writer
+                .lock()
+                .map_err(|_| anyhow!("{name} writer mutex poisoned"))?
+                .finish()
+                .with_context(|| format!("failed to finish {name} writer")),
+            None => Ok(()),
+        })
+    }
+
+    /// Skip a query whose output was written by a previous run of a resumed search.
+    pub fn skip(&mut self, query_idx: usize) -> anyhow::Result<OutputStageStats> {
+        let mut stats = OutputStageStatsBuilder::default();
+        self.commit(query_idx, None, &mut stats)?;
         stats.build().map_err(Into::into)
     }
+
+    /// Write the output of the query at `query_idx` in the query database.
+    ///
+    /// In ordered mode, the output is held until the output of
+    /// every preceding query has been written, so that the output
+    /// files are in query order regardless of thread scheduling.
+    pub fn run(
+        &mut self,
+        query_idx: usize,
+        query_name: &str,
+        pipeline_results: &[PipelineResult],
+    ) -> anyhow::Result<OutputStageStats> {
+        let mut stats = OutputStageStatsBuilder::default();
+
+        let now = Instant::now();
+        let rendered = self.render(query_name, pipeline_results);
+        stats.add_write_time(now.elapsed());
+
+        match rendered {
+            Ok(rendered) => self.commit(query_idx, Some(rendered), &mut stats)?,
+            Err(err) => {
+                // the query still takes its place in
+                // the order, so later queries aren't held
+                self.commit(query_idx, None, &mut stats)?;
+                return Err(err);
+            }
+        }
+
+        stats.build().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(query_name: &str) -> Option<RenderedOutput> {
+        Some(RenderedOutput {
+            query_name: query_name.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_reorder_buffer() {
+        let mut buffer = ReorderBuffer::default();
+        let mut written: Vec<(usize, Option<String>)> = vec![];
+
+        let mut commit = |buffer: &mut ReorderBuffer, query_idx, output| {
+            buffer.insert(query_idx, output);
+            while let Some((idx, output)) = buffer.pop_ready() {
+                written.push((idx, output.map(|o| o.query_name)));
+            }
+        };
+
+        // nothing can be written until query 0 arrives
+        commit(&mut buffer, 2, rendered("c"));
+        commit(&mut buffer, 3, rendered("d"));
+        commit(&mut buffer, 1, None);
+        commit(&mut buffer, 0, rendered("a"));
+        commit(&mut buffer, 5, rendered("f"));
+        commit(&mut buffer, 4, rendered("e"));
+
+        assert_eq!(
+            written,
+            vec![
+                (0, Some("a".to_string())),
+                (1, None),
+                (2, Some("c".to_string())),
+                (3, Some("d".to_string())),
+                (4, Some("e".to_string())),
+                (5, Some("f".to_string())),
+            ]
+        );
+        assert!(buffer.pending.is_empty());
+        assert_eq!(buffer.next_query_idx, 6);
+    }
 }
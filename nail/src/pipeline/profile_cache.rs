@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::Context;
+use libnail::structs::{Profile, Sequence};
+use serde::{Deserialize, Serialize};
+
+use super::SequenceProfileParams;
+
+/// Bump this whenever a change to profile construction or
+/// calibration would invalidate previously cached values.
+const PROFILE_CACHE_VERSION: u64 = 1;
+
+/// The number of random sequences used to calibrate tau.
+pub const TAU_CALIBRATION_SAMPLES: usize = 200;
+/// The length of the random sequences used to calibrate tau.
+pub const TAU_CALIBRATION_TARGET_LENGTH: usize = 100;
+/// The tail probability used to calibrate tau.
+pub const TAU_CALIBRATION_TAIL_PROBABILITY: f32 = 0.04;
+
+/// A 64-bit FNV-1a hasher.
+///
+/// We don't use std's `DefaultHasher` because its output
+/// is not guaranteed to be stable across Rust releases,
+/// which would silently invalidate the on-disk cache.
+struct Fnv64(u64);
+
+impl Fnv64 {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        bytes.iter().for_each(|b| {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        });
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_f32(&mut self, value: f32) {
+        self.write(&value.to_bits().to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProfileCacheEntry {
+    key: String,
+    forward_tau: f32,
+    forward_lambda: f32,
+}
+
+/// An on-disk cache of the calibrated parameters of profiles built
+/// from single sequences, keyed by the sequence and build parameters.
+///
+/// The cache is a JSON lines file; new entries are appended as soon
+/// as they are computed, so an interrupted run still populates it.
+pub struct ProfileCache {
+    entries: HashMap<String, (f32, f32)>,
+    writer: Mutex<BufWriter<File>>,
+    hits: AtomicUsize,
+}
+
+impl ProfileCache {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut entries = HashMap::new();
+
+        if path.exists() {
+            let reader = BufReader::new(File::open(path)?);
+            for line in reader.lines() {
+                // a partially written line from an interrupted
+                // run is just a cache miss, so we skip it
+                if let Ok(entry) = serde_json::from_str::<ProfileCacheEntry>(&line?) {
+                    entries.insert(entry.key, (entry.forward_tau, entry.forward_lambda));
+                }
+            }
+        }
+
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!(
+                "failed to open profile cache: {}",
+                path.to_string_lossy()
+            ))?;
+
+        Ok(Self {
+            entries,
+            writer: Mutex::new(BufWriter::new(file)),
+            hits: AtomicUsize::new(0),
+        })
+    }
+
+    /// The cache key for the profile built from a sequence with the given parameters.
+    pub fn key(sequence: &Sequence, params: &SequenceProfileParams) -> String {
+        let mut hasher = Fnv64::new();
+        hasher.write_u64(PROFILE_CACHE_VERSION);
+
+        hasher.write_u64(sequence.digital_bytes.len() as u64);
+        hasher.write(&sequence.digital_bytes);
+
+        hasher.write(params.matrix.name().as_bytes());
+        params
+            .matrix
+            .scores()
+            .iter()
+            .flatten()
+            .for_each(|s| hasher.write_f32(*s));
+        hasher.write_f32(params.p_open);
+        hasher.write_f32(params.p_extend);
+
+        hasher.write_u64(TAU_CALIBRATION_SAMPLES as u64);
+        hasher.write_u64(TAU_CALIBRATION_TARGET_LENGTH as u64);
+        hasher.write_f32(TAU_CALIBRATION_TAIL_PROBABILITY);
+
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Set the calibrated parameters of a profile from the
+    /// cache, returning false if there is no cached entry.
+    pub fn load(&self, key: &str, profile: &mut Profile) -> bool {
+        match self.entries.get(key) {
+            Some((tau, lambda)) => {
+                profile.forward_tau = *tau;
+                profile.forward_lambda = *lambda;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Append the calibrated parameters of a profile to the cache file.
+    pub fn store(&self, key: String, profile: &Profile) -> anyhow::Result<()> {
+        let entry = ProfileCacheEntry {
+            key,
+            forward_tau: profile.forward_tau,
+            forward_lambda: profile.forward_lambda,
+        };
+
+        let line = serde_json::to_string(&entry)?;
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer, "{line}")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// The number of profiles that were loaded from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
@@ -1,17 +1,16 @@
-use std::collections::HashMap;
-
 use anyhow::bail;
-use libnail::{align::structs::Seed, structs::Profile};
+use libnail::{io::Fasta, search::SeedMap, structs::Profile};
 
 use crate::{
     args::SearchArgs,
-    io::Fasta,
     mmseqs::{
         run_mmseqs_search, seeds_from_mmseqs_align_tsv, write_mmseqs_profile_database,
         write_mmseqs_sequence_database, MmseqsDbPaths,
     },
 };
 
+use super::seeding_target_mask;
+
 fn merge_seed_maps(
     mut seed_map_a: SeedMap,
     mut seed_map_b: SeedMap,
@@ -48,7 +47,11 @@ pub fn seed_profile_to_sequence(
 ) -> anyhow::Result<SeedMap> {
     let paths = MmseqsDbPaths::new(&args.io_args.temp_dir_path);
 
-    write_mmseqs_sequence_database(targets, &paths.target_db)?;
+    write_mmseqs_sequence_database(
+        targets,
+        &paths.target_db,
+        seeding_target_mask(args).as_ref(),
+    )?;
     write_mmseqs_profile_database(queries, &paths.query_db)?;
 
     run_mmseqs_search(&paths, args)?;
@@ -87,8 +90,12 @@ pub fn seed_sequence_to_sequence(
 ) -> anyhow::Result<SeedMap> {
     let paths = MmseqsDbPaths::new(&args.io_args.temp_dir_path);
 
-    write_mmseqs_sequence_database(targets, &paths.target_db)?;
-    write_mmseqs_sequence_database(queries, &paths.query_db)?;
+    write_mmseqs_sequence_database(
+        targets,
+        &paths.target_db,
+        seeding_target_mask(args).as_ref(),
+    )?;
+    write_mmseqs_sequence_database(queries, &paths.query_db, None)?;
 
     run_mmseqs_search(&paths, args)?;
 
@@ -100,27 +107,3 @@ pub fn seed_sequence_to_sequence(
 
     Ok(seeds)
 }
-
-dyn_clone::clone_trait_object!(SeedStage);
-pub trait SeedStage: dyn_clone::DynClone + Send + Sync {
-    fn run(&mut self, profile: &Profile) -> Option<&HashMap<String, Seed>>;
-}
-
-pub type SeedMap = HashMap<String, HashMap<String, Seed>>;
-
-#[derive(Default, Clone)]
-pub struct DefaultSeedStage {
-    seeds: SeedMap,
-}
-
-impl DefaultSeedStage {
-    pub fn new(seeds: SeedMap) -> Self {
-        DefaultSeedStage { seeds }
-    }
-}
-
-impl SeedStage for DefaultSeedStage {
-    fn run(&mut self, profile: &Profile) -> Option<&HashMap<String, Seed>> {
-        self.seeds.get(&profile.name)
-    }
-}
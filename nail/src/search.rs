@@ -1,21 +1,24 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{stdout, BufReader, BufWriter};
+use std::io::{stdout, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Instant;
 
-use crate::args::SearchArgs;
-use crate::io::Fasta;
+use crate::args::{SearchArgs, Shard, ShardBy};
 use crate::pipeline::{
-    run_pipeline_profile_to_sequence, run_pipeline_sequence_to_sequence, seed_profile_to_sequence,
-    seed_sequence_to_sequence, DefaultAlignStage, DefaultCloudSearchStage, DefaultSeedStage,
-    FullDpCloudSearchStage, OutputStage, Pipeline, SeedMap,
+    alignment_targets, run_pipeline_profile_to_sequence, run_pipeline_sequence_to_sequence,
+    seed_profile_to_sequence, seed_sequence_to_sequence, AlignmentStages, DefaultSeedStage,
+    Journal, OutputStage, Pipeline, SeedMap, SequenceProfileParams,
 };
-use crate::stats::{SerialTimed, Stats};
+use crate::stats::{CountedValue, SerialTimed, Stats};
 use crate::util::{guess_query_format_from_query_file, FileFormat, PathBufExt};
 
-use libnail::structs::{Hmm, Profile};
+use libnail::{
+    io::{Fasta, SequenceDatabase},
+    structs::{Hmm, Profile},
+};
 
 use anyhow::Context;
 use serde::Serialize;
@@ -34,7 +37,7 @@ impl Queries {
     }
 }
 
-fn read_queries(path: impl AsRef<Path>) -> anyhow::Result<Queries> {
+pub fn read_queries(path: impl AsRef<Path>) -> anyhow::Result<Queries> {
     let query_format = guess_query_format_from_query_file(&path)?;
 
     match query_format {
@@ -58,6 +61,85 @@ fn read_queries(path: impl AsRef<Path>) -> anyhow::Result<Queries> {
     }
 }
 
+/// Write the sequences of a fasta file that belong to a shard to a new fasta file.
+fn shard_fasta(fasta: &Fasta, shard: &Shard, path: &Path) -> anyhow::Result<Fasta> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    fasta
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| shard.contains(*idx))
+        .try_for_each(|(_, sequence)| writeln!(writer, "{sequence}"))?;
+
+    writer.flush()?;
+    Fasta::from_path(path)
+}
+
+fn shard_queries(queries: Queries, shard: &Shard, temp_dir: &Path) -> anyhow::Result<Queries> {
+    match queries {
+        Queries::Sequence(queries) => {
+            let path = temp_dir.join("queries.fasta");
+            Ok(Queries::Sequence(shard_fasta(&queries, shard, &path)?))
+        }
+        Queries::Profile(queries) => Ok(Queries::Profile(
+            queries
+                .into_iter()
+                .enumerate()
+                .filter(|(idx, _)| shard.contains(*idx))
+                .map(|(_, profile)| profile)
+                .collect(),
+        )),
+    }
+}
+
+pub fn seed(
+    queries: &Queries,
+    targets: &Fasta,
+    args: &SearchArgs,
+    stats: &mut Stats,
+) -> anyhow::Result<SeedMap> {
+    let seeds = match args.io_args.seeds_input_path {
+        Some(ref path) => {
+            let mut seeds: SeedMap = HashMap::new();
+
+            let reader = BufReader::new(std::fs::File::open(path)?);
+            let stream = serde_json::Deserializer::from_reader(reader);
+
+            for entry in stream.into_iter::<SeedMap>() {
+                let entry =
+                    entry.context(format!("failed to read seeds: {}", path.to_string_lossy()))?;
+                seeds.extend(entry);
+            }
+
+            seeds
+        }
+        None => {
+            let now = Instant::now();
+            println!("running mmseqs...");
+            let seeds = match queries {
+                Queries::Sequence(queries) => seed_sequence_to_sequence(queries, targets, args)?,
+                Queries::Profile(queries) => seed_profile_to_sequence(queries, targets, args)?,
+            };
+            stats.set_serial_time(SerialTimed::Seeding, now.elapsed());
+            println!(
+                "\x1b[Arunning mmseqs...           done ({:.2}s)",
+                now.elapsed().as_secs_f64()
+            );
+            seeds
+        }
+    };
+
+    if let Some(ref path) = args.io_args.seeds_output_path {
+        // TODO: don't open with allow_overwrite = true
+        //       after I've updated the open() API
+        let writer = BufWriter::new(path.open(true)?);
+        let mut serializer = serde_json::Serializer::new(writer);
+        seeds.serialize(&mut serializer)?;
+    }
+
+    Ok(seeds)
+}
+
 pub fn search(mut args: SearchArgs) -> anyhow::Result<()> {
     let start_time = Instant::now();
 
@@ -69,7 +151,35 @@ pub fn search(mut args: SearchArgs) -> anyhow::Result<()> {
         args.io_args.seeds_output_path = Some(PathBuf::from_str("./seeds.json")?);
     }
 
-    {
+    if let Some(shard) = args.io_args.shard {
+        // shards get their own temp directory, since they may be run side by side
+        args.io_args.temp_dir_path = args
+            .io_args
+            .temp_dir_path
+            .join(format!("shard_{}_of_{}", shard.index, shard.count));
+    }
+
+    // a resumed search appends to the output of the previous run
+    let resuming = args.io_args.resume && Journal::path(&args).exists();
+
+    if args.io_args.resume && args.io_args.seeds_input_path.is_none() {
+        // the seeds are kept so that a resumed search doesn't need to re-run mmseqs
+        let seeds_path = args
+            .io_args
+            .seeds_output_path
+            .clone()
+            .unwrap_or_else(|| args.io_args.temp_dir_path.join("seeds.json"));
+
+        if resuming && seeds_path.exists() {
+            args.io_args.seeds_input_path = Some(seeds_path);
+            args.io_args.seeds_output_path = None;
+        } else {
+            std::fs::create_dir_all(&args.io_args.temp_dir_path)?;
+            args.io_args.seeds_output_path = Some(seeds_path);
+        }
+    }
+
+    if !resuming {
         // quickly make sure we can write to all of the results paths
         if let Some(path) = &args.io_args.tbl_results_path {
             path.open(args.io_args.allow_overwrite)?;
@@ -79,113 +189,124 @@ pub fn search(mut args: SearchArgs) -> anyhow::Result<()> {
             path.open(args.io_args.allow_overwrite)?;
         }
 
+        if let Some(path) = &args.io_args.json_results_path {
+            path.open(args.io_args.allow_overwrite)?;
+        }
+
+        if let Some(path) = &args.io_args.sam_results_path {
+            path.open(args.io_args.allow_overwrite)?;
+        }
+
+        if let Some(path) = &args.io_args.bam_results_path {
+            path.open(args.io_args.allow_overwrite)?;
+        }
+
         if let Some(path) = &args.io_args.seeds_output_path {
             path.open(args.io_args.allow_overwrite)?;
         }
 
-        if let Some(path) = &args.dev_args.stats_results_path {
+        if let Some(path) = &args.io_args.diagnostics_path {
             path.open(args.io_args.allow_overwrite)?;
         }
     }
 
     let now = Instant::now();
     println!("reading query database...");
-    let queries = read_queries(&args.query_path)?;
+    let mut queries = read_queries(&args.query_path)?;
     println!(
         "\x1b[Areading query database...   done ({:.2}s)",
         now.elapsed().as_secs_f64()
     );
 
+    let sequence_profile_params = SequenceProfileParams::new(&args, &queries)?;
+
     let now = Instant::now();
     println!("indexing target database...");
-    let targets = Fasta::from_path(&args.target_path).context("failed to read target fasta")?;
+    let mut targets = Fasta::from_path(&args.target_path).context("failed to read target fasta")?;
     println!(
         "\x1b[Aindexing target database... done ({:.2}s)",
         now.elapsed().as_secs_f64()
     );
 
-    let mut stats = Stats::new(&queries, &targets);
-
+    // E-values are computed with the size of the full
+    // target database, even if only a shard is searched
     match args.expert_args.target_database_size {
         Some(_) => {}
         None => args.expert_args.target_database_size = Some(targets.len()),
     }
 
-    let seeds = match args.io_args.seeds_input_path {
-        Some(ref path) => {
-            let mut seeds: SeedMap = HashMap::new();
+    if let Some(shard) = args.io_args.shard {
+        std::fs::create_dir_all(&args.io_args.temp_dir_path)?;
 
-            let reader = BufReader::new(std::fs::File::open(path)?);
-            let stream = serde_json::Deserializer::from_reader(reader);
-
-            for entry in stream.into_iter::<SeedMap>() {
-                let entry = entry?;
-                seeds.extend(entry);
+        match args.io_args.shard_by {
+            ShardBy::Queries => {
+                queries = shard_queries(queries, &shard, &args.io_args.temp_dir_path)
+                    .context("failed to shard query database")?;
+            }
+            ShardBy::Targets => {
+                let path = args.io_args.temp_dir_path.join("targets.fasta");
+                targets = shard_fasta(&targets, &shard, &path)
+                    .context("failed to shard target database")?;
             }
-
-            seeds
-        }
-        None => {
-            let now = Instant::now();
-            println!("running mmseqs...");
-            let seeds = match queries {
-                Queries::Sequence(ref queries) => {
-                    seed_sequence_to_sequence(queries, &targets, &args)?
-                }
-                Queries::Profile(ref queries) => {
-                    seed_profile_to_sequence(queries, &targets, &args)?
-                }
-            };
-            stats.set_serial_time(SerialTimed::Seeding, now.elapsed());
-            println!(
-                "\x1b[Arunning mmseqs...           done ({:.2}s)",
-                now.elapsed().as_secs_f64()
-            );
-            seeds
         }
-    };
-
-    if let Some(ref path) = args.io_args.seeds_output_path {
-        // TODO: don't open with allow_overwrite = true
-        //       after I've updated the open() API
-        let writer = BufWriter::new(path.open(true)?);
-        let mut serializer = serde_json::Serializer::new(writer);
-        seeds.serialize(&mut serializer)?;
     }
 
+    let mut stats = Stats::new(&queries, &targets);
+
+    let seeds = seed(&queries, &targets, &args, &mut stats)?;
+
     if args.pipeline_args.only_seed {
         return Ok(());
     }
 
     let mut pipeline = Pipeline {
-        targets,
         seed: Box::new(DefaultSeedStage::new(seeds)),
-        cloud_search: match args.dev_args.full_dp {
-            true => Box::<FullDpCloudSearchStage>::default(),
-            false => Box::new(DefaultCloudSearchStage::new(&args)),
-        },
-        align: Box::new(
-            DefaultAlignStage::new(&args).context("failed to create DefaultAlignStage")?,
+        stages: Arc::new(
+            AlignmentStages::new(
+                Box::new(alignment_targets(&targets, &args, "targets.masked.fasta")?),
+                &args.search_params(),
+            )
+            .context("failed to create alignment stages")?,
         ),
         output: OutputStage::new(&args).context("failed to create OutputStage")?,
         stats,
     };
 
+    pipeline
+        .output
+        .write_sam_header(pipeline.stages.targets.as_ref())
+        .context("failed to write SAM header")?;
+
+    pipeline
+        .output
+        .fix_table_widths(&queries, pipeline.stages.targets.as_ref());
+
     println!("running nail pipeline...");
     let align_timer = Instant::now();
     match queries {
         Queries::Sequence(queries) => {
-            run_pipeline_sequence_to_sequence(&queries, &mut pipeline);
+            run_pipeline_sequence_to_sequence(&queries, &sequence_profile_params, &mut pipeline)?;
         }
-        Queries::Profile(mut queries) => {
-            run_pipeline_profile_to_sequence(&mut queries, &mut pipeline);
+        Queries::Profile(queries) => {
+            run_pipeline_profile_to_sequence(&queries, &mut pipeline)?;
         }
     }
 
+    pipeline
+        .output
+        .finish()
+        .context("failed to finish writing output")?;
+
     pipeline
         .stats
         .set_serial_time(SerialTimed::Alignment, align_timer.elapsed());
 
+    if let Some(ref cache) = sequence_profile_params.cache {
+        pipeline
+            .stats
+            .add_count(CountedValue::ProfileCacheHits, cache.hits());
+    }
+
     println!(
         "\x1b[Arunning nail pipeline...    done ({:.2}s)\n",
         align_timer.elapsed().as_secs_f64()
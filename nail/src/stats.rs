@@ -2,6 +2,7 @@ use libnail::{
     align::structs::{
         Alignment, Boundaries, CellStats, DisplayStrings, DpMatrixSparse, RowBounds, Scores,
     },
+    io::Fasta,
     structs::{hmm::Alphabet, Profile, Sequence},
 };
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
@@ -19,7 +20,6 @@ use anyhow::anyhow;
 use strum::{EnumCount, EnumIter, IntoEnumIterator};
 
 use crate::{
-    io::Fasta,
     pipeline::{
         OutputStageStats, PipelineResult,
         StageResult::{Filtered, Passed},
@@ -206,6 +206,7 @@ pub enum ThreadedTimed {
     Total,
     MemoryInit,
     HmmBuild,
+    BiasFilter,
     CloudSearch,
     Forward,
     Backward,
@@ -224,6 +225,7 @@ impl Debug for ThreadedTimed {
             ThreadedTimed::OutputMutex => "output (mutex)",
             ThreadedTimed::MemoryInit => "memory init",
             ThreadedTimed::HmmBuild => "hmm build",
+            ThreadedTimed::BiasFilter => "bias filter",
             ThreadedTimed::CloudSearch => "cloud search",
             ThreadedTimed::Forward => "forward",
             ThreadedTimed::Backward => "backward",
@@ -261,8 +263,12 @@ impl Debug for ComputedValue {
 #[repr(usize)]
 #[derive(Clone, Copy, EnumIter, EnumCount)]
 pub enum CountedValue {
+    ProfileCacheHits,
     Seeds,
+    PassedBias,
     PassedCloud,
+    TrimFallbacks,
+    RoutedFullDp,
     PassedForward,
     SeedCells,
     CloudForwardCells,
@@ -274,8 +280,12 @@ pub enum CountedValue {
 impl Debug for CountedValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match self {
+            CountedValue::ProfileCacheHits => "query profiles loaded from cache",
             CountedValue::Seeds => "passed seed filter",
+            CountedValue::PassedBias => "passed bias filter",
             CountedValue::PassedCloud => "passed cloud filter",
+            CountedValue::TrimFallbacks => "cloud trim fallbacks",
+            CountedValue::RoutedFullDp => "routed to full DP",
             CountedValue::PassedForward => "passed forward filter",
             CountedValue::SeedCells => "total potential seed DP cells",
             CountedValue::CloudForwardCells => "cloud forward DP cells computed",
@@ -339,6 +349,14 @@ impl Stats {
                 result.profile_length * result.target_length,
             );
 
+            if let Some(ref result) = result.bias_filter_result {
+                if let Passed { .. } = result {
+                    self.increment_count(CountedValue::PassedBias);
+                }
+
+                self.add_threaded_time(ThreadedTimed::BiasFilter, result.stats().time);
+            }
+
             if let Some(ref result) = result.cloud_result {
                 match result {
                     Filtered { stats } => {
@@ -352,6 +370,14 @@ impl Stats {
                     Passed { stats, .. } => {
                         self.increment_count(CountedValue::PassedCloud);
 
+                        if stats.trim_fallback.is_some() {
+                            self.increment_count(CountedValue::TrimFallbacks);
+                        }
+
+                        if stats.full_dp_routed {
+                            self.increment_count(CountedValue::RoutedFullDp);
+                        }
+
                         self.add_count(CountedValue::CloudForwardCells, stats.forward_cells);
                         self.add_count(CountedValue::CloudBackwardCells, stats.backward_cells);
 
@@ -594,3 +620,49 @@ impl Stats {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{CloudStageStats, TrimFallback};
+    use libnail::align::structs::Seed;
+
+    #[test]
+    fn test_trim_fallback_count() {
+        let result = |trim_fallback| PipelineResult {
+            profile_name: "query".to_string(),
+            target_name: "target".to_string(),
+            profile_length: 10,
+            target_length: 10,
+            seed: Seed {
+                target_start: 1,
+                target_end: 10,
+                profile_start: 1,
+                profile_end: 10,
+                score: 0.0,
+            },
+            bias_filter_result: None,
+            cloud_result: Some(Passed {
+                data: RowBounds::default(),
+                stats: CloudStageStats {
+                    trim_fallback,
+                    ..Default::default()
+                },
+            }),
+            align_result: None,
+        };
+
+        let mut stats = Stats::default();
+        stats.add_sample(
+            &[
+                result(None),
+                result(Some(TrimFallback::FullDp)),
+                result(Some(TrimFallback::Expanded)),
+            ],
+            &OutputStageStats::default(),
+        );
+
+        assert_eq!(stats.counted_value(CountedValue::PassedCloud), 3);
+        assert_eq!(stats.counted_value(CountedValue::TrimFallbacks), 2);
+    }
+}
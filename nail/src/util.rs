@@ -1,10 +1,10 @@
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter};
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use thiserror::Error;
 
 #[derive(Default, Debug, Clone)]
@@ -91,6 +91,7 @@ impl CommandExt for Command {
 
 pub trait PathBufExt {
     fn open(&self, allow_overwrite: bool) -> anyhow::Result<BufWriter<File>>;
+    fn open_at(&self, offset: u64) -> anyhow::Result<BufWriter<File>>;
     fn remove(&self) -> anyhow::Result<()>;
 }
 
@@ -112,6 +113,30 @@ impl PathBufExt for PathBuf {
         Ok(BufWriter::new(file))
     }
 
+    /// Open a file for writing at an offset, discarding
+    /// anything after it. The file is created if the offset is 0.
+    fn open_at(&self, offset: u64) -> anyhow::Result<BufWriter<File>> {
+        let mut file = File::options()
+            .write(true)
+            .create(offset == 0)
+            .truncate(false)
+            .open(self)
+            .context(format!("failed to open file: {}", self.to_string_lossy()))?;
+
+        let len = file.metadata()?.len();
+        if len < offset {
+            bail!(
+                "file {} is shorter than expected ({len} < {offset} bytes)",
+                self.to_string_lossy()
+            );
+        }
+
+        file.set_len(offset)?;
+        file.seek(SeekFrom::Start(offset))?;
+
+        Ok(BufWriter::new(file))
+    }
+
     fn remove(&self) -> anyhow::Result<()> {
         std::fs::remove_file(self)?;
         Ok(())
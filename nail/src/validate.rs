@@ -0,0 +1,303 @@
+use std::fs::File;
+use std::io::{stdout, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::args::{DecoyArgs, DecoyKind, ValidateArgs};
+use crate::pipeline::{
+    alignment_targets, profile_from_sequence, AlignmentStages, DefaultSeedStage, OutputStage,
+    Pipeline, SequenceProfileParams, StageResult,
+};
+use crate::search::{read_queries, seed, Queries};
+use crate::stats::Stats;
+use crate::util::PathBufExt;
+
+use libnail::{
+    io::{Fasta, SequenceDatabase},
+    structs::{Profile, Sequence},
+};
+
+use anyhow::Context;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+/// The upper edges of the E-value bins; the first bin starts at 0.
+const E_VALUE_BIN_EDGES: [f64; 8] = [1e-6, 1e-5, 1e-4, 1e-3, 1e-2, 1e-1, 1.0, 10.0];
+
+/// The observed and expected number of false positives in an E-value bin.
+struct EValueBin {
+    min: f64,
+    max: f64,
+    expected: f64,
+    observed: usize,
+    cumulative_expected: f64,
+    cumulative_observed: usize,
+    /// Whether the pipeline's filters may have removed
+    /// hits that would have had E-values in this bin
+    censored: bool,
+}
+
+impl EValueBin {
+    const HEADER: &'static str = "evalue_min\tevalue_max\texpected\tobserved\t\
+                                  cumulative_expected\tcumulative_observed\t\
+                                  ratio\tcensored";
+
+    fn ratio(&self) -> Option<f64> {
+        if self.cumulative_expected > 0.0 {
+            Some(self.cumulative_observed as f64 / self.cumulative_expected)
+        } else {
+            None
+        }
+    }
+
+    fn tab_string(&self) -> String {
+        format!(
+            "{:.0e}\t{:.0e}\t{:.4}\t{}\t{:.4}\t{}\t{}\t{}",
+            self.min,
+            self.max,
+            self.expected,
+            self.observed,
+            self.cumulative_expected,
+            self.cumulative_observed,
+            self.ratio().map_or("-".to_string(), |r| format!("{r:.3}")),
+            self.censored as u8,
+        )
+    }
+}
+
+/// Every decoy hit is a false positive, so for each query we expect
+/// E false positives with an E-value at or below E, if E-values are
+/// calibrated for the (decoy) database.
+fn e_value_bins(e_values: &[f64], num_queries: usize, e_value_ceiling: f64) -> Vec<EValueBin> {
+    let mut cumulative_observed = 0;
+
+    E_VALUE_BIN_EDGES
+        .iter()
+        .enumerate()
+        .map(|(idx, &max)| {
+            let min = match idx {
+                0 => 0.0,
+                _ => E_VALUE_BIN_EDGES[idx - 1],
+            };
+
+            let observed = e_values
+                .iter()
+                .filter(|&&e| match idx {
+                    0 => e <= max,
+                    _ => e > min && e <= max,
+                })
+                .count();
+            cumulative_observed += observed;
+
+            EValueBin {
+                min,
+                max,
+                expected: num_queries as f64 * (max - min),
+                observed,
+                cumulative_expected: num_queries as f64 * max,
+                cumulative_observed,
+                censored: max > e_value_ceiling,
+            }
+        })
+        .collect()
+}
+
+/// Write decoys generated from each target sequence to a fasta file.
+fn write_decoys(targets: &Fasta, args: &DecoyArgs, path: &Path) -> anyhow::Result<()> {
+    let composition = match args.decoy_kind {
+        DecoyKind::Random => {
+            let mut counts = [0usize; 20];
+            targets.iter().for_each(|target| {
+                target.digital_bytes[1..]
+                    .iter()
+                    .filter(|&&b| b < 20)
+                    .for_each(|&b| counts[b as usize] += 1)
+            });
+
+            let total = counts.iter().sum::<usize>().max(1) as f32;
+            counts.map(|c| c as f32 / total)
+        }
+        _ => [0.0; 20],
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut rng = Pcg64::seed_from_u64(args.decoy_seed);
+
+    for target in targets.iter() {
+        for copy in 0..args.decoy_copies {
+            let mut decoy = match args.decoy_kind {
+                DecoyKind::Shuffle => target.shuffled(&mut rng),
+                DecoyKind::Reverse => target.reversed(),
+                DecoyKind::Random => {
+                    Sequence::random_amino_with_frequencies(target.length, &composition, &mut rng)
+                }
+            };
+
+            decoy.name = format!("decoy{copy}_{}", target.name);
+            decoy.details = None;
+
+            writeln!(writer, "{decoy}")?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn run_validate(profiles: &[Profile], pipeline: &Pipeline) -> Vec<f64> {
+    profiles
+        .par_iter()
+        .panic_fuse()
+        .map_init(
+            || pipeline.clone(),
+            |pipeline, profile| {
+                pipeline
+                    .align(profile)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|r| match r.align_result {
+                        Some(StageResult::Passed { data: ali, .. }) => Some(ali.scores.e_value),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            },
+        )
+        .flatten_iter()
+        .collect()
+}
+
+pub fn validate(mut args: ValidateArgs) -> anyhow::Result<()> {
+    let start_time = Instant::now();
+
+    // validation collects pipeline results directly,
+    // so none of the search output writers are used
+    let search_args = &mut args.search_args;
+    search_args.ali_to_stdout = false;
+    search_args.io_args.tbl_results_path = None;
+    search_args.io_args.ali_results_path = None;
+    search_args.io_args.json_results_path = None;
+    search_args.io_args.sam_results_path = None;
+    search_args.io_args.bam_results_path = None;
+    search_args.io_args.diagnostics_path = None;
+    search_args.io_args.resume = false;
+
+    // pre-computed seeds refer to the real targets, not the decoys
+    search_args.io_args.seeds_input_path = None;
+
+    let mut validate_writer = args
+        .validate_args
+        .validate_results_path
+        .open(search_args.io_args.allow_overwrite)?;
+
+    let now = Instant::now();
+    println!("reading query database...");
+    let queries = read_queries(&search_args.query_path)?;
+    println!(
+        "\x1b[Areading query database...   done ({:.2}s)",
+        now.elapsed().as_secs_f64()
+    );
+
+    let sequence_profile_params = SequenceProfileParams::new(search_args, &queries)?;
+
+    let now = Instant::now();
+    println!("generating decoy database...");
+    let targets =
+        Fasta::from_path(&search_args.target_path).context("failed to read target fasta")?;
+    let decoys_path = search_args.io_args.temp_dir_path.join("decoys.fasta");
+    write_decoys(&targets, &args.validate_args, &decoys_path)?;
+    let decoys = Fasta::from_path(&decoys_path).context("failed to read decoy fasta")?;
+    println!(
+        "\x1b[Agenerating decoy database... done ({:.2}s)",
+        now.elapsed().as_secs_f64()
+    );
+
+    let mut stats = Stats::new(&queries, &decoys);
+
+    if search_args.expert_args.target_database_size.is_none() {
+        search_args.expert_args.target_database_size = Some(decoys.len());
+    }
+
+    let seeds = seed(&queries, &decoys, search_args, &mut stats)?;
+
+    let num_queries = queries.len();
+    let profiles: Vec<Profile> = match queries {
+        Queries::Sequence(queries) => queries
+            .par_iter()
+            .map(|sequence| profile_from_sequence(&sequence, &sequence_profile_params))
+            .collect::<anyhow::Result<_>>()
+            .context("failed to build profile from sequence")?,
+        Queries::Profile(queries) => queries,
+    };
+
+    let pipeline = Pipeline {
+        seed: Box::new(DefaultSeedStage::new(seeds)),
+        stages: Arc::new(
+            AlignmentStages::new(
+                Box::new(alignment_targets(
+                    &decoys,
+                    search_args,
+                    "decoys.masked.fasta",
+                )?),
+                &search_args.search_params(),
+            )
+            .context("failed to create alignment stages")?,
+        ),
+        output: OutputStage::new(search_args).context("failed to create OutputStage")?,
+        stats,
+    };
+
+    println!("running nail pipeline...");
+    let now = Instant::now();
+    let e_values = run_validate(&profiles, &pipeline);
+    println!(
+        "\x1b[Arunning nail pipeline...    done ({:.2}s)\n",
+        now.elapsed().as_secs_f64()
+    );
+
+    // hits with E-values above this may have been removed by a filter
+    let pipeline_args = &search_args.pipeline_args;
+    let e_value_ceiling = search_args.expert_args.target_database_size.unwrap_or(0) as f64
+        * pipeline_args
+            .seed_pvalue_threshold
+            .min(pipeline_args.cloud_pvalue_threshold)
+            .min(pipeline_args.forward_pvalue_threshold);
+
+    let bins = e_value_bins(&e_values, num_queries, e_value_ceiling);
+
+    writeln!(validate_writer, "{}", EValueBin::HEADER)?;
+    bins.iter()
+        .try_for_each(|b| writeln!(validate_writer, "{}", b.tab_string()))
+        .context("failed to write validation results")?;
+
+    let mut out = stdout();
+    writeln!(out, "E-value validation ({num_queries} queries)")?;
+    writeln!(
+        out,
+        "  {:>8}  {:>10}  {:>8}  {:>7}",
+        "E <=", "expected", "observed", "ratio"
+    )?;
+    bins.iter().try_for_each(|b| {
+        writeln!(
+            out,
+            "  {:>8.0e}  {:>10.3}  {:>8}  {:>7}{}",
+            b.max,
+            b.cumulative_expected,
+            b.cumulative_observed,
+            b.ratio().map_or("-".to_string(), |r| format!("{r:.3}")),
+            if b.censored { "  (filtered)" } else { "" }
+        )
+    })?;
+
+    println!(
+        "\ntotal validation time: {:.2}s",
+        start_time.elapsed().as_secs_f64()
+    );
+
+    Ok(())
+}